@@ -0,0 +1,12 @@
+CREATE TABLE labels
+(
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE todo_labels
+(
+    todo_id INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    label_id INTEGER NOT NULL REFERENCES labels (id) ON DELETE CASCADE,
+    PRIMARY KEY (todo_id, label_id)
+);
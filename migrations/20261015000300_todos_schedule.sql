@@ -0,0 +1,11 @@
+CREATE TYPE priority AS ENUM ('low', 'normal', 'high', 'urgent');
+
+ALTER TABLE todos
+    ADD COLUMN priority priority NOT NULL DEFAULT 'normal',
+    ADD COLUMN due_at TIMESTAMPTZ,
+    ADD COLUMN completed_at TIMESTAMPTZ,
+    ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+UPDATE todos SET updated_at = created_at, completed_at = CASE WHEN completed THEN created_at END;
+
+CREATE INDEX todos_due_at_idx ON todos (due_at) WHERE NOT completed;
@@ -0,0 +1,13 @@
+CREATE TABLE projects
+(
+    id         SERIAL PRIMARY KEY,
+    owner_id   INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name       TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (owner_id, name)
+);
+
+-- A todo without a project lives in the inbox.
+ALTER TABLE todos ADD COLUMN project_id INTEGER REFERENCES projects (id) ON DELETE SET NULL;
+
+CREATE INDEX todos_project_id_idx ON todos (project_id);
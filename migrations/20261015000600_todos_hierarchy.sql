@@ -0,0 +1,5 @@
+ALTER TABLE todos
+    ADD COLUMN parent_id INTEGER REFERENCES todos (id) ON DELETE CASCADE,
+    ADD COLUMN auto_complete BOOLEAN NOT NULL DEFAULT false;
+
+CREATE INDEX todos_parent_id_idx ON todos (parent_id);
@@ -0,0 +1,4 @@
+ALTER TABLE todos
+    ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (to_tsvector('english', text)) STORED;
+
+CREATE INDEX todos_search_vector_idx ON todos USING GIN (search_vector);
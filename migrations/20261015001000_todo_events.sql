@@ -0,0 +1,27 @@
+CREATE TYPE todo_action AS ENUM ('create', 'update', 'delete', 'restore', 'purge');
+
+-- Snapshots of a todo before and after every change. There is no foreign key to
+-- todos so the history outlives purged todos.
+CREATE TABLE todo_events
+(
+    id         SERIAL PRIMARY KEY,
+    todo_id    INTEGER     NOT NULL,
+    actor_id   INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    action     todo_action NOT NULL,
+    before     JSONB,
+    after      JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX todo_events_todo_id_idx ON todo_events (todo_id, id);
+CREATE INDEX todo_events_actor_id_idx ON todo_events (actor_id, id);
+
+CREATE FUNCTION todo_events_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'todo_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER todo_events_append_only
+    BEFORE UPDATE ON todo_events
+    FOR EACH ROW EXECUTE FUNCTION todo_events_append_only();
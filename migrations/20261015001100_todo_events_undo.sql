@@ -0,0 +1,5 @@
+ALTER TYPE todo_action ADD VALUE 'undo';
+ALTER TYPE todo_action ADD VALUE 'redo';
+
+-- For `undo` and `redo`, the event whose change was taken back or applied again.
+ALTER TABLE todo_events ADD COLUMN reverts INTEGER;
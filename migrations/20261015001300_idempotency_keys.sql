@@ -0,0 +1,14 @@
+CREATE TABLE idempotency_keys
+(
+    owner_id     INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    key          TEXT        NOT NULL,
+    request_hash TEXT        NOT NULL,
+    -- Both empty while the first request with the key is still running.
+    status       INTEGER,
+    body         JSONB,
+    created_at   TIMESTAMPTZ NOT NULL,
+    expires_at   TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (owner_id, key)
+);
+
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
@@ -0,0 +1,32 @@
+CREATE TABLE webhooks
+(
+    id         SERIAL PRIMARY KEY,
+    owner_id   INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    url        TEXT        NOT NULL,
+    secret     TEXT        NOT NULL,
+    -- JSON array of the todo events to deliver: created, updated and deleted.
+    events     JSONB       NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX webhooks_owner_id_idx ON webhooks (owner_id);
+
+-- One row per event sent to a webhook, kept as its delivery log. Pending
+-- deliveries are retried until they are delivered or given up on as dead.
+CREATE TABLE webhook_deliveries
+(
+    id              SERIAL PRIMARY KEY,
+    webhook_id      INTEGER     NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event           TEXT        NOT NULL CHECK (event IN ('created', 'updated', 'deleted')),
+    payload         JSONB       NOT NULL,
+    state           TEXT        NOT NULL CHECK (state IN ('pending', 'delivered', 'dead')),
+    attempts        INTEGER     NOT NULL DEFAULT 0,
+    response_status INTEGER,
+    last_error      TEXT,
+    next_attempt_at TIMESTAMPTZ,
+    created_at      TIMESTAMPTZ NOT NULL,
+    updated_at      TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX webhook_deliveries_webhook_id_idx ON webhook_deliveries (webhook_id, id);
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE state = 'pending';
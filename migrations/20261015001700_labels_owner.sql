@@ -0,0 +1,5 @@
+-- Labels created before they had an owner are not visible to anyone.
+ALTER TABLE labels ADD COLUMN owner_id INTEGER REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE labels DROP CONSTRAINT labels_name_key;
+ALTER TABLE labels ADD CONSTRAINT labels_owner_id_name_key UNIQUE (owner_id, name);
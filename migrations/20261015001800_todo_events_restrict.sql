@@ -0,0 +1,15 @@
+-- The history can neither be rewritten nor deleted, also not along with the
+-- user who made the changes.
+DROP TRIGGER todo_events_append_only ON todo_events;
+
+CREATE TRIGGER todo_events_append_only
+    BEFORE UPDATE OR DELETE ON todo_events
+    FOR EACH ROW EXECUTE FUNCTION todo_events_append_only();
+
+CREATE TRIGGER todo_events_append_only_truncate
+    BEFORE TRUNCATE ON todo_events
+    FOR EACH STATEMENT EXECUTE FUNCTION todo_events_append_only();
+
+ALTER TABLE todo_events DROP CONSTRAINT todo_events_actor_id_fkey;
+ALTER TABLE todo_events ADD CONSTRAINT todo_events_actor_id_fkey
+    FOREIGN KEY (actor_id) REFERENCES users (id) ON DELETE RESTRICT;
@@ -0,0 +1,9 @@
+ALTER TABLE todos ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal'
+    CHECK (priority IN ('low', 'normal', 'high', 'urgent'));
+ALTER TABLE todos ADD COLUMN due_at TEXT;
+ALTER TABLE todos ADD COLUMN completed_at TEXT;
+ALTER TABLE todos ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+
+UPDATE todos SET updated_at = created_at, completed_at = CASE WHEN completed THEN created_at END;
+
+CREATE INDEX todos_due_at_idx ON todos (due_at) WHERE NOT completed;
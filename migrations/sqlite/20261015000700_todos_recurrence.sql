@@ -0,0 +1,2 @@
+-- RRULE text such as 'FREQ=WEEKLY;BYDAY=SA', see src/recurrence.rs.
+ALTER TABLE todos ADD COLUMN recurrence TEXT;
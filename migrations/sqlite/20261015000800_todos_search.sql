@@ -0,0 +1,22 @@
+-- SQLite has no tsvector; an external content FTS5 table mirrors todos.text instead.
+CREATE VIRTUAL TABLE todos_fts USING fts5(
+    text,
+    content = 'todos',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61'
+);
+
+INSERT INTO todos_fts (rowid, text) SELECT id, text FROM todos;
+
+CREATE TRIGGER todos_fts_insert AFTER INSERT ON todos BEGIN
+    INSERT INTO todos_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER todos_fts_delete AFTER DELETE ON todos BEGIN
+    INSERT INTO todos_fts (todos_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+
+CREATE TRIGGER todos_fts_update AFTER UPDATE OF text ON todos BEGIN
+    INSERT INTO todos_fts (todos_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO todos_fts (rowid, text) VALUES (new.id, new.text);
+END;
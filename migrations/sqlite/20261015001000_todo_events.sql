@@ -0,0 +1,21 @@
+-- Snapshots of a todo before and after every change. There is no foreign key to
+-- todos so the history outlives purged todos.
+CREATE TABLE todo_events
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL,
+    actor_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete', 'restore', 'purge')),
+    before TEXT,
+    after TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX todo_events_todo_id_idx ON todo_events (todo_id, id);
+CREATE INDEX todo_events_actor_id_idx ON todo_events (actor_id, id);
+
+CREATE TRIGGER todo_events_append_only
+    BEFORE UPDATE ON todo_events
+BEGIN
+    SELECT RAISE(ABORT, 'todo_events is append-only');
+END;
@@ -0,0 +1,30 @@
+-- SQLite cannot change the CHECK constraint of a column, so the table is rebuilt.
+CREATE TABLE todo_events_undo
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    todo_id INTEGER NOT NULL,
+    actor_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    action TEXT NOT NULL CHECK (
+        action IN ('create', 'update', 'delete', 'restore', 'purge', 'undo', 'redo')
+    ),
+    before TEXT,
+    after TEXT,
+    created_at TEXT NOT NULL,
+    -- For `undo` and `redo`, the event whose change was taken back or applied again.
+    reverts INTEGER
+);
+
+INSERT INTO todo_events_undo (id, todo_id, actor_id, action, before, after, created_at)
+SELECT id, todo_id, actor_id, action, before, after, created_at FROM todo_events;
+
+DROP TABLE todo_events;
+ALTER TABLE todo_events_undo RENAME TO todo_events;
+
+CREATE INDEX todo_events_todo_id_idx ON todo_events (todo_id, id);
+CREATE INDEX todo_events_actor_id_idx ON todo_events (actor_id, id);
+
+CREATE TRIGGER todo_events_append_only
+    BEFORE UPDATE ON todo_events
+BEGIN
+    SELECT RAISE(ABORT, 'todo_events is append-only');
+END;
@@ -0,0 +1,2 @@
+-- Goes up with every change to the todo and is served as its `ETag`.
+ALTER TABLE todos ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,7 @@
+-- One secret per user for subscribing to `/calendar.ics` without logging in.
+CREATE TABLE calendar_tokens
+(
+    user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
@@ -0,0 +1,10 @@
+-- Todos created over CalDAV keep the resource name and UID the client chose.
+-- Todos without a row here are served as `todo-{id}.ics`.
+CREATE TABLE calendar_objects
+(
+    owner_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    todo_id INTEGER NOT NULL UNIQUE REFERENCES todos (id) ON DELETE CASCADE,
+    uid TEXT NOT NULL,
+    PRIMARY KEY (owner_id, name)
+);
@@ -0,0 +1,20 @@
+-- SQLite cannot drop the UNIQUE constraint on `name`, so the table is rebuilt.
+-- Dropping it cascades to `todo_labels`, whose rows are put back afterwards.
+-- Labels created before they had an owner are not visible to anyone.
+CREATE TABLE labels_owner
+(
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    UNIQUE (owner_id, name)
+);
+
+INSERT INTO labels_owner (id, name) SELECT id, name FROM labels;
+
+CREATE TEMP TABLE todo_labels_kept AS SELECT todo_id, label_id FROM todo_labels;
+
+DROP TABLE labels;
+ALTER TABLE labels_owner RENAME TO labels;
+
+INSERT INTO todo_labels (todo_id, label_id) SELECT todo_id, label_id FROM todo_labels_kept;
+DROP TABLE todo_labels_kept;
@@ -0,0 +1,168 @@
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Argon2,
+};
+use axum::{
+    extract::{Extension, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::handlers::problem;
+use crate::repositories::{
+    user::{User, UserRepository},
+    RepositoryError,
+};
+
+const BASIC_CHALLENGE: &str = r#"Basic realm="todo-rust", charset="UTF-8""#;
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("Invalid email or password")]
+    InvalidCredentials,
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid or expired token")]
+    InvalidToken,
+    #[error("Missing credentials")]
+    MissingCredentials,
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        match self {
+            AuthError::Repository(e) => e.into_response(),
+            _ => {
+                let mut response = problem(StatusCode::UNAUTHORIZED, self.to_string());
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+                response
+            }
+        }
+    }
+}
+
+pub fn hash_password(password: &str) -> Result<String, RepositoryError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .and_then(|hash| Argon2::default().verify_password(password.as_bytes(), &hash))
+        .is_ok()
+}
+
+/// Opaque bearer token handed to the client. Only its hash is stored.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Resolves the bearer token to a user and stores it in the request extensions,
+/// where handlers pick it up with `Extension<User>`.
+pub async fn require_auth<U: UserRepository>(
+    Extension(repository): Extension<Arc<U>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AuthError> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(AuthError::MissingToken)?;
+    let user = repository
+        .find_by_session(&hash_token(token))
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+    req.extensions_mut().insert(user);
+
+    Ok(next.run(req).await)
+}
+
+/// Like `require_auth`, but also takes an email and password over HTTP Basic, the
+/// only login CalDAV clients offer. Failures ask for Basic credentials.
+pub async fn require_dav_auth<U: UserRepository>(
+    Extension(repository): Extension<Arc<U>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    match dav_user(&*repository, req.headers()).await {
+        Ok(user) => {
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        Err(e) => {
+            let mut response = e.into_response();
+            if response.status() == StatusCode::UNAUTHORIZED {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static(BASIC_CHALLENGE),
+                );
+            }
+            response
+        }
+    }
+}
+
+async fn dav_user<U: UserRepository>(
+    repository: &U,
+    headers: &HeaderMap,
+) -> Result<User, AuthError> {
+    let value = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or(AuthError::MissingCredentials)?;
+    if let Some(token) = value.strip_prefix("Bearer ") {
+        return repository
+            .find_by_session(&hash_token(token))
+            .await?
+            .ok_or(AuthError::InvalidToken);
+    }
+    let (email, password) = value
+        .strip_prefix("Basic ")
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| {
+            let (email, password) = decoded.split_once(':')?;
+            Some((email.to_lowercase(), password.to_string()))
+        })
+        .ok_or(AuthError::MissingCredentials)?;
+    let user = repository
+        .find_by_email(&email)
+        .await?
+        .ok_or(AuthError::InvalidCredentials)?;
+    let password_hash = user.password_hash.clone();
+    let verified = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash))
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+    if !verified {
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    Ok(user)
+}
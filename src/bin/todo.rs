@@ -0,0 +1,7 @@
+use clap::Parser;
+use todo_rust::cli::{self, Cli};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    cli::run(Cli::parse(), &mut std::io::stdout().lock()).await
+}
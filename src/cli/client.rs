@@ -0,0 +1,80 @@
+use crate::repositories::todo::{CreateTodo, Todo, TodoPage, UpdateTodo};
+use anyhow::{bail, Context};
+use reqwest::{Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Talks to the todo API on behalf of one user.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    server: String,
+    token: String,
+}
+
+impl Client {
+    pub fn new(server: String, token: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            server: server.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    pub async fn create(&self, payload: &CreateTodo) -> anyhow::Result<Todo> {
+        json(self.request(Method::POST, "/todos").json(payload)).await
+    }
+
+    pub async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        json(self.request(Method::GET, &format!("/todos/{}", id))).await
+    }
+
+    /// `query` takes the parameters of `GET /todos`, e.g. `("completed", "false")`.
+    pub async fn all(&self, query: &[(&str, String)]) -> anyhow::Result<TodoPage> {
+        json(self.request(Method::GET, "/todos").query(query)).await
+    }
+
+    pub async fn update(&self, id: i32, payload: &UpdateTodo) -> anyhow::Result<Todo> {
+        json(
+            self.request(Method::PATCH, &format!("/todos/{}", id))
+                .json(payload),
+        )
+        .await
+    }
+
+    /// Moves the todo to the trash.
+    pub async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        send(self.request(Method::DELETE, &format!("/todos/{}", id))).await?;
+        Ok(())
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{}", self.server, path))
+            .bearer_auth(&self.token)
+    }
+}
+
+async fn json<T: DeserializeOwned>(request: RequestBuilder) -> anyhow::Result<T> {
+    let response = send(request).await?;
+    response
+        .json()
+        .await
+        .context("unexpected response from the server")
+}
+
+// Turns error statuses into errors, with the `detail` of a problem response or
+// the plain text a rejected request is answered with.
+async fn send(request: RequestBuilder) -> anyhow::Result<Response> {
+    let response = request.send().await.context("failed to reach the server")?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let detail = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|problem| problem["detail"].as_str().map(str::to_string))
+        .unwrap_or(body);
+    bail!("{}: {}", status, detail)
+}
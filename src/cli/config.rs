@@ -0,0 +1,80 @@
+use super::output::Format;
+use anyhow::Context;
+use serde::Deserialize;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Settings of the `todo` binary, read from a TOML file such as
+///
+/// ```toml
+/// server = "http://localhost:3000"
+/// token = "token from POST /login"
+/// output = "json"
+/// ```
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub server: Option<String>,
+    pub token: Option<String>,
+    pub output: Option<Format>,
+}
+
+impl Config {
+    /// `TODO_CONFIG`, else `todo/config.toml` below `XDG_CONFIG_HOME` or `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("TODO_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(dir.join("todo").join("config.toml"))
+    }
+
+    /// Reads `path`, or the default file if there is one. Only a file that was
+    /// asked for explicitly has to exist.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+        let source = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&source).with_context(|| format!("invalid config file {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+                server = "http://localhost:3000"
+                token = "secret"
+                output = "json"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                server: Some("http://localhost:3000".to_string()),
+                token: Some("secret".to_string()),
+                output: Some(Format::Json),
+            }
+        );
+
+        let empty: Config = toml::from_str("").unwrap();
+        assert_eq!(empty, Config::default());
+
+        assert!(toml::from_str::<Config>("url = \"http://localhost:3000\"").is_err());
+    }
+}
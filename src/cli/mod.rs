@@ -0,0 +1,324 @@
+pub mod client;
+pub mod config;
+pub mod output;
+
+use crate::repositories::todo::{CreateTodo, Priority, UpdateTodo};
+use anyhow::{bail, Context};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Parser, Subcommand};
+use client::Client;
+use config::Config;
+use output::Format;
+use std::{io::Write, path::PathBuf};
+
+const DEFAULT_SERVER: &str = "http://localhost:3000";
+
+/// Command-line client for the todo API.
+#[derive(Debug, Parser)]
+#[command(name = "todo", version)]
+pub struct Cli {
+    /// Base url of the API, overrides `server` of the config file.
+    #[arg(long, global = true)]
+    server: Option<String>,
+    /// Bearer token from `POST /login`, overrides `token` of the config file.
+    #[arg(long, global = true)]
+    token: Option<String>,
+    /// Output format, overrides `output` of the config file.
+    #[arg(short, long, global = true, value_enum)]
+    output: Option<Format>,
+    /// Config file, `~/.config/todo/config.toml` by default.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Add a todo.
+    Add {
+        text: String,
+        /// low, normal, high or urgent.
+        #[arg(short, long)]
+        priority: Option<Priority>,
+        /// `2026-10-20` (midnight UTC) or an RFC 3339 timestamp.
+        #[arg(short, long, value_parser = parse_due)]
+        due: Option<DateTime<Utc>>,
+        #[arg(long)]
+        project: Option<i32>,
+        /// Label id, can be repeated.
+        #[arg(short, long = "label")]
+        labels: Vec<i32>,
+    },
+    /// List todos, open and done ones unless filtered.
+    Ls {
+        /// Only completed todos.
+        #[arg(long, conflicts_with = "open")]
+        done: bool,
+        /// Only todos still to do.
+        #[arg(long)]
+        open: bool,
+        /// Only todos whose text contains this.
+        #[arg(short, long)]
+        query: Option<String>,
+        #[arg(long)]
+        limit: Option<i64>,
+    },
+    /// Mark a todo as completed.
+    Done { id: i32 },
+    /// Change a todo.
+    Edit {
+        id: i32,
+        #[arg(short, long)]
+        text: Option<String>,
+        /// low, normal, high or urgent.
+        #[arg(short, long)]
+        priority: Option<Priority>,
+        /// `2026-10-20` (midnight UTC) or an RFC 3339 timestamp.
+        #[arg(short, long, value_parser = parse_due, conflicts_with = "no_due")]
+        due: Option<DateTime<Utc>>,
+        /// Remove the due date.
+        #[arg(long)]
+        no_due: bool,
+        /// Mark a completed todo as still to do.
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// Move a todo to the trash.
+    Rm { id: i32 },
+    /// Show a todo in detail.
+    Show { id: i32 },
+}
+
+/// Runs the command line against the server of `--server` or the config file,
+/// writing the result to `out`.
+pub async fn run(cli: Cli, out: &mut impl Write) -> anyhow::Result<()> {
+    let config = Config::load(cli.config.as_deref())?;
+    let server = cli
+        .server
+        .or(config.server)
+        .unwrap_or_else(|| DEFAULT_SERVER.to_string());
+    let token = cli
+        .token
+        .or(config.token)
+        .context("no token, pass --token or set `token` in the config file")?;
+    let format = cli.output.or(config.output).unwrap_or_default();
+
+    execute(&Client::new(server, token), cli.command, format, out).await
+}
+
+async fn execute(
+    client: &Client,
+    command: Command,
+    format: Format,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Add {
+            text,
+            priority,
+            due,
+            project,
+            labels,
+        } => {
+            let mut payload = CreateTodo::new(text, labels);
+            if let Some(priority) = priority {
+                payload = payload.with_priority(priority);
+            }
+            if let Some(due) = due {
+                payload = payload.with_due_at(due);
+            }
+            if let Some(project) = project {
+                payload = payload.in_project(project);
+            }
+            let todo = client.create(&payload).await?;
+            output::todo(out, format, &todo)
+        }
+        Command::Ls {
+            done,
+            open,
+            query,
+            limit,
+        } => {
+            let mut params = vec![];
+            if done || open {
+                params.push(("completed", done.to_string()));
+            }
+            if let Some(query) = query {
+                params.push(("q", query));
+            }
+            if let Some(limit) = limit {
+                params.push(("limit", limit.to_string()));
+            }
+            let page = client.all(&params).await?;
+            output::page(out, format, &page)
+        }
+        Command::Done { id } => {
+            let todo = client.update(id, &UpdateTodo::complete()).await?;
+            output::todo(out, format, &todo)
+        }
+        Command::Edit {
+            id,
+            text,
+            priority,
+            due,
+            no_due,
+            reopen,
+        } => {
+            let mut payload = UpdateTodo::default();
+            if let Some(text) = text {
+                payload = payload.with_text(text);
+            }
+            if let Some(priority) = priority {
+                payload = payload.with_priority(priority);
+            }
+            if due.is_some() || no_due {
+                payload = payload.with_due_at(due);
+            }
+            if reopen {
+                payload = payload.with_completed(false);
+            }
+            if payload == UpdateTodo::default() {
+                bail!("nothing to change, see `todo edit --help`");
+            }
+            let todo = client.update(id, &payload).await?;
+            output::todo(out, format, &todo)
+        }
+        Command::Rm { id } => {
+            client.delete(id).await?;
+            output::message(out, format, &format!("moved todo {} to the trash", id))
+        }
+        Command::Show { id } => {
+            let todo = client.find(id).await?;
+            output::todo(out, format, &todo)
+        }
+    }
+}
+
+fn parse_due(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(due) = DateTime::parse_from_rfc3339(value) {
+        return Ok(due.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(Default::default()).and_utc())
+        .map_err(|_| format!("expected YYYY-MM-DD or RFC 3339, got {}", value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::todo::{Todo, TodoPage},
+        test_utils::{test_app, TEST_TOKEN},
+    };
+    use std::{fs, path::Path};
+
+    // Serves the test app on a free port and writes a config file pointing to it.
+    async fn serve() -> PathBuf {
+        let app = test_app().await.build();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = std::env::temp_dir().join(format!("todo-cli-{}.toml", addr.port()));
+        fs::write(
+            &config,
+            format!("server = \"http://{}\"\ntoken = \"{}\"\n", addr, TEST_TOKEN),
+        )
+        .unwrap();
+        config
+    }
+
+    async fn todo(config: &Path, args: &[&str]) -> anyhow::Result<String> {
+        let args = ["todo", "--config", config.to_str().unwrap()]
+            .into_iter()
+            .chain(args.iter().copied());
+        let mut out = vec![];
+        run(Cli::try_parse_from(args)?, &mut out).await?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_manage_todos_from_the_command_line() {
+        let config = serve().await;
+
+        let added = todo(
+            &config,
+            &[
+                "add",
+                "buy milk",
+                "-p",
+                "high",
+                "--due",
+                "2026-10-20",
+                "-o",
+                "json",
+            ],
+        )
+        .await
+        .unwrap();
+        let added: Todo = serde_json::from_str(&added).unwrap();
+        assert_eq!(added.text(), "buy milk");
+        assert_eq!(added.priority(), Priority::High);
+        assert_eq!(added.due_at(), Some(parse_due("2026-10-20").unwrap()));
+        todo(&config, &["add", "walk the dog"]).await.unwrap();
+
+        let listed = todo(&config, &["ls"]).await.unwrap();
+        let lines: Vec<&str> = listed.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "ID  DONE  PRIORITY  DUE               TEXT");
+        assert!(lines.iter().any(|line| *line
+            == format!(
+                "{:<2}        high      2026-10-20 00:00  buy milk",
+                added.id()
+            )));
+
+        let id = added.id().to_string();
+        todo(&config, &["done", &id]).await.unwrap();
+        todo(
+            &config,
+            &["edit", &id, "--text", "buy oat milk", "--no-due"],
+        )
+        .await
+        .unwrap();
+        let shown: Todo =
+            serde_json::from_str(&todo(&config, &["show", &id, "-o", "json"]).await.unwrap())
+                .unwrap();
+        assert_eq!(shown.text(), "buy oat milk");
+        assert!(shown.completed());
+        assert_eq!(shown.due_at(), None);
+        let details = todo(&config, &["show", &id]).await.unwrap();
+        assert!(details.contains("\ndone      yes\n"), "{}", details);
+
+        let done: TodoPage = serde_json::from_str(
+            &todo(&config, &["ls", "--done", "-o", "json"])
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(done.items(), &[shown]);
+
+        assert_eq!(
+            todo(&config, &["rm", &id]).await.unwrap(),
+            format!("moved todo {} to the trash\n", id)
+        );
+        let missing = todo(&config, &["show", &id]).await.unwrap_err();
+        assert!(missing.to_string().starts_with("404"), "{}", missing);
+        assert!(todo(&config, &["edit", &id]).await.is_err());
+
+        fs::remove_file(config).unwrap();
+    }
+
+    #[test]
+    fn should_parse_due_dates() {
+        assert_eq!(
+            parse_due("2026-10-20").unwrap().to_rfc3339(),
+            "2026-10-20T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_due("2026-10-20T09:30:00+02:00").unwrap().to_rfc3339(),
+            "2026-10-20T07:30:00+00:00"
+        );
+        assert!(parse_due("tomorrow").is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::repositories::todo::{Todo, TodoPage};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Aligned columns for people.
+    #[default]
+    Table,
+    /// The API's own JSON, for scripts.
+    Json,
+}
+
+pub fn page(out: &mut impl Write, format: Format, page: &TodoPage) -> anyhow::Result<()> {
+    if format == Format::Json {
+        return json(out, page);
+    }
+    let mut rows = vec![row(["ID", "DONE", "PRIORITY", "DUE", "TEXT"])];
+    rows.extend(page.items().iter().map(|todo| {
+        row([
+            &todo.id().to_string(),
+            if todo.completed() { "x" } else { "" },
+            todo.priority().name(),
+            &date(todo.due_at()),
+            todo.text(),
+        ])
+    }));
+    table(out, &rows)?;
+    if page.total() > page.items().len() as i64 {
+        writeln!(out, "{} of {} todos", page.items().len(), page.total())?;
+    }
+    Ok(())
+}
+
+pub fn todo(out: &mut impl Write, format: Format, todo: &Todo) -> anyhow::Result<()> {
+    if format == Format::Json {
+        return json(out, todo);
+    }
+    let labels: Vec<&str> = todo
+        .labels()
+        .iter()
+        .map(|label| label.name.as_str())
+        .collect();
+    let rows = [
+        row(["id", &todo.id().to_string()]),
+        row(["text", todo.text()]),
+        row(["done", if todo.completed() { "yes" } else { "no" }]),
+        row(["priority", todo.priority().name()]),
+        row(["due", &date(todo.due_at())]),
+        row([
+            "project",
+            &todo
+                .project_id()
+                .map_or("-".to_string(), |id| id.to_string()),
+        ]),
+        row(["labels", &labels.join(", ")]),
+        row(["version", &todo.version().to_string()]),
+        row(["created", &date(Some(todo.created_at()))]),
+        row(["updated", &date(Some(todo.updated_at()))]),
+    ];
+    table(out, &rows)
+}
+
+pub fn message(out: &mut impl Write, format: Format, message: &str) -> anyhow::Result<()> {
+    if format == Format::Table {
+        writeln!(out, "{}", message)?;
+    }
+    Ok(())
+}
+
+fn json<T: Serialize>(out: &mut impl Write, value: &T) -> anyhow::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn row<const N: usize>(cells: [&str; N]) -> Vec<String> {
+    cells.iter().map(|cell| cell.to_string()).collect()
+}
+
+fn date(date: Option<DateTime<Utc>>) -> String {
+    date.map_or("-".to_string(), |date| {
+        date.format("%Y-%m-%d %H:%M").to_string()
+    })
+}
+
+// Pads every column but the last to its widest cell.
+fn table(out: &mut impl Write, rows: &[Vec<String>]) -> anyhow::Result<()> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,594 @@
+use axum::{
+    extract::Extension,
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use quick_xml::escape::escape;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, sync::Arc};
+
+use super::{calendar::all_todos, validate};
+use crate::ical::{self, VTodo};
+use crate::repositories::{
+    calendar_object::{CalendarObject, CalendarObjectRepository},
+    todo::{CreateTodo, Todo, TodoRepository, UpdateTodo},
+    user::User,
+    RepositoryError,
+};
+use crate::webdav::{self, DavRequest, Multistatus, Name, CALDAV, CALENDARSERVER, DAV};
+
+const ROOT: &str = "/dav/";
+const PRINCIPAL: &str = "/dav/principal/";
+const HOME: &str = "/dav/calendars/";
+const COLLECTION: &str = "/dav/calendars/todos/";
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, REPORT";
+const ICS: &str = "text/calendar; charset=utf-8";
+const PRIVILEGES: &str = "<d:privilege><d:read/></d:privilege>\
+                          <d:privilege><d:write/></d:privilege>\
+                          <d:privilege><d:write-content/></d:privilege>\
+                          <d:privilege><d:bind/></d:privilege>\
+                          <d:privilege><d:unbind/></d:privilege>";
+const SUPPORTED_REPORTS: &str =
+    "<d:supported-report><d:report><c:calendar-query/></d:report></d:supported-report>\
+     <d:supported-report><d:report><c:calendar-multiget/></d:report></d:supported-report>";
+
+/// The CalDAV tree of a user: a principal with a single calendar, which holds
+/// their todos.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resource {
+    Root,
+    Principal,
+    Home,
+    Collection,
+    Object(String),
+}
+
+impl Resource {
+    /// Takes absolute URLs too, as clients may send them in `DAV:href`.
+    fn from_href(href: &str) -> Option<Self> {
+        match path_of(href)?.as_str() {
+            "/dav" | ROOT => Some(Resource::Root),
+            "/dav/principal" | PRINCIPAL => Some(Resource::Principal),
+            "/dav/calendars" | HOME => Some(Resource::Home),
+            "/dav/calendars/todos" | COLLECTION => Some(Resource::Collection),
+            path => {
+                let name = path.strip_prefix(COLLECTION)?;
+                (name.ends_with(".ics") && !name.contains('/'))
+                    .then(|| Resource::Object(name.to_string()))
+            }
+        }
+    }
+
+    fn path(&self) -> String {
+        match self {
+            Resource::Root => ROOT.to_string(),
+            Resource::Principal => PRINCIPAL.to_string(),
+            Resource::Home => HOME.to_string(),
+            Resource::Collection => COLLECTION.to_string(),
+            Resource::Object(name) => format!("{}{}", COLLECTION, name),
+        }
+    }
+}
+
+fn path_of(href: &str) -> Option<String> {
+    let path = match href.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => href,
+    };
+    webdav::decode_path(path)
+}
+
+/// A todo as CalDAV clients see it.
+struct Entry {
+    name: String,
+    uid: String,
+    todo: Todo,
+}
+
+// Todos keep the name and UID of the client that created them, the others are
+// `todo-{id}.ics`.
+async fn entries<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    owner_id: i32,
+) -> Result<Vec<Entry>, RepositoryError> {
+    let mut named: HashMap<i32, CalendarObject> = objects
+        .all(owner_id)
+        .await?
+        .into_iter()
+        .map(|object| (object.todo_id, object))
+        .collect();
+    let entries = all_todos(todos, owner_id)
+        .await?
+        .into_iter()
+        .map(|todo| match named.remove(&todo.id()) {
+            Some(object) => Entry {
+                name: object.name,
+                uid: object.uid,
+                todo,
+            },
+            None => Entry {
+                name: format!("todo-{}.ics", todo.id()),
+                uid: ical::uid(todo.id()),
+                todo,
+            },
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+// The todo served as `name`, looked up on its own so that requests on a single
+// object do not read the whole calendar.
+async fn entry<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    owner_id: i32,
+    name: &str,
+) -> Result<Option<Entry>, RepositoryError> {
+    let (todo_id, uid) = match objects.find(owner_id, name).await? {
+        Some(object) => (object.todo_id, object.uid),
+        None => {
+            let Some(id) = default_id(name) else {
+                return Ok(None);
+            };
+            // A todo named by its client is not served as `todo-{id}.ics` too.
+            if objects.find_by_todo(owner_id, id).await?.is_some() {
+                return Ok(None);
+            }
+            (id, ical::uid(id))
+        }
+    };
+    match todos.find(owner_id, todo_id).await {
+        Ok(todo) => Ok(Some(Entry {
+            name: name.to_string(),
+            uid,
+            todo,
+        })),
+        Err(RepositoryError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn default_id(name: &str) -> Option<i32> {
+    let id = name
+        .strip_prefix("todo-")?
+        .strip_suffix(".ics")?
+        .parse()
+        .ok()?;
+    (name == format!("todo-{}.ics", id)).then_some(id)
+}
+
+// Bumped by every update, so it tells clients which todos to fetch again.
+fn etag(todo: &Todo) -> String {
+    format!("\"{}-{}\"", todo.id(), todo.version())
+}
+
+// Changes whenever a todo is added, edited or deleted, so clients can skip syncing
+// an unchanged calendar.
+fn ctag(entries: &[Entry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        hasher.update(format!("{}:{}\n", entry.name, etag(&entry.todo)));
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Serves the CalDAV calendar of the user under `/dav`: `PROPFIND` to discover it,
+/// `REPORT` to sync it and `GET`, `PUT` and `DELETE` on its `.ics` resources. There
+/// are no routing helpers for the WebDAV methods, so they are told apart here.
+pub async fn caldav<T: TodoRepository, D: CalendarObjectRepository>(
+    Extension(todos): Extension<Arc<T>>,
+    Extension(objects): Extension<Arc<D>>,
+    Extension(user): Extension<User>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, RepositoryError> {
+    let Some(resource) = Resource::from_href(uri.path()) else {
+        return Ok(not_found());
+    };
+    match method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "PROPFIND" => propfind(&*todos, &*objects, &user, resource, &headers, &body).await,
+        "PROPPATCH" => proppatch(resource, &body),
+        "REPORT" => report(&*todos, &*objects, &user, resource, &body).await,
+        "GET" | "HEAD" => get(&*todos, &*objects, &user, resource).await,
+        "PUT" => put(&*todos, &*objects, &user, resource, &headers, &body).await,
+        "DELETE" => delete(&*todos, &*objects, &user, resource, &headers).await,
+        _ => Ok(method_not_allowed()),
+    }
+}
+
+/// Where clients that were only given the host name look for the calendar, RFC
+/// 6764.
+pub async fn well_known() -> impl IntoResponse {
+    (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, ROOT)])
+}
+
+fn options() -> Response {
+    (
+        [
+            (
+                header::HeaderName::from_static("dav"),
+                "1, 3, calendar-access",
+            ),
+            (header::ALLOW, ALLOW),
+        ],
+        StatusCode::OK,
+    )
+        .into_response()
+}
+
+fn method_not_allowed() -> Response {
+    (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response()
+}
+
+fn not_found() -> Response {
+    StatusCode::NOT_FOUND.into_response()
+}
+
+fn parse(body: &str) -> Result<DavRequest, (StatusCode, String)> {
+    webdav::parse(body).map_err(|e| {
+        let message = format!("Xml parse error: [{}]", e);
+        (StatusCode::BAD_REQUEST, message)
+    })
+}
+
+async fn propfind<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    user: &User,
+    resource: Resource,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, RepositoryError> {
+    let request = match parse(body) {
+        Ok(request) => request,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    // Only the collection needs all of its todos, for its ctag and its children.
+    let entries = match &resource {
+        Resource::Collection => entries(todos, objects, user.id).await?,
+        Resource::Object(name) => match entry(todos, objects, user.id, name).await? {
+            Some(entry) => vec![entry],
+            None => return Ok(not_found()),
+        },
+        _ => vec![],
+    };
+    // Infinity, the default, is served as 1: the tree is only three levels deep.
+    let depth = headers.get("depth").and_then(|value| value.to_str().ok());
+    let mut resources = vec![resource.clone()];
+    if depth != Some("0") {
+        resources.extend(children(&resource, &entries));
+    }
+
+    let mut multistatus = Multistatus::new();
+    for resource in resources {
+        let Some(props) = props(&resource, user, &entries) else {
+            return Ok(not_found());
+        };
+        multistatus.response(&resource.path(), webdav::propstats(props, &request));
+    }
+    Ok(multistatus.into_response())
+}
+
+fn children(resource: &Resource, entries: &[Entry]) -> Vec<Resource> {
+    match resource {
+        Resource::Root => vec![Resource::Principal, Resource::Home],
+        Resource::Home => vec![Resource::Collection],
+        Resource::Collection => entries
+            .iter()
+            .map(|entry| Resource::Object(entry.name.clone()))
+            .collect(),
+        Resource::Principal | Resource::Object(_) => vec![],
+    }
+}
+
+// The properties of a resource with their XML content, `None` for an unknown
+// object. `calendar-data` only comes with reports.
+fn props(resource: &Resource, user: &User, entries: &[Entry]) -> Option<Vec<(Name, String)>> {
+    let principal = webdav::href(PRINCIPAL);
+    let mut props = vec![(Name::new(DAV, "current-user-principal"), principal.clone())];
+    match resource {
+        Resource::Root => {
+            props.push((
+                Name::new(DAV, "resourcetype"),
+                "<d:collection/>".to_string(),
+            ));
+        }
+        Resource::Principal => props.extend([
+            (Name::new(DAV, "resourcetype"), "<d:principal/>".to_string()),
+            (
+                Name::new(DAV, "displayname"),
+                escape(&user.email).into_owned(),
+            ),
+            (Name::new(DAV, "principal-URL"), principal),
+            (Name::new(CALDAV, "calendar-home-set"), webdav::href(HOME)),
+            (
+                Name::new(CALDAV, "calendar-user-address-set"),
+                format!("<d:href>mailto:{}</d:href>", escape(&user.email)),
+            ),
+        ]),
+        Resource::Home => props.extend([
+            (
+                Name::new(DAV, "resourcetype"),
+                "<d:collection/>".to_string(),
+            ),
+            (Name::new(DAV, "owner"), principal),
+        ]),
+        Resource::Collection => {
+            let ctag = ctag(entries);
+            props.extend([
+                (
+                    Name::new(DAV, "resourcetype"),
+                    "<d:collection/><c:calendar/>".to_string(),
+                ),
+                (Name::new(DAV, "displayname"), "Todos".to_string()),
+                (Name::new(DAV, "owner"), principal),
+                (
+                    Name::new(CALDAV, "supported-calendar-component-set"),
+                    r#"<c:comp name="VTODO"/>"#.to_string(),
+                ),
+                (
+                    Name::new(DAV, "supported-report-set"),
+                    SUPPORTED_REPORTS.to_string(),
+                ),
+                (
+                    Name::new(DAV, "current-user-privilege-set"),
+                    PRIVILEGES.to_string(),
+                ),
+                (Name::new(DAV, "getetag"), format!("&quot;{}&quot;", ctag)),
+                (Name::new(CALENDARSERVER, "getctag"), ctag),
+            ]);
+        }
+        Resource::Object(name) => {
+            let entry = entries.iter().find(|entry| entry.name == *name)?;
+            props.extend(object_props(entry));
+        }
+    }
+    Some(props)
+}
+
+fn object_props(entry: &Entry) -> Vec<(Name, String)> {
+    let modified = entry.todo.updated_at().format("%a, %d %b %Y %H:%M:%S GMT");
+    vec![
+        (Name::new(DAV, "resourcetype"), String::new()),
+        (
+            Name::new(DAV, "getetag"),
+            escape(&etag(&entry.todo)).into_owned(),
+        ),
+        (
+            Name::new(DAV, "getcontenttype"),
+            "text/calendar; charset=utf-8; component=VTODO".to_string(),
+        ),
+        (Name::new(DAV, "getlastmodified"), modified.to_string()),
+        (
+            Name::new(DAV, "current-user-privilege-set"),
+            PRIVILEGES.to_string(),
+        ),
+    ]
+}
+
+// Nothing about the calendar can be changed, not even its name or color.
+fn proppatch(resource: Resource, body: &str) -> Result<Response, RepositoryError> {
+    let request = match parse(body) {
+        Ok(request) => request,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    let props = request
+        .props
+        .into_iter()
+        .map(|name| (name, String::new()))
+        .collect();
+    let mut multistatus = Multistatus::new();
+    multistatus.response(&resource.path(), vec![(StatusCode::FORBIDDEN, props)]);
+    Ok(multistatus.into_response())
+}
+
+async fn report<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    user: &User,
+    resource: Resource,
+    body: &str,
+) -> Result<Response, RepositoryError> {
+    let request = match parse(body) {
+        Ok(request) => request,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    let unsupported = || webdav::error(StatusCode::FORBIDDEN, Name::new(DAV, "supported-report"));
+    if resource != Resource::Collection {
+        return Ok(unsupported());
+    }
+    let entries = entries(todos, objects, user.id).await?;
+    let now = Utc::now();
+    let respond = |multistatus: &mut Multistatus, entry: &Entry| {
+        let mut props = object_props(entry);
+        props.push((
+            Name::new(CALDAV, "calendar-data"),
+            escape(&ical::object(&entry.todo, &entry.uid, now)).into_owned(),
+        ));
+        let path = Resource::Object(entry.name.clone()).path();
+        multistatus.response(&path, webdav::propstats(props, &request));
+    };
+
+    let mut multistatus = Multistatus::new();
+    match &request.root {
+        // Filters are not applied: the calendar holds nothing but todos, and
+        // clients keep the ones they asked for.
+        Some(root) if root.is(CALDAV, "calendar-query") => {
+            for entry in &entries {
+                respond(&mut multistatus, entry);
+            }
+        }
+        Some(root) if root.is(CALDAV, "calendar-multiget") => {
+            for href in &request.hrefs {
+                let entry = match Resource::from_href(href) {
+                    Some(Resource::Object(name)) => entries.iter().find(|e| e.name == name),
+                    _ => None,
+                };
+                match entry {
+                    Some(entry) => respond(&mut multistatus, entry),
+                    None => {
+                        let path = path_of(href).unwrap_or_else(|| href.clone());
+                        multistatus.status(&path, StatusCode::NOT_FOUND);
+                    }
+                }
+            }
+        }
+        _ => return Ok(unsupported()),
+    }
+    Ok(multistatus.into_response())
+}
+
+async fn get<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    user: &User,
+    resource: Resource,
+) -> Result<Response, RepositoryError> {
+    let Resource::Object(name) = resource else {
+        return Ok(method_not_allowed());
+    };
+    let Some(entry) = entry(todos, objects, user.id, &name).await? else {
+        return Ok(not_found());
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, ICS.to_string()),
+            (header::ETAG, etag(&entry.todo)),
+        ],
+        ical::object(&entry.todo, &entry.uid, Utc::now()),
+    )
+        .into_response())
+}
+
+// The version an `If-Match` ETag pins the todo to, or a failed precondition.
+// `If-None-Match: *` only lets new resources through.
+fn precondition(headers: &HeaderMap, existing: Option<&Todo>) -> Result<Option<i32>, StatusCode> {
+    let failed = || StatusCode::PRECONDITION_FAILED;
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if header(header::IF_NONE_MATCH).is_some_and(|value| value.trim() == "*") && existing.is_some()
+    {
+        return Err(failed());
+    }
+    let Some(if_match) = header(header::IF_MATCH).map(str::trim) else {
+        return Ok(None);
+    };
+    // The update itself checks the version, so a stale ETag fails the same way
+    // as a concurrent edit over REST.
+    match existing {
+        Some(_) if if_match == "*" => Ok(None),
+        Some(todo) => if_match
+            .trim_matches('"')
+            .split_once('-')
+            .filter(|(id, _)| *id == todo.id().to_string())
+            .and_then(|(_, version)| version.parse().ok())
+            .map(Some)
+            .ok_or_else(failed),
+        None => Err(failed()),
+    }
+}
+
+async fn put<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    user: &User,
+    resource: Resource,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, RepositoryError> {
+    let Resource::Object(name) = resource else {
+        return Ok(method_not_allowed());
+    };
+    let vtodo = match ical::parse_vtodo(body) {
+        Ok(vtodo) => vtodo,
+        Err(e) => {
+            let message = format!("Calendar parse error: [{}]", e);
+            return Ok((StatusCode::BAD_REQUEST, message).into_response());
+        }
+    };
+    let existing = entry(todos, objects, user.id, &name).await?;
+    let version = match precondition(headers, existing.as_ref().map(|entry| &entry.todo)) {
+        Ok(version) => version,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+
+    match existing {
+        Some(entry) if entry.uid != vtodo.uid => Ok(webdav::error(
+            StatusCode::CONFLICT,
+            Name::new(CALDAV, "no-uid-conflict"),
+        )),
+        Some(entry) => {
+            let payload = update_payload(vtodo);
+            if let Err(rejection) = validate(&payload) {
+                return Ok(rejection.into_response());
+            }
+            let todo = todos
+                .update(user.id, entry.todo.id(), payload, version)
+                .await?;
+            Ok((StatusCode::NO_CONTENT, [(header::ETAG, etag(&todo))]).into_response())
+        }
+        None => {
+            let uid = vtodo.uid.clone();
+            let payload = create_payload(vtodo);
+            if let Err(rejection) = validate(&payload) {
+                return Ok(rejection.into_response());
+            }
+            let todo = objects.create(user.id, name, uid, payload).await?;
+            Ok((StatusCode::CREATED, [(header::ETAG, etag(&todo))]).into_response())
+        }
+    }
+}
+
+// A `VTODO` carries the whole todo, so whatever it leaves out is cleared. Labels,
+// projects and subtasks have no place in it and are kept.
+fn update_payload(vtodo: VTodo) -> UpdateTodo {
+    UpdateTodo::default()
+        .with_text(vtodo.summary)
+        .with_completed(vtodo.completed)
+        .with_priority(vtodo.priority)
+        .with_due_at(vtodo.due_at)
+        .with_recurrence(vtodo.recurrence)
+}
+
+// A todo completed elsewhere comes in completed, without an occurrence after it.
+fn create_payload(vtodo: VTodo) -> CreateTodo {
+    let mut payload = CreateTodo::new(vtodo.summary, vec![]).with_priority(vtodo.priority);
+    if let Some(due_at) = vtodo.due_at {
+        payload = payload.with_due_at(due_at);
+    }
+    if let Some(recurrence) = vtodo.recurrence {
+        payload = payload.with_recurrence(recurrence);
+    }
+    if vtodo.completed {
+        payload = payload.completed_at(Utc::now());
+    }
+    payload
+}
+
+async fn delete<T: TodoRepository, D: CalendarObjectRepository>(
+    todos: &T,
+    objects: &D,
+    user: &User,
+    resource: Resource,
+    headers: &HeaderMap,
+) -> Result<Response, RepositoryError> {
+    let Resource::Object(name) = resource else {
+        return Ok(method_not_allowed());
+    };
+    let Some(entry) = entry(todos, objects, user.id, &name).await? else {
+        return Ok(not_found());
+    };
+    let version = match precondition(headers, Some(&entry.todo)) {
+        Ok(version) => version,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    todos.delete(user.id, entry.todo.id(), version).await?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::Extension,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use super::ValidatedQuery;
+use crate::auth::{self, AuthError};
+use crate::ical;
+use crate::repositories::{
+    todo::{Todo, TodoQuery, TodoRepository},
+    user::{User, UserRepository},
+    RepositoryError,
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct CalendarFeed {
+    /// Shown only this once, a new one replaces it.
+    pub token: String,
+    /// Where to subscribe, relative to the server.
+    #[schema(example = "/calendar.ics?token=5f0c...")]
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CalendarQuery {
+    /// From `POST /calendar/token`.
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/calendar/token",
+    tag = "calendar",
+    responses(
+        (status = 201, description = "A new secret for the calendar feed, replacing the old one", body = CalendarFeed),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn create_calendar_token<U: UserRepository>(
+    Extension(repository): Extension<Arc<U>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let token = auth::generate_token();
+    repository
+        .create_calendar_token(user.id, auth::hash_token(&token))
+        .await?;
+    let feed = CalendarFeed {
+        url: format!("/calendar.ics?token={}", token),
+        token,
+    };
+
+    Ok((StatusCode::CREATED, Json(feed)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/calendar/token",
+    tag = "calendar",
+    responses(
+        (status = 204, description = "The calendar feed can no longer be subscribed to"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn delete_calendar_token<U: UserRepository>(
+    Extension(repository): Extension<Arc<U>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, RepositoryError> {
+    repository.delete_calendar_token(user.id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Every todo as an iCalendar `VTODO`, for calendar apps to subscribe to. The
+/// secret `token` in the URL stands in for a login.
+#[utoipa::path(
+    get,
+    path = "/calendar.ics",
+    tag = "calendar",
+    params(CalendarQuery),
+    responses(
+        (status = 200, description = "The todos as an RFC 5545 calendar", body = String, content_type = "text/calendar"),
+        (status = 400, description = "Missing token", body = String, content_type = "text/plain"),
+        (status = 401, description = "Unknown or revoked token", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(()),
+)]
+pub async fn calendar_feed<T: TodoRepository, U: UserRepository>(
+    Extension(todos): Extension<Arc<T>>,
+    Extension(users): Extension<Arc<U>>,
+    ValidatedQuery(query): ValidatedQuery<CalendarQuery>,
+) -> Result<impl IntoResponse, AuthError> {
+    let user = users
+        .find_by_calendar_token(&auth::hash_token(&query.token))
+        .await?
+        .ok_or(AuthError::InvalidToken)?;
+    let todos = all_todos(&*todos, user.id).await?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        ical::calendar(&todos, Utc::now()),
+    ))
+}
+
+/// Every todo of the owner, oldest first.
+pub async fn all_todos<T: TodoRepository>(
+    repository: &T,
+    owner_id: i32,
+) -> Result<Vec<Todo>, RepositoryError> {
+    let mut todos = vec![];
+    let mut cursor = None;
+    loop {
+        let page = repository.all(owner_id, TodoQuery::by_id(cursor)).await?;
+        todos.extend_from_slice(page.items());
+        cursor = page.next_cursor();
+        if cursor.is_none() {
+            return Ok(todos);
+        }
+    }
+}
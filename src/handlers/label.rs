@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+use super::ValidatedJson;
+use crate::repositories::{
+    label::{CreateLabel, LabelRepository, UpdateLabel},
+    user::User,
+    RepositoryError,
+};
+
+#[utoipa::path(
+    post,
+    path = "/labels",
+    tag = "labels",
+    request_body = CreateLabel,
+    responses(
+        (status = 201, description = "Created", body = Label),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "A label with this name exists", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn create_label<T: LabelRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<CreateLabel>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let label = repository.create(user.id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(label)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/labels/{id}",
+    tag = "labels",
+    params(
+        ("id" = i32, Path, description = "Label id"),
+    ),
+    responses(
+        (status = 200, description = "The label", body = Label),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Label not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn find_label<T: LabelRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let label = repository.find(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(label)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/labels",
+    tag = "labels",
+    responses(
+        (status = 200, description = "All labels of the user", body = [Label]),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_label<T: LabelRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let labels = repository.all(user.id).await?;
+
+    Ok((StatusCode::OK, Json(labels)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/labels/{id}",
+    tag = "labels",
+    params(
+        ("id" = i32, Path, description = "Label id"),
+    ),
+    request_body = UpdateLabel,
+    responses(
+        (status = 201, description = "Updated", body = Label),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Label not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "A label with this name exists", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn update_label<T: LabelRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<UpdateLabel>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let label = repository.update(user.id, id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(label)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/labels/{id}",
+    tag = "labels",
+    params(
+        ("id" = i32, Path, description = "Label id"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Label not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn delete_label<T: LabelRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, RepositoryError> {
+    repository.delete(user.id, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,156 @@
+pub mod caldav;
+pub mod calendar;
+pub mod label;
+pub mod project;
+pub mod todo;
+pub mod user;
+pub mod webhook;
+pub mod ws;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Query, Request},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::repositories::RepositoryError;
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+impl RepositoryError {
+    fn status(&self) -> StatusCode {
+        match self {
+            RepositoryError::NotFound(_) => StatusCode::NOT_FOUND,
+            RepositoryError::VersionMismatch(_) => StatusCode::PRECONDITION_FAILED,
+            RepositoryError::Conflict(_) => StatusCode::CONFLICT,
+            RepositoryError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            RepositoryError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RepositoryError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Server-side failures are logged and only described by their status, so
+    // database internals do not leak to clients.
+    fn detail(&self) -> String {
+        match self {
+            RepositoryError::Unavailable(_) | RepositoryError::Unexpected(_) => {
+                tracing::error!("{}", self);
+                self.status()
+                    .canonical_reason()
+                    .unwrap_or_default()
+                    .to_string()
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for RepositoryError {
+    fn into_response(self) -> Response {
+        problem(self.status(), self.detail())
+    }
+}
+
+/// RFC 7807 problem details, the body of every error response except a rejected
+/// request body or query, which is answered with plain text.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    kind: &'static str,
+    #[schema(example = "Not Found")]
+    title: Option<&'static str>,
+    #[schema(example = 404)]
+    status: u16,
+    #[schema(example = "NotFound, id is 1")]
+    detail: String,
+}
+
+/// Builds an RFC 7807 `application/problem+json` response.
+pub fn problem(status: StatusCode, detail: String) -> Response {
+    problem_with(status, detail, Map::new())
+}
+
+/// Like `problem`, with `extensions` added as extra members of the body.
+pub fn problem_with(
+    status: StatusCode,
+    detail: String,
+    extensions: Map<String, Value>,
+) -> Response {
+    let mut body = json!(Problem {
+        kind: "about:blank",
+        title: status.canonical_reason(),
+        status: status.as_u16(),
+        detail,
+    });
+    if let Value::Object(members) = &mut body {
+        members.extend(extensions);
+    }
+
+    (
+        status,
+        [(header::CONTENT_TYPE, PROBLEM_JSON)],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+#[derive(Debug)]
+pub struct ValidatedJson<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|rejection| {
+                let message = format!("Json parse error: [{}]", rejection);
+                (StatusCode::BAD_REQUEST, message)
+            })?;
+        validate(&value)?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+#[derive(Debug)]
+pub struct ValidatedQuery<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) =
+            Query::<T>::from_request_parts(parts, state)
+                .await
+                .map_err(|rejection| {
+                    let message = format!("Query parse error: [{}]", rejection);
+                    (StatusCode::BAD_REQUEST, message)
+                })?;
+        validate(&value)?;
+        Ok(ValidatedQuery(value))
+    }
+}
+
+fn validate<T: Validate>(value: &T) -> Result<(), (StatusCode, String)> {
+    value.validate().map_err(|rejection| {
+        let message = format!("Validation error: [{}]", rejection).replace('\n', ", ");
+        (StatusCode::BAD_REQUEST, message)
+    })
+}
@@ -0,0 +1,190 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+use super::{ValidatedJson, ValidatedQuery};
+use crate::repositories::{
+    project::{CreateProject, DeleteProjectQuery, ProjectRepository, UpdateProject},
+    todo::{CreateTodo, TodoQuery, TodoRepository},
+    user::User,
+    RepositoryError,
+};
+
+#[utoipa::path(
+    post,
+    path = "/projects",
+    tag = "projects",
+    request_body = CreateProject,
+    responses(
+        (status = 201, description = "Created", body = Project),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "A project with this name exists", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn create_project<P: ProjectRepository>(
+    Extension(repository): Extension<Arc<P>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<CreateProject>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let project = repository.create(user.id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(
+        ("id" = i32, Path, description = "Project id"),
+    ),
+    responses(
+        (status = 200, description = "The project", body = Project),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn find_project<P: ProjectRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let project = repository.find(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(project)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects",
+    tag = "projects",
+    responses(
+        (status = 200, description = "All projects", body = [Project]),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_project<P: ProjectRepository>(
+    Extension(repository): Extension<Arc<P>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let projects = repository.all(user.id).await?;
+
+    Ok((StatusCode::OK, Json(projects)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(
+        ("id" = i32, Path, description = "Project id"),
+    ),
+    request_body = UpdateProject,
+    responses(
+        (status = 201, description = "Updated", body = Project),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "A project with this name exists", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn update_project<P: ProjectRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<UpdateProject>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let project = repository.update(user.id, id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/projects/{id}",
+    tag = "projects",
+    params(
+        ("id" = i32, Path, description = "Project id"),
+        DeleteProjectQuery,
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn delete_project<P: ProjectRepository>(
+    Path(id): Path<i32>,
+    ValidatedQuery(query): ValidatedQuery<DeleteProjectQuery>,
+    Extension(repository): Extension<Arc<P>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, RepositoryError> {
+    repository.delete(user.id, id, query.todos).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/projects/{id}/todos",
+    tag = "projects",
+    params(
+        ("id" = i32, Path, description = "Project id"),
+    ),
+    request_body = CreateTodo,
+    responses(
+        (status = 201, description = "Created in the project", body = Todo),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Unknown parent or label", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn create_project_todo<P: ProjectRepository, T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(projects): Extension<Arc<P>>,
+    Extension(todos): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<CreateTodo>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let project = projects.find(user.id, id).await?;
+    let todo = todos
+        .create(user.id, payload.in_project(project.id))
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/projects/{id}/todos",
+    tag = "projects",
+    params(
+        ("id" = i32, Path, description = "Project id"),
+        TodoQuery,
+    ),
+    responses(
+        (status = 200, description = "One page of the todos in the project", body = TodoPage),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Project not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_project_todo<P: ProjectRepository, T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedQuery(query): ValidatedQuery<TodoQuery>,
+    Extension(projects): Extension<Arc<P>>,
+    Extension(todos): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let project = projects.find(user.id, id).await?;
+    let page = todos.all(user.id, query.in_project(project.id)).await?;
+
+    Ok((StatusCode::OK, Json(page)))
+}
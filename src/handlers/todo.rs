@@ -0,0 +1,695 @@
+use axum::{
+    body::Body,
+    extract::{Extension, Path},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use serde_json::{json, Map, Value};
+use std::{cmp::Ordering, sync::Arc};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use super::{problem_with, ValidatedJson, ValidatedQuery};
+use crate::idempotency;
+use crate::repositories::{
+    changes::{Subscription, TodoChange},
+    idempotency::IdempotencyRepository,
+    label::LabelRepository,
+    project::ProjectRepository,
+    todo::{
+        AuditQuery, BatchError, CreateTodo, FindTodoQuery, Todo, TodoBatch, TodoExpand, TodoQuery,
+        TodoRepository, TodoSearchQuery, UpdateTodo,
+    },
+    user::User,
+    RepositoryError,
+};
+use crate::transfer::{self, ExportQuery, ImportError, ImportQuery, ImportReport, LineError};
+
+#[utoipa::path(
+    post,
+    path = "/todos",
+    tag = "todos",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries of the request safe: a repeat replays the first response"),
+    ),
+    request_body = CreateTodo,
+    responses(
+        (status = 201, description = "Created", body = Todo),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Unknown project, parent or label, or the Idempotency-Key was used for a different request", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn create_todo<T: TodoRepository, I: IdempotencyRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(keys): Extension<Arc<I>>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<CreateTodo>,
+) -> Response {
+    let request_hash = idempotency::request_hash(&payload);
+    idempotency::run_once(&*keys, user.id, &headers, request_hash, || async move {
+        let todo = repository.create(user.id, payload).await?;
+        Ok((StatusCode::CREATED, json!(todo)))
+    })
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+        FindTodoQuery,
+        ("If-None-Match" = Option<String>, Header, description = "Answer with 304 while the `ETag` is one of these"),
+    ),
+    responses(
+        (status = 200, description = "The todo, with its subtree when expanded", body = Todo, headers(("ETag" = String, description = "Version of the todo"))),
+        (status = 304, description = "Not modified", headers(("ETag" = String, description = "Version of the todo"))),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn find_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    ValidatedQuery(query): ValidatedQuery<FindTodoQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+) -> Result<Response, RepositoryError> {
+    let todo = repository.find(user.id, id).await?;
+    // The version does not cover the subtree, so only the todo on its own gets an
+    // `ETag`.
+    if query.expand == Some(TodoExpand::Children) {
+        let descendants = repository.descendants(user.id, id).await?;
+        return Ok((StatusCode::OK, Json(todo.with_subtree(descendants))).into_response());
+    }
+    let unchanged = match entity_tags(&headers, header::IF_NONE_MATCH, true) {
+        Some(EntityTags::Any) => true,
+        Some(EntityTags::Versions(versions)) => versions.contains(&todo.version()),
+        None => false,
+    };
+    if unchanged {
+        return Ok((StatusCode::NOT_MODIFIED, etag(&todo)).into_response());
+    }
+
+    Ok((StatusCode::OK, etag(&todo), Json(todo)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos",
+    tag = "todos",
+    params(
+        TodoQuery,
+    ),
+    responses(
+        (status = 200, description = "One page of todos", body = TodoPage),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_todo<T: TodoRepository>(
+    ValidatedQuery(query): ValidatedQuery<TodoQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let page = repository.all(user.id, query).await?;
+
+    Ok((StatusCode::OK, Json(page)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/search",
+    tag = "todos",
+    params(
+        TodoSearchQuery,
+    ),
+    responses(
+        (status = 200, description = "Matches, best first", body = [TodoSearchHit]),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn search_todo<T: TodoRepository>(
+    ValidatedQuery(query): ValidatedQuery<TodoSearchQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let hits = repository.search(user.id, query).await?;
+
+    Ok((StatusCode::OK, Json(hits)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+        ("If-Match" = Option<String>, Header, description = "Only change the todo while its `ETag` is one of these"),
+    ),
+    request_body = UpdateTodo,
+    responses(
+        (status = 201, description = "Updated", body = Todo, headers(("ETag" = String, description = "Version of the todo"))),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = Problem, content_type = "application/problem+json"),
+        (status = 412, description = "The todo is no longer at the version of `If-Match`", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Unknown project, parent or label, or a parent that would become a descendant of the todo", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn update_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<UpdateTodo>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let version = expected_version(&*repository, user.id, id, &headers).await?;
+    let todo = repository.update(user.id, id, payload, version).await?;
+
+    Ok((StatusCode::CREATED, etag(&todo), Json(todo)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+        ("If-Match" = Option<String>, Header, description = "Only change the todo while its `ETag` is one of these"),
+    ),
+    responses(
+        (status = 204, description = "Moved to the trash"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = Problem, content_type = "application/problem+json"),
+        (status = 412, description = "The todo is no longer at the version of `If-Match`", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn delete_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+) -> Result<StatusCode, RepositoryError> {
+    let version = expected_version(&*repository, user.id, id, &headers).await?;
+    repository.delete(user.id, id, version).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn etag(todo: &Todo) -> [(HeaderName, String); 1] {
+    [(header::ETAG, format!("\"{}\"", todo.version()))]
+}
+
+enum EntityTags {
+    Any,
+    Versions(Vec<i32>),
+}
+
+// Reads an `If-Match` or `If-None-Match` header. Tags that are not one of our
+// versions can never match and are left out; weak ones only count where the
+// comparison is weak.
+fn entity_tags(headers: &HeaderMap, name: HeaderName, weak: bool) -> Option<EntityTags> {
+    let tags: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    if tags.is_empty() {
+        return None;
+    }
+    if tags.contains(&"*") {
+        return Some(EntityTags::Any);
+    }
+    let versions = tags
+        .into_iter()
+        .filter_map(|tag| match tag.strip_prefix("W/") {
+            Some(tag) if weak => Some(tag),
+            Some(_) => None,
+            None => Some(tag),
+        })
+        .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+        .collect();
+    Some(EntityTags::Versions(versions))
+}
+
+// The version `If-Match` holds the todo to, checked by the repository along with
+// the change itself. Only a list of several tags needs a look at the todo first.
+async fn expected_version<T: TodoRepository>(
+    repository: &T,
+    owner_id: i32,
+    id: i32,
+    headers: &HeaderMap,
+) -> Result<Option<i32>, RepositoryError> {
+    let Some(EntityTags::Versions(versions)) = entity_tags(headers, header::IF_MATCH, false) else {
+        return Ok(None);
+    };
+    match versions[..] {
+        [] => Err(RepositoryError::VersionMismatch(id)),
+        [version] => Ok(Some(version)),
+        _ => {
+            let version = repository.find(owner_id, id).await?.version();
+            if versions.contains(&version) {
+                Ok(Some(version))
+            } else {
+                Err(RepositoryError::VersionMismatch(id))
+            }
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/undo",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+    ),
+    responses(
+        (status = 200, description = "The todo as it was before its last change", body = Todo),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Nothing to undo, or the todo was changed in the meantime", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn undo_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let todo = repository.undo(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/redo",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+    ),
+    responses(
+        (status = 200, description = "The todo with the undone change applied again", body = Todo),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Nothing to redo, or the todo was changed in the meantime", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn redo_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let todo = repository.redo(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}/history",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+    ),
+    responses(
+        (status = 200, description = "Changes to the todo, oldest first", body = [TodoEvent]),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn history_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let events = repository.history(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(events)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "todos",
+    params(
+        AuditQuery,
+    ),
+    responses(
+        (status = 200, description = "One page of changes to any todo, newest first", body = TodoEventPage),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_audit<T: TodoRepository>(
+    ValidatedQuery(query): ValidatedQuery<AuditQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let page = repository.audit(user.id, query).await?;
+
+    Ok((StatusCode::OK, Json(page)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/trash",
+    tag = "todos",
+    responses(
+        (status = 200, description = "Todos in the trash", body = [Todo]),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_trash<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let todos = repository.trash(user.id).await?;
+
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/restore",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+    ),
+    responses(
+        (status = 200, description = "Restored", body = Todo),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Trashed todo not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn restore_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let todo = repository.restore(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/trash/{id}",
+    tag = "todos",
+    params(
+        ("id" = i32, Path, description = "Todo id"),
+    ),
+    responses(
+        (status = 204, description = "Deleted for good"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Trashed todo not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn purge_todo<T: TodoRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, RepositoryError> {
+    repository.purge(user.id, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/export",
+    tag = "todos",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Every todo, oldest first, streamed as they are read", content(
+            ("text/csv" = String),
+            ("application/x-ndjson" = String),
+            ("text/plain" = String),
+        )),
+        (status = 400, description = "Missing or unknown format", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn export_todo<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    ValidatedQuery(query): ValidatedQuery<ExportQuery>,
+) -> Response {
+    let format = query.format;
+    let disposition = format!("attachment; filename=\"{}\"", format.file_name());
+    let body = Body::from_stream(transfer::export(repository, user.id, format));
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Imports todos in any export format, each line checked like a `POST /todos`
+/// body. Nothing is imported unless every line is valid.
+#[utoipa::path(
+    post,
+    path = "/todos/import",
+    tag = "todos",
+    params(ImportQuery),
+    request_body(content = String, description = "The todos, in the given format", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Dry run, every line can be imported", body = ImportReport),
+        (status = 201, description = "Imported", body = ImportReport),
+        (status = 400, description = "Missing or unknown format, or a body that is not UTF-8", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid lines, or an unknown project, label or parent; `errors` tells which lines", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn import_todo<T: TodoRepository, P: ProjectRepository, L: LabelRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+    Extension(labels): Extension<Arc<L>>,
+    Extension(user): Extension<User>,
+    ValidatedQuery(query): ValidatedQuery<ImportQuery>,
+    body: String,
+) -> Result<impl IntoResponse, Response> {
+    let entries = transfer::parse(query.format, &body).map_err(invalid_lines)?;
+    let errors = transfer::check(&*repository, &*projects, &*labels, user.id, &entries)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if !errors.is_empty() {
+        return Err(invalid_lines(errors));
+    }
+    if query.dry_run {
+        let report = ImportReport {
+            dry_run: true,
+            count: entries.len(),
+            todos: vec![],
+        };
+        return Ok((StatusCode::OK, Json(report)));
+    }
+    let todos = transfer::import(&*repository, user.id, entries)
+        .await
+        .map_err(import_failure)?;
+    let report = ImportReport {
+        dry_run: false,
+        count: todos.len(),
+        todos,
+    };
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+fn invalid_lines(errors: Vec<LineError>) -> Response {
+    let mut extensions = Map::new();
+    extensions.insert("errors".to_string(), json!(errors));
+
+    problem_with(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid lines, nothing was imported".to_string(),
+        extensions,
+    )
+}
+
+fn import_failure(error: ImportError) -> Response {
+    let Some(line) = error.line else {
+        return error.error.into_response();
+    };
+    let detail = error.error.detail();
+    let mut extensions = Map::new();
+    extensions.insert(
+        "errors".to_string(),
+        json!([LineError {
+            line,
+            message: detail.clone(),
+        }]),
+    );
+
+    problem_with(
+        error.error.status(),
+        format!("line {} failed, nothing was imported: {}", line, detail),
+        extensions,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/batch",
+    tag = "todos",
+    request_body = TodoBatch,
+    responses(
+        (status = 200, description = "All operations applied, one result each", body = Object, example = json!({"results": [{"op": "delete", "id": 1}]})),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "An operation failed and the batch was rolled back; `results` tells the outcome of each", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "An operation failed and the batch was rolled back", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "An operation failed and the batch was rolled back", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn batch_todo<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<TodoBatch>,
+) -> Result<impl IntoResponse, Response> {
+    let names: Vec<&str> = payload.operations.iter().map(|op| op.name()).collect();
+    let results = repository
+        .batch(user.id, payload.operations)
+        .await
+        .map_err(|error| batch_failure(&names, error))?;
+
+    Ok((StatusCode::OK, Json(json!({ "results": results }))))
+}
+
+// Reports the failed operation with its own status, the ones before it as rolled
+// back and the ones after it as skipped.
+fn batch_failure(names: &[&str], error: BatchError) -> Response {
+    let Some(failed) = error.index else {
+        return error.error.into_response();
+    };
+    let detail = error.error.detail();
+    let results: Vec<Value> = names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| match index.cmp(&failed) {
+            Ordering::Less => json!({ "op": name, "outcome": "rolled_back" }),
+            Ordering::Equal => json!({ "op": name, "outcome": "failed", "detail": detail }),
+            Ordering::Greater => json!({ "op": name, "outcome": "skipped" }),
+        })
+        .collect();
+    let mut extensions = Map::new();
+    extensions.insert("results".to_string(), Value::Array(results));
+
+    problem_with(
+        error.error.status(),
+        format!(
+            "operation {} failed, no changes were applied: {}",
+            failed, detail
+        ),
+        extensions,
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/complete-all",
+    tag = "todos",
+    params(
+        TodoQuery,
+    ),
+    responses(
+        (status = 200, description = "Number of todos completed", body = Object, example = json!({"completed": 3})),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn complete_all_todo<T: TodoRepository>(
+    ValidatedQuery(query): ValidatedQuery<TodoQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let completed = repository.complete_all(user.id, query).await?;
+
+    Ok((StatusCode::OK, Json(json!({ "completed": completed }))))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/completed",
+    tag = "todos",
+    params(
+        TodoQuery,
+    ),
+    responses(
+        (status = 200, description = "Number of todos moved to the trash, subtrees of the completed ones included", body = Object, example = json!({"deleted": 2})),
+        (status = 400, description = "Malformed or invalid query", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn clear_completed_todo<T: TodoRepository>(
+    ValidatedQuery(query): ValidatedQuery<TodoQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let deleted = repository.clear_completed(user.id, query).await?;
+
+    Ok((StatusCode::OK, Json(json!({ "deleted": deleted }))))
+}
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+#[utoipa::path(
+    get,
+    path = "/todos/events",
+    tag = "todos",
+    params(
+        ("Last-Event-ID" = Option<u64>, Header, description = "Id of the last event received, to resume with the ones after it"),
+    ),
+    responses(
+        (status = 200, description = "Server-sent events named `created`, `updated` or `deleted` with the todo as data, and `resync` when some were missed and the todos have to be fetched again", body = String, content_type = "text/event-stream"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn todo_events<T: TodoRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok());
+    let Subscription { replay, receiver } = repository.changes().subscribe(last_event_id);
+    // `None` stands for changes that were missed, before or while streaming.
+    let replay: Vec<Option<TodoChange>> = match replay {
+        Some(changes) => changes.into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+    let stream = tokio_stream::iter(replay)
+        .chain(BroadcastStream::new(receiver).map(Result::ok))
+        .filter_map(move |change| match change {
+            Some(change) if change.owner_id != user.id => None,
+            Some(change) => Some(
+                Event::default()
+                    .id(change.id.to_string())
+                    .event(change.kind.name())
+                    .json_data(&change.todo),
+            ),
+            // Clients drop events without data.
+            None => Some(Ok(Event::default().event("resync").data("missed"))),
+        });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
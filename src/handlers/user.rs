@@ -0,0 +1,94 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use super::ValidatedJson;
+use crate::auth::{self, AuthError};
+use crate::repositories::{
+    user::{Credentials, UserRepository},
+    RepositoryError,
+};
+
+const SESSION_TTL_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct Session {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/signup",
+    tag = "users",
+    request_body = Credentials,
+    responses(
+        (status = 201, description = "Signed up", body = User),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 409, description = "The email is taken", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(()),
+)]
+pub async fn signup<T: UserRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<Credentials>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let password = payload.password;
+    let password_hash = tokio::task::spawn_blocking(move || auth::hash_password(&password))
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))??;
+    let user = repository
+        .create(payload.email.to_lowercase(), password_hash)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "users",
+    request_body = Credentials,
+    responses(
+        (status = 200, description = "A new session", body = Session),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Invalid email or password", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(()),
+)]
+pub async fn login<T: UserRepository>(
+    Extension(repository): Extension<Arc<T>>,
+    ValidatedJson(payload): ValidatedJson<Credentials>,
+) -> Result<impl IntoResponse, AuthError> {
+    let user = repository
+        .find_by_email(&payload.email.to_lowercase())
+        .await?
+        .ok_or(AuthError::InvalidCredentials)?;
+    let password = payload.password;
+    let password_hash = user.password_hash.clone();
+    let verified =
+        tokio::task::spawn_blocking(move || auth::verify_password(&password, &password_hash))
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+    if !verified {
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    let token = auth::generate_token();
+    let expires_at = Utc::now() + Duration::days(SESSION_TTL_DAYS);
+    repository
+        .create_session(user.id, auth::hash_token(&token), expires_at)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(Session {
+            token,
+            token_type: "Bearer".to_string(),
+            expires_at,
+        }),
+    ))
+}
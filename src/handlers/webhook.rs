@@ -0,0 +1,148 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+use super::ValidatedJson;
+use crate::repositories::{
+    user::User,
+    webhook::{CreateWebhook, UpdateWebhook, WebhookRepository},
+    RepositoryError,
+};
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = CreateWebhook,
+    responses(
+        (status = 201, description = "Created", body = Webhook),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn create_webhook<W: WebhookRepository>(
+    Extension(repository): Extension<Arc<W>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<CreateWebhook>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let webhook = repository.create(user.id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    responses(
+        (status = 200, description = "The webhook", body = Webhook),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn find_webhook<W: WebhookRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<W>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let webhook = repository.find(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(webhook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "All webhooks", body = [Webhook]),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_webhook<W: WebhookRepository>(
+    Extension(repository): Extension<Arc<W>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let webhooks = repository.all(user.id).await?;
+
+    Ok((StatusCode::OK, Json(webhooks)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    request_body = UpdateWebhook,
+    responses(
+        (status = 201, description = "Updated", body = Webhook),
+        (status = 400, description = "Malformed or invalid body", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn update_webhook<W: WebhookRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<W>>,
+    Extension(user): Extension<User>,
+    ValidatedJson(payload): ValidatedJson<UpdateWebhook>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let webhook = repository.update(user.id, id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    responses(
+        (status = 204, description = "Deleted along with its delivery log"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn delete_webhook<W: WebhookRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<W>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, RepositoryError> {
+    repository.delete(user.id, id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    params(
+        ("id" = i32, Path, description = "Webhook id"),
+    ),
+    responses(
+        (status = 200, description = "The latest 100 deliveries, newest first", body = [WebhookDelivery]),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn all_webhook_delivery<W: WebhookRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<W>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, RepositoryError> {
+    let deliveries = repository.deliveries(user.id, id).await?;
+
+    Ok((StatusCode::OK, Json(deliveries)))
+}
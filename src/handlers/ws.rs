@@ -0,0 +1,268 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension,
+    },
+    http::StatusCode,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+use super::validate;
+use crate::repositories::{
+    changes::{ChangeKind, TodoChange, TodoChanges},
+    project::ProjectRepository,
+    todo::{CreateTodo, Todo, TodoRepository, UpdateTodo},
+    user::User,
+    RepositoryError,
+};
+
+// Tells the changes of one connection apart from everybody else's.
+static CONNECTIONS: AtomicU64 = AtomicU64::new(1);
+
+/// A frame sent by the client. The `id`, any JSON value, is echoed back in the
+/// `ack` or `error` answering it.
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    command: Command,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Command {
+    Subscribe {
+        topic: Topic,
+    },
+    Unsubscribe {
+        topic: Topic,
+    },
+    Create {
+        todo: CreateTodo,
+    },
+    /// With a `version`, fails unless the todo is still at it, like `If-Match`.
+    Update {
+        todo_id: i32,
+        todo: UpdateTodo,
+        version: Option<i32>,
+    },
+    Delete {
+        todo_id: i32,
+        version: Option<i32>,
+    },
+}
+
+/// What a connection follows: all todos of a project, or a single todo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Topic {
+    Project(i32),
+    Todo(i32),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Frame {
+    Ack {
+        id: Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        todo: Option<Todo>,
+    },
+    Error {
+        id: Value,
+        status: u16,
+        title: Option<&'static str>,
+        detail: String,
+    },
+    Created {
+        todo: Todo,
+    },
+    Updated {
+        todo: Todo,
+    },
+    Deleted {
+        todo: Todo,
+    },
+    /// Changes were missed, the subscribed todos have to be fetched again.
+    Resync,
+}
+
+impl Frame {
+    fn error(id: Value, (status, detail): (StatusCode, String)) -> Self {
+        Frame::Error {
+            id,
+            status: status.as_u16(),
+            title: status.canonical_reason(),
+            detail,
+        }
+    }
+}
+
+impl From<RepositoryError> for (StatusCode, String) {
+    fn from(error: RepositoryError) -> Self {
+        (error.status(), error.detail())
+    }
+}
+
+/// Collaborative editing over a WebSocket. Clients send JSON text frames of a
+/// `type` among `subscribe` and `unsubscribe` (with a `topic` such as
+/// `{"project": 1}` or `{"todo": 1}`), `create`, `update` and `delete`, each
+/// answered by an `ack` or an `error`. Changes others make to the subscribed
+/// todos, through this endpoint or the REST API, arrive as `created`, `updated`
+/// and `deleted` frames.
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "todos",
+    responses(
+        (status = 101, description = "Switched to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket handshake", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or invalid bearer token", body = Problem, content_type = "application/problem+json"),
+    ),
+)]
+pub async fn websocket<T: TodoRepository, P: ProjectRepository>(
+    upgrade: WebSocketUpgrade,
+    Extension(todos): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+    Extension(user): Extension<User>,
+) -> Response {
+    let session = Session {
+        todos,
+        projects,
+        owner_id: user.id,
+        origin: CONNECTIONS.fetch_add(1, Ordering::Relaxed),
+        topics: HashSet::new(),
+    };
+    upgrade.on_upgrade(|socket| session.run(socket))
+}
+
+struct Session<T, P> {
+    todos: Arc<T>,
+    projects: Arc<P>,
+    owner_id: i32,
+    origin: u64,
+    topics: HashSet<Topic>,
+}
+
+impl<T: TodoRepository, P: ProjectRepository> Session<T, P> {
+    async fn run(mut self, mut socket: WebSocket) {
+        let receiver = self.todos.changes().subscribe(None).receiver;
+        let mut changes = BroadcastStream::new(receiver);
+        loop {
+            let frame = tokio::select! {
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => self.answer(&text).await,
+                    Some(Ok(Message::Binary(_))) => Frame::error(
+                        Value::Null,
+                        (StatusCode::BAD_REQUEST, "Expected a text frame".to_string()),
+                    ),
+                    // Pings are answered by axum.
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                    Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                },
+                Some(change) = changes.next() => match change {
+                    Ok(change) => match self.notification(change) {
+                        Some(frame) => frame,
+                        None => continue,
+                    },
+                    Err(_) => Frame::Resync,
+                },
+            };
+            let text = serde_json::to_string(&frame).unwrap();
+            if socket.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn answer(&mut self, text: &str) -> Frame {
+        // The id is echoed even when the rest of the frame does not parse.
+        let value = serde_json::from_str::<Value>(text).unwrap_or_default();
+        let id = value.get("id").cloned().unwrap_or_default();
+        let request = match serde_json::from_str::<Request>(text) {
+            Ok(request) => request,
+            Err(error) => {
+                let message = format!("Json parse error: [{}]", error);
+                return Frame::error(id, (StatusCode::BAD_REQUEST, message));
+            }
+        };
+        match self.execute(request.command).await {
+            Ok(todo) => Frame::Ack {
+                id: request.id,
+                todo,
+            },
+            Err(error) => Frame::error(request.id, error),
+        }
+    }
+
+    // Mutations run within the connection's scope of the change feed, so their
+    // own changes are not sent back as notifications.
+    async fn execute(&mut self, command: Command) -> Result<Option<Todo>, (StatusCode, String)> {
+        let owner_id = self.owner_id;
+        match command {
+            Command::Subscribe { topic } => {
+                match topic {
+                    Topic::Project(id) => self.projects.find(owner_id, id).await.map(|_| ())?,
+                    Topic::Todo(id) => self.todos.find(owner_id, id).await.map(|_| ())?,
+                }
+                self.topics.insert(topic);
+                Ok(None)
+            }
+            Command::Unsubscribe { topic } => {
+                self.topics.remove(&topic);
+                Ok(None)
+            }
+            Command::Create { todo } => {
+                validate(&todo)?;
+                let todo =
+                    TodoChanges::scope(self.origin, self.todos.create(owner_id, todo)).await?;
+                Ok(Some(todo))
+            }
+            Command::Update {
+                todo_id,
+                todo,
+                version,
+            } => {
+                validate(&todo)?;
+                let update = self.todos.update(owner_id, todo_id, todo, version);
+                let todo = TodoChanges::scope(self.origin, update).await?;
+                Ok(Some(todo))
+            }
+            Command::Delete { todo_id, version } => {
+                let delete = self.todos.delete(owner_id, todo_id, version);
+                TodoChanges::scope(self.origin, delete).await?;
+                Ok(None)
+            }
+        }
+    }
+
+    // The frame telling about a change to one of the subscribed todos made by
+    // somebody else.
+    fn notification(&self, change: TodoChange) -> Option<Frame> {
+        let followed = self.topics.contains(&Topic::Todo(change.todo.id()))
+            || change
+                .todo
+                .project_id()
+                .is_some_and(|id| self.topics.contains(&Topic::Project(id)));
+        if change.owner_id != self.owner_id || change.origin == Some(self.origin) || !followed {
+            return None;
+        }
+        let todo = change.todo;
+        Some(match change.kind {
+            ChangeKind::Created => Frame::Created { todo },
+            ChangeKind::Updated => Frame::Updated { todo },
+            ChangeKind::Deleted => Frame::Deleted { todo },
+        })
+    }
+}
@@ -0,0 +1,540 @@
+use crate::recurrence::{Recurrence, RecurrenceError};
+use crate::repositories::todo::{Priority, Todo};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use thiserror::Error;
+
+const PRODID: &str = "-//todo-rust//todo-rust//EN";
+// Content lines longer than this many octets are folded, RFC 5545 section 3.1.
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ICalError {
+    #[error("No VTODO component")]
+    MissingTodo,
+    #[error("{0} is required")]
+    MissingProperty(&'static str),
+    #[error("Invalid value for {0}: [{1}]")]
+    InvalidValue(&'static str, String),
+    #[error(transparent)]
+    Recurrence(#[from] RecurrenceError),
+}
+
+/// The parts of a `VTODO` sent by a client that a todo keeps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VTodo {
+    pub uid: String,
+    pub summary: String,
+    pub completed: bool,
+    pub priority: Priority,
+    pub due_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+}
+
+/// An RFC 5545 calendar holding every todo as a `VTODO`, stamped with `now`.
+pub fn calendar(todos: &[Todo], now: DateTime<Utc>) -> String {
+    let mut lines = header();
+    lines.push("X-WR-CALNAME:Todos".to_string());
+    for todo in todos {
+        lines.extend(vtodo(todo, &uid(todo.id()), now));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+/// A calendar holding just the one todo, as a CalDAV resource.
+pub fn object(todo: &Todo, uid: &str, now: DateTime<Utc>) -> String {
+    let mut lines = header();
+    lines.extend(vtodo(todo, uid, now));
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+fn header() -> Vec<String> {
+    vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+    ]
+}
+
+/// The content lines of the `VTODO` of a todo, not folded yet.
+pub fn vtodo(todo: &Todo, uid: &str, now: DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", escape(uid)),
+        format!("DTSTAMP:{}", datetime(now)),
+        format!("CREATED:{}", datetime(todo.created_at())),
+        format!("LAST-MODIFIED:{}", datetime(todo.updated_at())),
+        format!("SEQUENCE:{}", todo.version()),
+        format!("SUMMARY:{}", escape(todo.text())),
+        format!("PRIORITY:{}", priority(todo.priority())),
+    ];
+    if todo.completed() {
+        lines.push("STATUS:COMPLETED".to_string());
+        lines.extend(
+            todo.completed_at()
+                .map(|at| format!("COMPLETED:{}", datetime(at))),
+        );
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    lines.extend(todo.due_at().map(due));
+    if !todo.labels().is_empty() {
+        let names: Vec<String> = todo
+            .labels()
+            .iter()
+            .map(|label| escape(&label.name))
+            .collect();
+        lines.push(format!("CATEGORIES:{}", names.join(",")));
+    }
+    lines.extend(
+        todo.recurrence()
+            .map(|recurrence| format!("RRULE:{}", recurrence)),
+    );
+    lines.push("END:VTODO".to_string());
+    lines
+}
+
+/// The UID of todos that were not created over CalDAV.
+pub fn uid(id: i32) -> String {
+    format!("todo-{}@todo-rust", id)
+}
+
+/// Reads the first `VTODO` of a calendar. Other components, including alarms, are
+/// skipped, as are properties a todo has no place for. A local `DUE` is read in
+/// the IANA zone of its `TZID`, an unknown one failing with `InvalidValue("TZID",
+/// ..)`, and as UTC without a `TZID`.
+pub fn parse_vtodo(calendar: &str) -> Result<VTodo, ICalError> {
+    let lines = unfold(calendar);
+    let mut components = vec![];
+    let mut properties = vec![];
+    for line in &lines {
+        let (name, params, value) = property(line);
+        match name.as_str() {
+            "BEGIN" => components.push(value.to_uppercase()),
+            "END" if components.last().map(String::as_str) == Some("VTODO") => break,
+            "END" => {
+                components.pop();
+            }
+            _ if components.last().map(String::as_str) == Some("VTODO") => {
+                properties.push((name, params, value))
+            }
+            _ => {}
+        }
+    }
+    if !components.iter().any(|component| component == "VTODO") {
+        return Err(ICalError::MissingTodo);
+    }
+    let find = |wanted: &str| {
+        properties
+            .iter()
+            .find(|(name, _, _)| name == wanted)
+            .map(|(_, params, value)| (params, value.as_str()))
+    };
+
+    let uid = find("UID")
+        .map(|(_, value)| unescape(value))
+        .filter(|uid| !uid.is_empty())
+        .ok_or(ICalError::MissingProperty("UID"))?;
+    let summary = find("SUMMARY")
+        .map(|(_, value)| unescape(value))
+        .ok_or(ICalError::MissingProperty("SUMMARY"))?;
+    let completed = match find("STATUS") {
+        Some((_, status)) => status.eq_ignore_ascii_case("COMPLETED"),
+        None => find("COMPLETED").is_some(),
+    };
+    let priority = match find("PRIORITY") {
+        Some((_, value)) => value
+            .trim()
+            .parse()
+            .ok()
+            .and_then(from_priority)
+            .ok_or_else(|| ICalError::InvalidValue("PRIORITY", value.to_string()))?,
+        None => Priority::Normal,
+    };
+    let due_at = find("DUE")
+        .map(|(params, value)| parse_due(params, value))
+        .transpose()?;
+    let recurrence = find("RRULE")
+        .map(|(_, value)| value.parse::<Recurrence>())
+        .transpose()?;
+
+    Ok(VTodo {
+        uid,
+        summary,
+        completed,
+        priority,
+        due_at,
+        recurrence,
+    })
+}
+
+// Joins lines continued with a space or a tab, dropping blank ones.
+fn unfold(calendar: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in calendar.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// Splits `NAME;PARAM=VALUE:VALUE` into the upper-case name, the parameters and the
+// raw value. Parameter values may be quoted and hold colons.
+fn property(line: &str) -> (String, Vec<(String, String)>, String) {
+    let mut quoted = false;
+    let split = line
+        .char_indices()
+        .find(|(_, c)| {
+            if *c == '"' {
+                quoted = !quoted;
+            }
+            *c == ':' && !quoted
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(line.len());
+    let (head, value) = (&line[..split], line.get(split + 1..).unwrap_or(""));
+    let mut parts = head.split(';');
+    let name = parts.next().unwrap_or("").trim().to_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_string()))
+        .collect();
+    (name, params, value.to_string())
+}
+
+fn from_priority(priority: u8) -> Option<Priority> {
+    match priority {
+        0 | 5 => Some(Priority::Normal),
+        1..=2 => Some(Priority::Urgent),
+        3..=4 => Some(Priority::High),
+        6..=9 => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+// Local times are read in their `TZID`, which has to be an IANA name, or as UTC
+// without one. A time skipped by a DST change is moved past the gap, RFC 5545
+// section 3.3.5.
+fn parse_due(params: &[(String, String)], value: &str) -> Result<DateTime<Utc>, ICalError> {
+    let invalid = || ICalError::InvalidValue("DUE", value.to_string());
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let value = value.trim();
+    let is_date = param("VALUE").is_some_and(|kind| kind.eq_ignore_ascii_case("DATE"));
+    if is_date || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        return Ok(date.and_time(NaiveTime::MIN).and_utc());
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let at = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok(at.and_utc());
+    }
+    let at = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    let Some(tzid) = param("TZID") else {
+        return Ok(at.and_utc());
+    };
+    let tz: Tz = tzid
+        .parse()
+        .map_err(|_| ICalError::InvalidValue("TZID", tzid.to_string()))?;
+    tz.from_local_datetime(&at)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(at + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(invalid)
+}
+
+// 1 is the highest priority and 9 the lowest, 0 would be none at all.
+fn priority(priority: Priority) -> u8 {
+    match priority {
+        Priority::Urgent => 1,
+        Priority::High => 3,
+        Priority::Normal => 5,
+        Priority::Low => 9,
+    }
+}
+
+fn datetime(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Due dates at midnight are all-day.
+fn due(at: DateTime<Utc>) -> String {
+    if at.time() == NaiveTime::MIN {
+        format!("DUE;VALUE=DATE:{}", at.format("%Y%m%d"))
+    } else {
+        format!("DUE:{}", datetime(at))
+    }
+}
+
+/// Escapes a `TEXT` value.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverses `escape`.
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Ends a content line with CRLF, folding it into lines of at most 75 octets
+/// that continue with a space. Characters are never split.
+pub fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn todo() -> Todo {
+        serde_json::from_value(json!({
+            "id": 7,
+            "text": "buy milk, eggs; bread",
+            "completed": true,
+            "priority": "high",
+            "due_at": "2024-05-03T00:00:00Z",
+            "completed_at": "2024-05-02T18:00:00Z",
+            "created_at": "2024-05-01T08:30:00Z",
+            "updated_at": "2024-05-02T18:00:00Z",
+            "deleted_at": null,
+            "version": 3,
+            "project_id": null,
+            "parent_id": null,
+            "auto_complete": false,
+            "recurrence": "FREQ=WEEKLY;BYDAY=SA",
+            "labels": [{ "id": 1, "name": "shopping" }, { "id": 2, "name": "home" }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn should_render_todos_as_vtodo() {
+        let now = at("2024-05-04T12:00:00Z");
+        assert_eq!(
+            calendar(&[todo()], now),
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//todo-rust//todo-rust//EN\r\n\
+             CALSCALE:GREGORIAN\r\n\
+             X-WR-CALNAME:Todos\r\n\
+             BEGIN:VTODO\r\n\
+             UID:todo-7@todo-rust\r\n\
+             DTSTAMP:20240504T120000Z\r\n\
+             CREATED:20240501T083000Z\r\n\
+             LAST-MODIFIED:20240502T180000Z\r\n\
+             SEQUENCE:3\r\n\
+             SUMMARY:buy milk\\, eggs\\; bread\r\n\
+             PRIORITY:3\r\n\
+             STATUS:COMPLETED\r\n\
+             COMPLETED:20240502T180000Z\r\n\
+             DUE;VALUE=DATE:20240503\r\n\
+             CATEGORIES:shopping,home\r\n\
+             RRULE:FREQ=WEEKLY;BYDAY=SA\r\n\
+             END:VTODO\r\n\
+             END:VCALENDAR\r\n"
+        );
+    }
+
+    #[test]
+    fn should_render_open_todos() {
+        let todo = Todo::new(1, "walk dog".to_string(), vec![]);
+        let lines = vtodo(&todo, &uid(todo.id()), Utc::now());
+        assert!(lines.contains(&"STATUS:NEEDS-ACTION".to_string()));
+        assert!(lines.contains(&"PRIORITY:5".to_string()));
+        assert!(!lines.iter().any(|line| line.starts_with("DUE")));
+
+        assert_eq!(
+            due(at("2024-05-03T09:15:00Z")),
+            "DUE:20240503T091500Z".to_string()
+        );
+    }
+
+    #[test]
+    fn should_escape_text() {
+        assert_eq!(escape("a\\b;c,d\r\ne"), "a\\\\b\\;c\\,d\\ne");
+    }
+
+    #[test]
+    fn should_fold_long_lines() {
+        assert_eq!(fold("SUMMARY:short"), "SUMMARY:short\r\n");
+
+        let line = format!("SUMMARY:{}", "a".repeat(150));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert_eq!(lines[1..].iter().filter(|l| l.starts_with(' ')).count(), 2);
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+
+        // Three octets each, never split.
+        let line = format!("SUMMARY:{}", "日".repeat(30));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(lines[0].len(), 74);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", ""), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn should_parse_vtodo() {
+        let body = "BEGIN:VCALENDAR\r\n\
+                    VERSION:2.0\r\n\
+                    PRODID:-//Apple Inc.//Reminders//EN\r\n\
+                    BEGIN:VTODO\r\n\
+                    UID:0B1F-AC\r\n\
+                    SUMMARY:buy milk\\, eggs\\; bre\r\n \
+                    ad\r\n\
+                    PRIORITY:1\r\n\
+                    STATUS:NEEDS-ACTION\r\n\
+                    DUE;TZID=\"Europe/Paris\":20240503T091500\r\n\
+                    RRULE:FREQ=WEEKLY;BYDAY=SA\r\n\
+                    BEGIN:VALARM\r\n\
+                    SUMMARY:not this one\r\n\
+                    END:VALARM\r\n\
+                    END:VTODO\r\n\
+                    END:VCALENDAR\r\n";
+        assert_eq!(
+            parse_vtodo(body),
+            Ok(VTodo {
+                uid: "0B1F-AC".to_string(),
+                summary: "buy milk, eggs; bread".to_string(),
+                completed: false,
+                priority: Priority::Urgent,
+                due_at: Some(at("2024-05-03T07:15:00Z")),
+                recurrence: Some("FREQ=WEEKLY;BYDAY=SA".parse().unwrap()),
+            })
+        );
+    }
+
+    #[test]
+    fn should_read_back_rendered_todos() {
+        let todo = todo();
+        let parsed = parse_vtodo(&object(&todo, "custom", Utc::now())).unwrap();
+        assert_eq!(parsed.uid, "custom");
+        assert_eq!(parsed.summary, todo.text());
+        assert!(parsed.completed);
+        assert_eq!(parsed.priority, Priority::High);
+        assert_eq!(parsed.due_at, todo.due_at());
+        assert_eq!(parsed.recurrence.as_ref(), todo.recurrence());
+    }
+
+    #[test]
+    fn should_reject_invalid_vtodo() {
+        let vtodo = |lines: &str| {
+            format!(
+                "BEGIN:VCALENDAR\nBEGIN:VTODO\n{}END:VTODO\nEND:VCALENDAR\n",
+                lines
+            )
+        };
+        assert_eq!(
+            parse_vtodo("BEGIN:VCALENDAR\nEND:VCALENDAR\n"),
+            Err(ICalError::MissingTodo)
+        );
+        assert_eq!(
+            parse_vtodo(&vtodo("SUMMARY:a\n")),
+            Err(ICalError::MissingProperty("UID"))
+        );
+        assert_eq!(
+            parse_vtodo(&vtodo("UID:1\nSUMMARY:a\nPRIORITY:10\n")),
+            Err(ICalError::InvalidValue("PRIORITY", "10".to_string()))
+        );
+        assert_eq!(
+            parse_vtodo(&vtodo("UID:1\nSUMMARY:a\nDUE:tomorrow\n")),
+            Err(ICalError::InvalidValue("DUE", "tomorrow".to_string()))
+        );
+        assert!(matches!(
+            parse_vtodo(&vtodo("UID:1\nSUMMARY:a\nRRULE:FREQ=YEARLY\n")),
+            Err(ICalError::Recurrence(_))
+        ));
+
+        let completed = parse_vtodo(&vtodo("UID:1\nSUMMARY:a\nCOMPLETED:20240502T180000Z\n"));
+        assert!(completed.unwrap().completed);
+        let all_day = parse_vtodo(&vtodo("UID:1\nSUMMARY:a\nDUE;VALUE=DATE:20240503\n"));
+        assert_eq!(all_day.unwrap().due_at, Some(at("2024-05-03T00:00:00Z")));
+        assert_eq!(
+            parse_vtodo(&vtodo(
+                "UID:1\nSUMMARY:a\nDUE;TZID=Mars/Olympus:20240503T091500\n"
+            )),
+            Err(ICalError::InvalidValue("TZID", "Mars/Olympus".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_read_due_times_in_their_zone() {
+        let due = |line: &str| {
+            let body = format!("BEGIN:VTODO\nUID:1\nSUMMARY:a\n{}\nEND:VTODO\n", line);
+            parse_vtodo(&body).unwrap().due_at.unwrap()
+        };
+        assert_eq!(due("DUE:20240503T091500"), at("2024-05-03T09:15:00Z"));
+        assert_eq!(
+            due("DUE;TZID=America/New_York:20240115T091500Z"),
+            at("2024-01-15T09:15:00Z")
+        );
+        assert_eq!(
+            due("DUE;TZID=America/New_York:20240115T091500"),
+            at("2024-01-15T14:15:00Z")
+        );
+        // 02:30 does not exist on the night clocks go forward.
+        assert_eq!(
+            due("DUE;TZID=Europe/Paris:20240331T023000"),
+            at("2024-03-31T01:30:00Z")
+        );
+    }
+}
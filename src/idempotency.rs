@@ -0,0 +1,142 @@
+use crate::{
+    handlers::problem,
+    repositories::{
+        idempotency::{IdempotencyKey, IdempotencyRepository},
+        RepositoryError,
+    },
+};
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{env, future::Future};
+use tokio::task::JoinHandle;
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+const MAX_KEY_LENGTH: usize = 255;
+const DEFAULT_TTL_HOURS: i64 = 24;
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// How long a response is kept for replay, `IDEMPOTENCY_TTL_HOURS` (24 by default).
+pub fn ttl() -> Duration {
+    let hours = env::var("IDEMPOTENCY_TTL_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(DEFAULT_TTL_HOURS);
+    Duration::hours(hours)
+}
+
+/// Deletes expired idempotency keys, right away and then once an hour.
+pub fn spawn_purge<I: IdempotencyRepository>(repository: I) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repository.purge_expired(Utc::now()).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("purged {} expired idempotency keys", purged),
+                Err(e) => tracing::error!("failed to purge idempotency keys: {}", e),
+            }
+        }
+    })
+}
+
+/// Identifies a request body, so a key reused for a different one is noticed.
+pub fn request_hash<T: Serialize>(request: &T) -> String {
+    let body = serde_json::to_vec(request).unwrap_or_default();
+    format!("{:x}", Sha256::digest(body))
+}
+
+/// Runs `handler` at most once per `Idempotency-Key` of the owner. A repeat of the
+/// request gets the stored response again, marked with `Idempotent-Replayed`.
+/// Reusing the key for a different request fails with 422, repeating it while the
+/// first request is still running with 409. Without the header, `handler` just
+/// runs.
+pub async fn run_once<I, F, Fut>(
+    repository: &I,
+    owner_id: i32,
+    headers: &HeaderMap,
+    request_hash: String,
+    handler: F,
+) -> Response
+where
+    I: IdempotencyRepository,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<(StatusCode, Value), RepositoryError>>,
+{
+    let key = match headers.get(IDEMPOTENCY_KEY).map(|key| key.to_str()) {
+        None => {
+            return match handler().await {
+                Ok((status, body)) => (status, Json(body)).into_response(),
+                Err(e) => e.into_response(),
+            }
+        }
+        Some(Ok(key)) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key,
+        Some(_) => {
+            return problem(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    MAX_KEY_LENGTH
+                ),
+            )
+        }
+    };
+
+    let holder = match repository
+        .reserve(owner_id, key, &request_hash, Utc::now() + ttl())
+        .await
+    {
+        Ok(holder) => holder,
+        Err(e) => return e.into_response(),
+    };
+    match holder {
+        None => {}
+        Some(holder) if holder.request_hash != request_hash => {
+            return problem(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request".to_string(),
+            )
+        }
+        Some(IdempotencyKey {
+            status: Some(status),
+            body: Some(body),
+            ..
+        }) => {
+            let status = StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK);
+            return (status, [(IDEMPOTENT_REPLAYED, "true")], Json(body.0)).into_response();
+        }
+        Some(_) => {
+            return problem(
+                StatusCode::CONFLICT,
+                "a request with this Idempotency-Key is still in progress".to_string(),
+            )
+        }
+    }
+
+    match handler().await {
+        Ok((status, body)) => {
+            // The change is made either way, so the response still goes out; a
+            // repeat is answered with 409 until the key expires.
+            if let Err(e) = repository
+                .complete(owner_id, key, status.as_u16(), body.clone())
+                .await
+            {
+                tracing::error!("failed to store the response to replay: {}", e);
+            }
+            (status, Json(body)).into_response()
+        }
+        Err(error) => {
+            if let Err(e) = repository.release(owner_id, key).await {
+                tracing::error!("failed to release an idempotency key: {}", e);
+            }
+            error.into_response()
+        }
+    }
+}
@@ -0,0 +1,2084 @@
+mod auth;
+pub mod cli;
+mod handlers;
+mod ical;
+pub mod idempotency;
+mod openapi;
+mod recurrence;
+pub mod repositories;
+mod transfer;
+pub mod trash;
+mod webdav;
+pub mod webhooks;
+
+use crate::repositories::{
+    calendar_object::CalendarObjectRepository, idempotency::IdempotencyRepository,
+    label::LabelRepository, project::ProjectRepository, todo::TodoRepository, user::UserRepository,
+    webhook::WebhookRepository,
+};
+use axum::{
+    extract::Extension,
+    middleware,
+    routing::{any, delete, get, post},
+    Router,
+};
+use handlers::{
+    caldav::{caldav, well_known},
+    calendar::{calendar_feed, create_calendar_token, delete_calendar_token},
+    label::{all_label, create_label, delete_label, find_label, update_label},
+    project::{
+        all_project, all_project_todo, create_project, create_project_todo, delete_project,
+        find_project, update_project,
+    },
+    todo::{
+        all_audit, all_todo, all_trash, batch_todo, clear_completed_todo, complete_all_todo,
+        create_todo, delete_todo, export_todo, find_todo, history_todo, import_todo, purge_todo,
+        redo_todo, restore_todo, search_todo, todo_events, undo_todo, update_todo,
+    },
+    user::{login, signup},
+    webhook::{
+        all_webhook, all_webhook_delivery, create_webhook, delete_webhook, find_webhook,
+        update_webhook,
+    },
+    ws::websocket,
+};
+use std::sync::Arc;
+
+// Everything but `/`, `/signup`, `/login`, the API docs and `/calendar.ics`, which
+// has a token of its own, requires a bearer token. CalDAV clients under `/dav` may
+// log in with their email and password instead.
+// The repository extensions are added last so the auth middleware can reach the
+// user repository.
+pub fn create_app<T, L, U, P, I, W, D>(
+    todo_repository: T,
+    label_repository: L,
+    user_repository: U,
+    project_repository: P,
+    idempotency_repository: I,
+    webhook_repository: W,
+    calendar_object_repository: D,
+) -> Router
+where
+    T: TodoRepository,
+    L: LabelRepository,
+    U: UserRepository,
+    P: ProjectRepository,
+    I: IdempotencyRepository,
+    W: WebhookRepository,
+    D: CalendarObjectRepository,
+{
+    let dav = Router::new()
+        .route("/dav", any(caldav::<T, D>))
+        .route("/dav/", any(caldav::<T, D>))
+        .route("/dav/*path", any(caldav::<T, D>))
+        .route_layer(middleware::from_fn(auth::require_dav_auth::<U>));
+
+    Router::new()
+        .route("/todos", post(create_todo::<T, I>).get(all_todo::<T>))
+        .route("/todos/search", get(search_todo::<T>))
+        .route("/todos/events", get(todo_events::<T>))
+        .route("/todos/batch", post(batch_todo::<T>))
+        .route("/todos/export", get(export_todo::<T>))
+        .route("/todos/import", post(import_todo::<T, P, L>))
+        .route("/todos/complete-all", post(complete_all_todo::<T>))
+        .route("/todos/completed", delete(clear_completed_todo::<T>))
+        .route(
+            "/todos/:id",
+            get(find_todo::<T>)
+                .delete(delete_todo::<T>)
+                .patch(update_todo::<T>),
+        )
+        .route("/todos/:id/restore", post(restore_todo::<T>))
+        .route("/todos/:id/undo", post(undo_todo::<T>))
+        .route("/todos/:id/redo", post(redo_todo::<T>))
+        .route("/todos/:id/history", get(history_todo::<T>))
+        .route("/audit", get(all_audit::<T>))
+        .route("/trash", get(all_trash::<T>))
+        .route("/trash/:id", delete(purge_todo::<T>))
+        .route("/ws", get(websocket::<T, P>))
+        .route(
+            "/calendar/token",
+            post(create_calendar_token::<U>).delete(delete_calendar_token::<U>),
+        )
+        .route("/labels", post(create_label::<L>).get(all_label::<L>))
+        .route(
+            "/labels/:id",
+            get(find_label::<L>)
+                .delete(delete_label::<L>)
+                .patch(update_label::<L>),
+        )
+        .route("/projects", post(create_project::<P>).get(all_project::<P>))
+        .route(
+            "/projects/:id",
+            get(find_project::<P>)
+                .delete(delete_project::<P>)
+                .patch(update_project::<P>),
+        )
+        .route(
+            "/projects/:id/todos",
+            post(create_project_todo::<P, T>).get(all_project_todo::<P, T>),
+        )
+        .route("/webhooks", post(create_webhook::<W>).get(all_webhook::<W>))
+        .route(
+            "/webhooks/:id",
+            get(find_webhook::<W>)
+                .delete(delete_webhook::<W>)
+                .patch(update_webhook::<W>),
+        )
+        .route("/webhooks/:id/deliveries", get(all_webhook_delivery::<W>))
+        .route_layer(middleware::from_fn(auth::require_auth::<U>))
+        .merge(dav)
+        .route("/", get(root))
+        .route("/signup", post(signup::<U>))
+        .route("/login", post(login::<U>))
+        .route("/calendar.ics", get(calendar_feed::<T, U>))
+        .route("/.well-known/caldav", any(well_known))
+        .route("/openapi.json", get(openapi::openapi_json))
+        .route("/docs", get(openapi::docs))
+        .layer(Extension(Arc::new(todo_repository)))
+        .layer(Extension(Arc::new(label_repository)))
+        .layer(Extension(Arc::new(user_repository)))
+        .layer(Extension(Arc::new(project_repository)))
+        .layer(Extension(Arc::new(idempotency_repository)))
+        .layer(Extension(Arc::new(webhook_repository)))
+        .layer(Extension(Arc::new(calendar_object_repository)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "docs",
+    responses(
+        (status = 200, description = "Greeting", body = String, content_type = "text/plain"),
+    ),
+    security(()),
+)]
+async fn root() -> &'static str {
+    "Hello, World!"
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::{
+        calendar_object::test_utils::CalendarObjectRepositoryForMemory,
+        idempotency::test_utils::IdempotencyRepositoryForMemory,
+        label::{test_utils::LabelRepositoryForMemory, Label},
+        project::test_utils::ProjectRepositoryForMemory,
+        todo::test_utils::TodoRepositoryForMemory,
+        user::test_utils::UserRepositoryForMemory,
+        webhook::test_utils::WebhookRepositoryForMemory,
+    };
+    use chrono::{Duration, Utc};
+
+    pub const TEST_TOKEN: &str = "test-token";
+    pub const TEST_USER_ID: i32 = 1;
+
+    // A user repository holding `TEST_USER_ID` with a live session for `TEST_TOKEN`.
+    pub async fn user_fixture() -> UserRepositoryForMemory {
+        let repository = UserRepositoryForMemory::new();
+        let user = repository
+            .create("test@example.com".to_string(), "hash".to_string())
+            .await
+            .expect("failed create user");
+        repository
+            .create_session(
+                user.id,
+                auth::hash_token(TEST_TOKEN),
+                Utc::now() + Duration::hours(1),
+            )
+            .await
+            .expect("failed create session");
+        repository
+    }
+
+    pub fn label_fixture() -> (Vec<Label>, Vec<i32>) {
+        let id = 999;
+        (vec![Label::new(id, String::from("test label"))], vec![id])
+    }
+
+    /// The repositories `create_app` is given by `test_app`.
+    pub struct TestApp {
+        todos: TodoRepositoryForMemory,
+        labels: LabelRepositoryForMemory,
+        users: UserRepositoryForMemory,
+        projects: ProjectRepositoryForMemory,
+        idempotency: IdempotencyRepositoryForMemory,
+        webhooks: WebhookRepositoryForMemory,
+        calendar_objects: CalendarObjectRepositoryForMemory,
+    }
+
+    /// The app on memory repositories: todos that know the label of
+    /// `label_fixture`, the user of `user_fixture` and nothing else. A test swaps
+    /// in the repositories it sets up itself before calling `build`.
+    pub async fn test_app() -> TestApp {
+        let (labels, _) = label_fixture();
+        TestApp {
+            todos: TodoRepositoryForMemory::new(labels),
+            labels: LabelRepositoryForMemory::new(),
+            users: user_fixture().await,
+            projects: ProjectRepositoryForMemory::new(),
+            idempotency: IdempotencyRepositoryForMemory::new(),
+            webhooks: WebhookRepositoryForMemory::new(),
+            calendar_objects: CalendarObjectRepositoryForMemory::new(),
+        }
+    }
+
+    impl TestApp {
+        pub fn with_todos(self, todos: TodoRepositoryForMemory) -> Self {
+            Self { todos, ..self }
+        }
+
+        pub fn with_labels(self, labels: LabelRepositoryForMemory) -> Self {
+            Self { labels, ..self }
+        }
+
+        pub fn with_users(self, users: UserRepositoryForMemory) -> Self {
+            Self { users, ..self }
+        }
+
+        pub fn with_projects(self, projects: ProjectRepositoryForMemory) -> Self {
+            Self { projects, ..self }
+        }
+
+        pub fn with_webhooks(self, webhooks: WebhookRepositoryForMemory) -> Self {
+            Self { webhooks, ..self }
+        }
+
+        pub fn with_calendar_objects(
+            self,
+            calendar_objects: CalendarObjectRepositoryForMemory,
+        ) -> Self {
+            Self {
+                calendar_objects,
+                ..self
+            }
+        }
+
+        pub fn build(self) -> Router {
+            create_app(
+                self.todos,
+                self.labels,
+                self.users,
+                self.projects,
+                self.idempotency,
+                self.webhooks,
+                self.calendar_objects,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        calendar_object::test_utils::CalendarObjectRepositoryForMemory,
+        changes::ChangeKind,
+        label::{test_utils::LabelRepositoryForMemory, CreateLabel, Label},
+        project::{test_utils::ProjectRepositoryForMemory, CreateProject},
+        todo::{test_utils::TodoRepositoryForMemory, CreateTodo, Todo, TodoPage, UpdateTodo},
+        user::test_utils::UserRepositoryForMemory,
+        webhook::test_utils::WebhookRepositoryForMemory,
+    };
+    use crate::test_utils::{label_fixture, test_app, TEST_TOKEN, TEST_USER_ID};
+    use axum::response::Response;
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+    };
+    use chrono::Utc;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn build_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
+            .body(Body::from(json_body))
+            .unwrap()
+    }
+
+    fn build_req_with_empty(path: &str, method: Method) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn res_to_todo(res: Response) -> Todo {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let todo: Todo = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
+        todo
+    }
+
+    async fn res_to_todo_page(res: Response) -> TodoPage {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let page: TodoPage = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert TodoPage instance. body: {}", body));
+        page
+    }
+
+    async fn res_to_label(res: Response) -> Label {
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let label: Label = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
+        label
+    }
+
+    #[tokio::test]
+    async fn should_return_hello_world() {
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let res = test_app().await.build().oneshot(req).await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        assert_eq!(body, "Hello, World!");
+    }
+
+    #[tokio::test]
+    async fn should_serve_openapi_without_token() {
+        let app = test_app().await.build();
+        let req = Request::builder()
+            .uri("/openapi.json")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(spec["paths"]["/todos"]["post"].is_object());
+        let update = &spec["paths"]["/todos/{id}"]["patch"]["responses"];
+        assert!(update["409"].is_null(), "a parent cycle is a 422");
+        assert!(update["422"].is_object());
+
+        let req = Request::builder().uri("/docs").body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(res.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+
+    // Reads server-sent events off `body` until `count` of them arrived.
+    async fn read_events(
+        body: &mut (impl tokio_stream::Stream<Item = Result<axum::body::Bytes, axum::Error>> + Unpin),
+        count: usize,
+    ) -> Vec<String> {
+        use tokio_stream::StreamExt;
+
+        let mut buffer = String::new();
+        while buffer.matches("\n\n").count() < count {
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+                .await
+                .expect("no event in time")
+                .expect("stream ended")
+                .unwrap();
+            buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        buffer
+            .split_terminator("\n\n")
+            .map(|event| format!("{}\n", event))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn should_stream_todo_events() {
+        let repository = TodoRepositoryForMemory::new(vec![]);
+        let mine = repository
+            .create(TEST_USER_ID, CreateTodo::new("mine".to_string(), vec![]))
+            .await
+            .unwrap();
+        repository
+            .create(
+                TEST_USER_ID + 1,
+                CreateTodo::new("theirs".to_string(), vec![]),
+            )
+            .await
+            .unwrap();
+        let app = test_app().await.with_todos(repository.clone()).build();
+
+        let mut req = build_req_with_empty("/todos/events", Method::GET);
+        req.headers_mut()
+            .insert("Last-Event-ID", "0".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let mut body = res.into_body().into_data_stream();
+        let replayed = read_events(&mut body, 1).await;
+        assert!(replayed[0].contains("event: created\n"), "{}", replayed[0]);
+        assert!(replayed[0].contains("id: 1\n"), "{}", replayed[0]);
+        assert!(
+            replayed[0].contains(&format!(
+                "data: {}\n",
+                serde_json::to_string(&mine).unwrap()
+            )),
+            "{}",
+            replayed[0]
+        );
+
+        repository
+            .update(TEST_USER_ID + 1, 2, UpdateTodo::complete(), None)
+            .await
+            .unwrap();
+        repository
+            .delete(TEST_USER_ID, mine.id(), None)
+            .await
+            .unwrap();
+        let streamed = read_events(&mut body, 1).await;
+        assert!(streamed[0].contains("event: deleted\n"), "{}", streamed[0]);
+        assert!(streamed[0].contains("id: 4\n"), "{}", streamed[0]);
+
+        // Ahead of the feed, as after a restart of the server.
+        let mut req = build_req_with_empty("/todos/events", Method::GET);
+        req.headers_mut()
+            .insert("Last-Event-ID", "99".parse().unwrap());
+        let res = app.oneshot(req).await.unwrap();
+        let mut body = res.into_body().into_data_stream();
+        let missed = read_events(&mut body, 1).await;
+        assert!(missed[0].contains("event: resync\n"), "{}", missed[0]);
+    }
+
+    type Socket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    // Serves `app` on a free port and opens a WebSocket to its `/ws` as the test user.
+    async fn connect_ws(app: Router) -> (Socket, Socket) {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let url = format!("ws://{}/ws", addr);
+        let res = tokio_tungstenite::connect_async(url.as_str()).await;
+        assert!(matches!(
+            res,
+            Err(tokio_tungstenite::tungstenite::Error::Http(res))
+                if res.status().as_u16() == StatusCode::UNAUTHORIZED.as_u16()
+        ));
+
+        let mut sockets = vec![];
+        for _ in 0..2 {
+            let mut req = url.as_str().into_client_request().unwrap();
+            req.headers_mut().insert(
+                header::AUTHORIZATION.as_str(),
+                format!("Bearer {}", TEST_TOKEN).parse().unwrap(),
+            );
+            sockets.push(tokio_tungstenite::connect_async(req).await.unwrap().0);
+        }
+        (sockets.remove(0), sockets.remove(0))
+    }
+
+    async fn ws_send(socket: &mut Socket, frame: serde_json::Value) {
+        use futures_util::SinkExt;
+
+        socket
+            .send(tokio_tungstenite::tungstenite::Message::Text(
+                frame.to_string(),
+            ))
+            .await
+            .unwrap();
+    }
+
+    async fn ws_recv(socket: &mut Socket) -> serde_json::Value {
+        use tokio_stream::StreamExt;
+
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+            .await
+            .expect("no frame in time")
+            .expect("socket closed")
+            .unwrap();
+        serde_json::from_str(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_edit_todos_over_websocket() {
+        use serde_json::json;
+
+        let projects = ProjectRepositoryForMemory::new();
+        let project = projects
+            .create(TEST_USER_ID, CreateProject::new("shared".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::new(vec![]);
+        let (mut alice, mut bob) = connect_ws(
+            test_app()
+                .await
+                .with_todos(repository.clone())
+                .with_projects(projects)
+                .build(),
+        )
+        .await;
+
+        let subscribe = json!({"id": 1, "type": "subscribe", "topic": {"project": project.id}});
+        ws_send(&mut alice, subscribe.clone()).await;
+        assert_eq!(ws_recv(&mut alice).await, json!({"type": "ack", "id": 1}));
+        ws_send(&mut bob, subscribe).await;
+        assert_eq!(ws_recv(&mut bob).await, json!({"type": "ack", "id": 1}));
+
+        let create = json!({
+            "id": "create",
+            "type": "create",
+            "todo": {"text": "shared todo", "project_id": project.id},
+        });
+        ws_send(&mut bob, create).await;
+        let ack = ws_recv(&mut bob).await;
+        assert_eq!(
+            (&ack["type"], &ack["id"]),
+            (&json!("ack"), &json!("create"))
+        );
+        let todo = ack["todo"].clone();
+        assert_eq!(todo["text"], "shared todo");
+        let created = ws_recv(&mut alice).await;
+        assert_eq!(created, json!({"type": "created", "todo": todo}));
+
+        let update = json!({
+            "id": 2,
+            "type": "update",
+            "todo_id": todo["id"],
+            "todo": {"completed": true},
+            "version": 1,
+        });
+        ws_send(&mut alice, update.clone()).await;
+        let ack = ws_recv(&mut alice).await;
+        assert_eq!(ack["todo"]["completed"], true);
+        // The first frame Bob gets is Alice's change, not his own creation again.
+        let updated = ws_recv(&mut bob).await;
+        assert_eq!(updated, json!({"type": "updated", "todo": ack["todo"]}));
+
+        ws_send(&mut alice, update).await;
+        let error = ws_recv(&mut alice).await;
+        assert_eq!((&error["type"], &error["id"]), (&json!("error"), &json!(2)));
+        assert_eq!(error["status"], 412);
+        ws_send(
+            &mut alice,
+            json!({"id": 3, "type": "create", "todo": {"text": ""}}),
+        )
+        .await;
+        assert_eq!(ws_recv(&mut alice).await["status"], 400);
+        ws_send(&mut alice, json!({"id": 4, "type": "rename"})).await;
+        let error = ws_recv(&mut alice).await;
+        assert_eq!((&error["id"], &error["status"]), (&json!(4), &json!(400)));
+        let missing = json!({"id": 5, "type": "subscribe", "topic": {"todo": 99}});
+        ws_send(&mut alice, missing).await;
+        assert_eq!(ws_recv(&mut alice).await["status"], 404);
+
+        // Changes made through the REST API reach every subscriber.
+        let id = todo["id"].as_i64().unwrap() as i32;
+        repository.delete(TEST_USER_ID, id, None).await.unwrap();
+        for socket in [&mut alice, &mut bob] {
+            let deleted = ws_recv(socket).await;
+            assert_eq!(
+                (&deleted["type"], &deleted["todo"]["id"]),
+                (&json!("deleted"), &json!(id))
+            );
+        }
+
+        ws_send(&mut bob, json!({"id": 6, "type": "delete", "todo_id": id})).await;
+        assert_eq!(ws_recv(&mut bob).await["status"], 404);
+    }
+
+    #[tokio::test]
+    async fn should_created_todo() {
+        let expected = Todo::new(1, "should_return_created_todo".to_string(), vec![]);
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "should_return_created_todo" }"#.to_string(),
+        );
+        let res = test_app().await.build().oneshot(req).await.unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(expected.with_timestamps_of(&todo), todo);
+    }
+
+    #[tokio::test]
+    async fn should_created_todo_with_labels() {
+        let (labels, _) = label_fixture();
+        let expected = Todo::new(
+            1,
+            "should_created_todo_with_labels".to_string(),
+            labels.clone(),
+        );
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "should_created_todo_with_labels", "labels": [999] }"#.to_string(),
+        );
+        let res = test_app().await.build().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(expected.with_timestamps_of(&todo), todo);
+    }
+
+    #[tokio::test]
+    async fn should_get_all_todos() {
+        let (labels, label_ids) = label_fixture();
+        let expected = Todo::new(1, "should_get_all_todos".to_string(), labels.clone());
+        let repository = TodoRepositoryForMemory::new(labels);
+        repository
+            .create(
+                TEST_USER_ID,
+                CreateTodo::new("should_get_all_todos".to_string(), label_ids),
+            )
+            .await
+            .expect("failed create todo");
+        let req = build_req_with_empty("/todos", Method::GET);
+        let res = test_app()
+            .await
+            .with_todos(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let page: TodoPage = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert TodoPage instance. body: {}", body));
+        assert_eq!(
+            TodoPage::new(vec![expected], None, 1).with_timestamps_of(&page),
+            page
+        );
+    }
+
+    #[tokio::test]
+    async fn should_filter_and_paginate_todos() {
+        let (labels, _) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        for text in ["buy milk", "walk dog", "buy bread", "buy eggs"] {
+            repository
+                .create(TEST_USER_ID, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create todo");
+        }
+        let app = test_app().await.with_todos(repository).build();
+
+        let req = build_req_with_empty("/todos?q=BUY&sort=text&order=asc&limit=2", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page = res_to_todo_page(res).await;
+        assert_eq!(
+            TodoPage::new(
+                vec![
+                    Todo::new(3, "buy bread".to_string(), vec![]),
+                    Todo::new(4, "buy eggs".to_string(), vec![]),
+                ],
+                Some(4),
+                3
+            )
+            .with_timestamps_of(&page),
+            page
+        );
+
+        let req = build_req_with_empty(
+            "/todos?q=BUY&sort=text&order=asc&limit=2&cursor=4",
+            Method::GET,
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page = res_to_todo_page(res).await;
+        assert_eq!(
+            TodoPage::new(vec![Todo::new(1, "buy milk".to_string(), vec![])], None, 3)
+                .with_timestamps_of(&page),
+            page
+        );
+
+        let req = build_req_with_empty("/todos?limit=0", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_list_overdue_todos() {
+        let app = test_app().await.build();
+        for body in [
+            r#"{ "text": "overdue", "priority": "urgent", "due_at": "2000-01-01T00:00:00Z" }"#,
+            r#"{ "text": "upcoming", "due_at": "2999-01-01T09:00:00+09:00" }"#,
+            r#"{ "text": "someday" }"#,
+        ] {
+            let req = build_req_with_json("/todos", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+
+        let req = build_req_with_empty("/todos?overdue=true", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page = res_to_todo_page(res).await;
+        let bytes = serde_json::to_vec(&page).unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, page["total"]);
+        assert_eq!("overdue", page["items"][0]["text"]);
+        assert_eq!("urgent", page["items"][0]["priority"]);
+
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "bad due date", "due_at": "tomorrow" }"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_update_todo() {
+        let (labels, label_ids) = label_fixture();
+        let expected = Todo::new(1, "should_update_todo".to_string(), vec![]);
+        let repository = TodoRepositoryForMemory::new(labels);
+        repository
+            .create(
+                TEST_USER_ID,
+                CreateTodo::new("before_update_todo".to_string(), label_ids),
+            )
+            .await
+            .expect("failed create todo");
+        let req = build_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{
+                "id": 1,
+                "text": "should_update_todo",
+                "completed": false,
+                "labels": []
+            }"#
+            .to_string(),
+        );
+        let res = test_app()
+            .await
+            .with_todos(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(expected.with_timestamps_of(&todo), todo);
+    }
+
+    #[tokio::test]
+    async fn should_delete_todo() {
+        let (labels, label_ids) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        repository
+            .create(
+                TEST_USER_ID,
+                CreateTodo::new("should_delete_todo".to_string(), label_ids),
+            )
+            .await
+            .expect("failed create todo");
+        let req = build_req_with_empty("/todos/1", Method::DELETE);
+        let res = test_app()
+            .await
+            .with_todos(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_return_problem_for_missing_todo() {
+        let req = build_req_with_empty("/todos/1", Method::GET);
+        let res = test_app().await.build().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        assert_eq!(
+            "application/problem+json",
+            res.headers().get(header::CONTENT_TYPE).unwrap()
+        );
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(404, problem["status"]);
+        assert_eq!("Not Found", problem["title"]);
+    }
+
+    #[tokio::test]
+    async fn should_reject_request_without_token() {
+        let app = test_app().await.build();
+
+        let req = Request::builder()
+            .uri("/todos")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        assert_eq!(
+            "Bearer",
+            res.headers().get(header::WWW_AUTHENTICATE).unwrap()
+        );
+
+        let req = Request::builder()
+            .uri("/labels")
+            .header(header::AUTHORIZATION, "Bearer unknown-token")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_hide_todos_of_other_users() {
+        let (labels, _) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        repository
+            .create(
+                TEST_USER_ID + 1,
+                CreateTodo::new("someone else's todo".to_string(), vec![]),
+            )
+            .await
+            .expect("failed create todo");
+        let app = test_app().await.with_todos(repository).build();
+
+        let req = build_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/todos", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let page = res_to_todo_page(res).await;
+        assert_eq!(TodoPage::new(vec![], None, 0), page);
+    }
+
+    #[tokio::test]
+    async fn should_hide_labels_of_other_users() {
+        let repository = LabelRepositoryForMemory::new();
+        repository
+            .create(
+                TEST_USER_ID + 1,
+                CreateLabel::new("someone else's label".to_string()),
+            )
+            .await
+            .expect("failed create label");
+        let app = test_app().await.with_labels(repository).build();
+
+        let req = build_req_with_empty("/labels/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r#"{ "name": "renamed" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/labels/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_json(
+            "/labels",
+            Method::POST,
+            r#"{ "name": "someone else's label" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(
+            StatusCode::CREATED,
+            res.status(),
+            "names are unique per user"
+        );
+
+        let req = build_req_with_empty("/labels", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let labels: Vec<Label> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            vec![Label::new(2, "someone else's label".to_string())],
+            labels
+        );
+    }
+
+    #[tokio::test]
+    async fn should_signup_and_login() {
+        let app = test_app()
+            .await
+            .with_users(UserRepositoryForMemory::new())
+            .build();
+        let credentials = r#"{ "email": "New@Example.com", "password": "correct horse" }"#;
+
+        let req = Request::builder()
+            .uri("/signup")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(credentials))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let user: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!("new@example.com", user["email"]);
+        assert!(user.get("password_hash").is_none());
+
+        let req = Request::builder()
+            .uri("/signup")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(credentials))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        let req = Request::builder()
+            .uri("/login")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(
+                r#"{ "email": "new@example.com", "password": "wrong password" }"#,
+            ))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let req = Request::builder()
+            .uri("/login")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(credentials))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let session: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!("Bearer", session["token_type"]);
+
+        let req = Request::builder()
+            .uri("/todos")
+            .header(
+                header::AUTHORIZATION,
+                format!("Bearer {}", session["token"].as_str().unwrap()),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_manage_project_todos() {
+        let (labels, _) = label_fixture();
+        let todos = TodoRepositoryForMemory::new(labels);
+        let app = test_app()
+            .await
+            .with_todos(todos.clone())
+            .with_projects(ProjectRepositoryForMemory::with_todos(todos))
+            .build();
+        let body = |res: Response| async {
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let req = build_req_with_json(
+            "/projects",
+            Method::POST,
+            r#"{ "name": "Groceries" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = build_req_with_json(
+            "/projects/1/todos",
+            Method::POST,
+            r#"{ "text": "buy milk" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(Some(1), todo.project_id());
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "in the inbox" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = build_req_with_empty("/projects/1/todos", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page = res_to_todo_page(res).await;
+        assert_eq!(TodoPage::new(vec![todo.clone()], None, 1), page);
+
+        let req = build_req_with_empty("/projects/2/todos", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/projects/1?todos=archive", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_req_with_empty("/projects/1?todos=cascade", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty("/projects/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_req_with_empty(&format!("/todos/{}", todo.id()), Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_req_with_empty("/trash", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let trash = body(res).await;
+        assert_eq!(trash.as_array().unwrap().len(), 1);
+        assert_eq!(trash[0]["id"], todo.id());
+        assert_eq!(trash[0]["project_id"], serde_json::Value::Null);
+
+        // Ids are not handed out again, so the inbox mode gets a project of its own.
+        let req = build_req_with_json(
+            "/projects",
+            Method::POST,
+            r#"{ "name": "Chores" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(body(res).await["id"], 2);
+        let req = build_req_with_json(
+            "/projects/2/todos",
+            Method::POST,
+            r#"{ "text": "water plants" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        let kept = res_to_todo(res).await;
+        let req = build_req_with_empty("/projects/2", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty(&format!("/todos/{}", kept.id()), Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res_to_todo(res).await.project_id(), None);
+    }
+
+    #[tokio::test]
+    async fn should_import_and_export_todos() {
+        let labels = LabelRepositoryForMemory::new();
+        let label = labels
+            .create(TEST_USER_ID, CreateLabel::new("test label".to_string()))
+            .await
+            .unwrap();
+        let app = test_app()
+            .await
+            .with_todos(TodoRepositoryForMemory::new(vec![label]))
+            .with_labels(labels)
+            .build();
+        let body = |res: Response| async {
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+        let csv = "text,priority,labels\r\nbuy milk,high,1\r\n,low,\r\nwalk dog,,\r\n";
+
+        let req = build_req_with_json("/todos/import?format=csv", Method::POST, csv.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let problem: serde_json::Value = serde_json::from_str(&body(res).await).unwrap();
+        assert_eq!(problem["errors"][0]["line"], 3);
+
+        let csv = csv.replace(",low,", "feed cat,low,");
+        // Someone else's label or project fails a dry run like the import.
+        let req = build_req_with_json(
+            "/todos/import?format=csv&dry_run=true",
+            Method::POST,
+            csv.replace("feed cat,low,", "feed cat,low,2"),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let problem: serde_json::Value = serde_json::from_str(&body(res).await).unwrap();
+        assert_eq!(
+            problem["errors"],
+            json!([{ "line": 3, "message": "label not found, id is 2" }])
+        );
+        let req = build_req_with_json(
+            "/todos/import?format=todotxt&dry_run=true",
+            Method::POST,
+            "call mom project:1\n".to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+
+        let req = build_req_with_json(
+            "/todos/import?format=csv&dry_run=true",
+            Method::POST,
+            csv.clone(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let report: serde_json::Value = serde_json::from_str(&body(res).await).unwrap();
+        assert_eq!(report, json!({ "dry_run": true, "count": 3, "todos": [] }));
+
+        let req = build_req_with_json("/todos/import?format=csv", Method::POST, csv);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let report: serde_json::Value = serde_json::from_str(&body(res).await).unwrap();
+        assert_eq!(report["count"], 3);
+        assert_eq!(report["todos"][0]["labels"][0]["name"], "test label");
+
+        let req = build_req_with_empty("/todos/export?format=todotxt", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(
+            res.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"todo.txt\""
+        );
+        let today = Utc::now().format("%Y-%m-%d");
+        assert_eq!(
+            body(res).await,
+            format!(
+                "(B) {today} buy milk id:1 labels:1\n(D) {today} feed cat id:2\n\
+                 (C) {today} walk dog id:3\n"
+            )
+        );
+
+        let req = build_req_with_empty("/todos/export?format=xlsx", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_serve_calendar_feed_by_token() {
+        let (labels, _) = label_fixture();
+        let todos = TodoRepositoryForMemory::new(labels);
+        todos
+            .create(
+                TEST_USER_ID,
+                CreateTodo::new("file taxes".to_string(), vec![])
+                    .with_due_at("2024-04-15T00:00:00Z".parse().unwrap()),
+            )
+            .await
+            .expect("failed create todo");
+        let app = test_app().await.with_todos(todos).build();
+        let get = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+
+        let req = build_req_with_empty("/calendar/token", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let feed: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let url = feed["url"].as_str().unwrap().to_string();
+
+        let res = app.clone().oneshot(get(&url)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            "text/calendar; charset=utf-8"
+        );
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let calendar = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.contains("SUMMARY:file taxes\r\n"));
+        assert!(calendar.contains("DUE;VALUE=DATE:20240415\r\n"));
+
+        let res = app
+            .clone()
+            .oneshot(get("/calendar.ics?token=guess"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let req = build_req_with_empty("/calendar/token", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app.oneshot(get(&url)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_sync_todos_over_caldav() {
+        let todos = TodoRepositoryForMemory::new(vec![]);
+        let taxes = todos
+            .create(
+                TEST_USER_ID,
+                CreateTodo::new("file taxes".to_string(), vec![]),
+            )
+            .await
+            .expect("failed create todo");
+        let app = test_app()
+            .await
+            .with_todos(todos.clone())
+            .with_calendar_objects(CalendarObjectRepositoryForMemory::with_todos(todos.clone()))
+            .build();
+        let dav = |method: &str, path: &str, headers: &[(&str, &str)], body: &str| {
+            let mut req = Request::builder()
+                .uri(path)
+                .method(Method::from_bytes(method.as_bytes()).unwrap())
+                .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN));
+            for (name, value) in headers {
+                req = req.header(*name, *value);
+            }
+            req.body(Body::from(body.to_string())).unwrap()
+        };
+        let text = |res: Response| async {
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(bytes.to_vec()).unwrap()
+        };
+        let ctag = |body: &str| {
+            let start = body.find("<cs:getctag>").unwrap() + "<cs:getctag>".len();
+            body[start..start + 64].to_string()
+        };
+        let propfind = r#"<d:propfind xmlns:d="DAV:" xmlns:cs="http://calendarserver.org/ns/">
+            <d:prop><d:getetag/><cs:getctag/><d:quota-used-bytes/></d:prop>
+        </d:propfind>"#;
+        let vtodo = |summary: &str| {
+            format!(
+                "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:ABC-123\r\n\
+                 SUMMARY:{}\r\nPRIORITY:1\r\nDUE;VALUE=DATE:20240503\r\n\
+                 END:VTODO\r\nEND:VCALENDAR\r\n",
+                summary
+            )
+        };
+
+        let res = app
+            .clone()
+            .oneshot(dav("PROPFIND", "/.well-known/caldav", &[], ""))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::MOVED_PERMANENTLY, res.status());
+        assert_eq!(res.headers()[header::LOCATION], "/dav/");
+
+        let req = dav("PROPFIND", "/dav/", &[("depth", "0")], "");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::MULTI_STATUS, res.status());
+        let body = text(res).await;
+        assert!(body.contains(
+            "<d:current-user-principal><d:href>/dav/principal/</d:href></d:current-user-principal>"
+        ));
+
+        let req = dav(
+            "PROPFIND",
+            "/dav/calendars/todos/",
+            &[("depth", "1")],
+            propfind,
+        );
+        let body = text(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(body.contains(&format!(
+            "<d:href>/dav/calendars/todos/todo-{}.ics</d:href>\
+             <d:propstat><d:prop><d:getetag>&quot;{}-{}&quot;</d:getetag></d:prop>",
+            taxes.id(),
+            taxes.id(),
+            taxes.version()
+        )));
+        assert!(body.contains("<d:quota-used-bytes/></d:prop><d:status>HTTP/1.1 404 Not Found"));
+        let first_ctag = ctag(&body);
+
+        let path = "/dav/calendars/todos/ABC-123.ics";
+        let req = dav("PUT", path, &[("if-none-match", "*")], &vtodo("call mom"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let created_etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+        let req = dav("PUT", path, &[("if-none-match", "*")], &vtodo("call mom"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+
+        // The todo made over CalDAV is a regular todo over REST.
+        let page = todos.all(TEST_USER_ID, Default::default()).await.unwrap();
+        let mom = page
+            .items()
+            .iter()
+            .find(|todo| todo.text() == "call mom")
+            .expect("todo created over CalDAV")
+            .clone();
+        assert_eq!(mom.due_at(), Some("2024-05-03T00:00:00Z".parse().unwrap()));
+        let req = build_req_with_json(
+            &format!("/todos/{}", mom.id()),
+            Method::PATCH,
+            r#"{"text": "call mom back"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = dav(
+            "PROPFIND",
+            "/dav/calendars/todos/",
+            &[("depth", "0")],
+            propfind,
+        );
+        let body = text(app.clone().oneshot(req).await.unwrap()).await;
+        assert_ne!(ctag(&body), first_ctag, "the ctag follows every change");
+
+        let multiget = r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/><c:calendar-data/></d:prop>
+            <d:href>/dav/calendars/todos/ABC-123.ics</d:href>
+            <d:href>/dav/calendars/todos/gone.ics</d:href>
+        </c:calendar-multiget>"#;
+        let req = dav("REPORT", "/dav/calendars/todos/", &[], multiget);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::MULTI_STATUS, res.status());
+        let body = text(res).await;
+        assert!(body.contains("UID:ABC-123"));
+        assert!(body.contains("SUMMARY:call mom back"));
+        assert!(body.contains(
+            "<d:href>/dav/calendars/todos/gone.ics</d:href><d:status>HTTP/1.1 404 Not Found"
+        ));
+
+        let query = r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+            <d:prop><d:getetag/></d:prop>
+            <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+        </c:calendar-query>"#;
+        let req = dav("REPORT", "/dav/calendars/todos/", &[("depth", "1")], query);
+        let body = text(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(body.contains(&format!("todo-{}.ics", taxes.id())));
+        assert!(body.contains("ABC-123.ics"));
+
+        // The REST edit bumped the version, so the ETag the client holds is stale.
+        let headers = [("if-match", created_etag.as_str())];
+        let req = dav("PUT", path, &headers, &vtodo("call dad"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+
+        let res = app
+            .clone()
+            .oneshot(dav("GET", path, &[], ""))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let etag = res.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert!(text(res).await.contains("SUMMARY:call mom back"));
+        let headers = [("if-match", etag.as_str())];
+        let req = dav("PUT", path, &headers, &vtodo("call dad"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let todo = todos.find(TEST_USER_ID, mom.id()).await.unwrap();
+        assert_eq!(todo.text(), "call dad");
+
+        // Todos without a name of their own are served under their id only.
+        let taxes_path = format!("/dav/calendars/todos/todo-{}.ics", taxes.id());
+        let res = app
+            .clone()
+            .oneshot(dav("GET", &taxes_path, &[], ""))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(text(res).await.contains("SUMMARY:file taxes"));
+        let mom_path = format!("/dav/calendars/todos/todo-{}.ics", mom.id());
+        let res = app
+            .clone()
+            .oneshot(dav("GET", &mom_path, &[], ""))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = dav("PROPFIND", path, &[("depth", "0")], propfind);
+        let body = text(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(body.contains("<d:getetag>"));
+
+        let req = dav("PUT", path, &[], "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let other = vtodo("call dad").replace("UID:ABC-123", "UID:XYZ-789");
+        let res = app
+            .clone()
+            .oneshot(dav("PUT", path, &[], &other))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        assert!(text(res).await.contains("<c:no-uid-conflict"));
+
+        // Completed elsewhere, so no next occurrence is due.
+        let done = vtodo("water plants")
+            .replace("UID:ABC-123", "UID:PLANTS")
+            .replace(
+                "END:VTODO",
+                "STATUS:COMPLETED\r\nRRULE:FREQ=DAILY\r\nEND:VTODO",
+            );
+        let before = todos.all(TEST_USER_ID, Default::default()).await.unwrap();
+        let req = dav("PUT", "/dav/calendars/todos/plants.ics", &[], &done);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let after = todos.all(TEST_USER_ID, Default::default()).await.unwrap();
+        assert_eq!(after.total(), before.total() + 1);
+        let plants = after
+            .items()
+            .iter()
+            .find(|todo| todo.text() == "water plants")
+            .unwrap();
+        assert!(plants.completed());
+
+        let res = app
+            .clone()
+            .oneshot(dav("DELETE", path, &[], ""))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty(&format!("/todos/{}", mom.id()), Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let res = app
+            .clone()
+            .oneshot(dav("GET", path, &[], ""))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_log_caldav_clients_in_with_basic_auth() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let users = UserRepositoryForMemory::new();
+        users
+            .create(
+                "dav@example.com".to_string(),
+                auth::hash_password("secret").unwrap(),
+            )
+            .await
+            .expect("failed create user");
+        let app = test_app()
+            .await
+            .with_todos(TodoRepositoryForMemory::new(vec![]))
+            .with_users(users)
+            .build();
+        let propfind = |authorization: Option<String>| {
+            let mut req = Request::builder()
+                .uri("/dav/principal/")
+                .method(Method::from_bytes(b"PROPFIND").unwrap());
+            if let Some(authorization) = authorization {
+                req = req.header(header::AUTHORIZATION, authorization);
+            }
+            req.body(Body::empty()).unwrap()
+        };
+        let basic = |credentials: &str| Some(format!("Basic {}", STANDARD.encode(credentials)));
+
+        let res = app.clone().oneshot(propfind(None)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        assert!(res.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap()
+            .starts_with("Basic "));
+
+        let req = propfind(basic("DAV@example.com:secret"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::MULTI_STATUS, res.status());
+
+        let req = propfind(basic("dav@example.com:guess"));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        // Only the DAV tree takes passwords.
+        let req = Request::builder()
+            .uri("/todos")
+            .header(
+                header::AUTHORIZATION,
+                basic("dav@example.com:secret").unwrap(),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_manage_webhooks() {
+        let webhooks = WebhookRepositoryForMemory::new();
+        let app = test_app().await.with_webhooks(webhooks.clone()).build();
+        let body = |res: Response| async {
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let req = build_req_with_json(
+            "/webhooks",
+            Method::POST,
+            r#"{ "url": "ftp://example.com/hook", "secret": "0123456789abcdef", "events": ["created"] }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_req_with_json(
+            "/webhooks",
+            Method::POST,
+            r#"{ "url": "https://example.com/hook", "secret": "0123456789abcdef", "events": ["created"] }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let webhook = body(res).await;
+        assert_eq!(webhook["url"], "https://example.com/hook");
+        assert_eq!(webhook.get("secret"), None, "the secret is write-only");
+
+        let req = build_req_with_json(
+            "/webhooks/1",
+            Method::PATCH,
+            r#"{ "events": ["created", "deleted"] }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!(body(res).await["events"], json!(["created", "deleted"]));
+
+        webhooks
+            .enqueue(TEST_USER_ID, ChangeKind::Deleted, json!({}))
+            .await
+            .unwrap();
+        let req = build_req_with_empty("/webhooks/1/deliveries", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let deliveries = body(res).await;
+        assert_eq!(deliveries[0]["event"], "deleted");
+        assert_eq!(deliveries[0]["state"], "pending");
+
+        let req = build_req_with_empty("/webhooks/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty("/webhooks", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(body(res).await, json!([]));
+    }
+
+    #[tokio::test]
+    async fn should_search_todos() {
+        let (labels, _) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        for text in ["buy oat milk", "walk dog"] {
+            repository
+                .create(TEST_USER_ID, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create todo");
+        }
+        let app = test_app().await.with_todos(repository).build();
+
+        let req = build_req_with_empty("/todos/search?q=Milk", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let hits: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, hits.as_array().unwrap().len());
+        assert_eq!("buy oat milk", hits[0]["text"]);
+        assert_eq!("buy oat <mark>milk</mark>", hits[0]["snippet"]);
+
+        let req = build_req_with_empty("/todos/search?q=", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_expand_children() {
+        let app = test_app().await.build();
+        for body in [
+            r#"{ "text": "release" }"#,
+            r#"{ "text": "write changelog", "parent_id": 1 }"#,
+            r#"{ "text": "tag", "parent_id": 1 }"#,
+            r#"{ "text": "push tag", "parent_id": 3 }"#,
+        ] {
+            let req = build_req_with_json("/todos", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+
+        let req = build_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(None, todo.children());
+
+        let req = build_req_with_empty("/todos/1?expand=children", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo = res_to_todo(res).await;
+        let children = todo.children().unwrap();
+        assert_eq!(2, children.len());
+        assert_eq!(3, children[1].id());
+        assert_eq!(1, children[1].children().unwrap().len());
+
+        let req = build_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{ "parent_id": 4 }"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            "Validation error: [parent would create a cycle, id is 4]",
+            problem["detail"]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_trash_restore_and_purge_todos() {
+        let (labels, _) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        repository
+            .create(TEST_USER_ID, CreateTodo::new("oops".to_string(), vec![]))
+            .await
+            .expect("failed create todo");
+        let app = test_app().await.with_todos(repository).build();
+
+        let req = build_req_with_empty("/todos/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/trash", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let trash: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, trash.as_array().unwrap().len());
+        assert!(trash[0]["deleted_at"].is_string());
+
+        let req = build_req_with_empty("/todos/1/restore", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(
+            Todo::new(1, "oops".to_string(), vec![]).with_timestamps_of(&todo),
+            todo
+        );
+
+        let req = build_req_with_empty("/trash/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/todos/1", Method::DELETE);
+        app.clone().oneshot(req).await.unwrap();
+        let req = build_req_with_empty("/trash/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty("/todos/1/restore", Method::POST);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_record_history() {
+        let app = test_app().await.build();
+        let req = build_req_with_json("/todos", Method::POST, r#"{ "text": "draft" }"#.to_string());
+        app.clone().oneshot(req).await.unwrap();
+        let req = build_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{ "completed": true }"#.to_string(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+
+        let req = build_req_with_empty("/todos/1/history", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let events: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(2, events.as_array().unwrap().len());
+        assert_eq!("update", events[1]["action"]);
+        assert_eq!(TEST_USER_ID, events[1]["actor_id"]);
+        assert_eq!(false, events[1]["before"]["completed"]);
+        assert_eq!(true, events[1]["after"]["completed"]);
+
+        let req = build_req_with_empty("/audit?action=create", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let page: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(1, page["items"].as_array().unwrap().len());
+        assert_eq!("draft", page["items"][0]["after"]["text"]);
+
+        let req = build_req_with_empty("/todos/2/history", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/audit?limit=0", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_undo_and_redo_changes() {
+        let app = test_app().await.build();
+        let req = build_req_with_json("/todos", Method::POST, r#"{ "text": "draft" }"#.to_string());
+        app.clone().oneshot(req).await.unwrap();
+
+        let req = build_req_with_empty("/todos/1/undo", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        let req = build_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{ "completed": true }"#.to_string(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+        let req = build_req_with_empty("/todos/1/undo", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(
+            Todo::new(1, "draft".to_string(), vec![]).with_timestamps_of(&todo),
+            todo
+        );
+
+        let req = build_req_with_empty("/todos/1/redo", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let todo: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(true, todo["completed"]);
+
+        let req = build_req_with_empty("/todos/1/redo", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        let req = build_req_with_empty("/todos/2/undo", Method::POST);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_check_entity_tags() {
+        let app = test_app().await.build();
+        let req = build_req_with_json("/todos", Method::POST, r#"{ "text": "draft" }"#.to_string());
+        app.clone().oneshot(req).await.unwrap();
+
+        let req = build_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!("\"1\"", res.headers()[header::ETAG]);
+        let mut req = build_req_with_empty("/todos/1", Method::GET);
+        req.headers_mut()
+            .insert(header::IF_NONE_MATCH, "W/\"1\"".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_MODIFIED, res.status());
+
+        let mut req = build_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{ "text": "final" }"#.to_string(),
+        );
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"1\"".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!("\"2\"", res.headers()[header::ETAG]);
+
+        let mut req = build_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{ "text": "lost" }"#.to_string(),
+        );
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"1\"".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+        let mut req = build_req_with_empty("/todos/1", Method::GET);
+        req.headers_mut()
+            .insert(header::IF_NONE_MATCH, "\"1\"".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(
+            Todo::new(1, "final".to_string(), vec![]).with_timestamps_of(&todo),
+            todo
+        );
+
+        let mut req = build_req_with_empty("/todos/1", Method::DELETE);
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"1\", W/\"2\"".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+        let mut req = build_req_with_empty("/todos/1", Method::DELETE);
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"1\", \"2\"".parse().unwrap());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_replay_idempotent_create() {
+        let (labels, _) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        let app = test_app().await.with_todos(repository.clone()).build();
+        let create = |key: &str, text: &str| {
+            let mut req = build_req_with_json(
+                "/todos",
+                Method::POST,
+                format!(r#"{{ "text": "{}" }}"#, text),
+            );
+            req.headers_mut()
+                .insert("idempotency-key", key.parse().unwrap());
+            req
+        };
+
+        let res = app.clone().oneshot(create("retry", "once")).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert!(res.headers().get("idempotent-replayed").is_none());
+        let first = res_to_todo(res).await;
+
+        let res = app.clone().oneshot(create("retry", "once")).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!("true", res.headers()["idempotent-replayed"]);
+        assert_eq!(first, res_to_todo(res).await);
+        let page = repository
+            .all(TEST_USER_ID, Default::default())
+            .await
+            .expect("failed all todo");
+        assert_eq!(
+            TodoPage::new(vec![first.clone()], None, 1).with_timestamps_of(&page),
+            page
+        );
+
+        let res = app.clone().oneshot(create("retry", "twice")).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let res = app.clone().oneshot(create("", "once")).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let res = app.oneshot(create("other", "once")).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_ne!(first, res_to_todo(res).await);
+    }
+
+    #[tokio::test]
+    async fn should_apply_batch() {
+        let app = test_app().await.build();
+        let req = build_req_with_json(
+            "/todos/batch",
+            Method::POST,
+            r#"{ "operations": [
+                { "op": "create", "todo": { "text": "first" } },
+                { "op": "create", "todo": { "text": "second" } },
+                { "op": "update", "id": 1, "todo": { "completed": true } }
+            ] }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(3, results.len());
+        assert_eq!("create", results[0]["op"]);
+        assert_eq!("second", results[1]["todo"]["text"]);
+        assert_eq!(true, results[2]["todo"]["completed"]);
+
+        let req = build_req_with_json(
+            "/todos/batch",
+            Method::POST,
+            r#"{ "operations": [
+                { "op": "delete", "id": 2 },
+                { "op": "delete", "id": 99 },
+                { "op": "create", "todo": { "text": "third" } }
+            ] }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let outcomes: Vec<&str> = body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["outcome"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["rolled_back", "failed", "skipped"], outcomes);
+
+        let req = build_req_with_empty("/todos/2", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = build_req_with_json(
+            "/todos/batch",
+            Method::POST,
+            r#"{ "operations": [{ "op": "create", "todo": { "text": "" } }] }"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_complete_all_and_clear_completed() {
+        let (labels, _) = label_fixture();
+        let repository = TodoRepositoryForMemory::new(labels);
+        for text in ["first", "second", "third"] {
+            repository
+                .create(TEST_USER_ID, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create todo");
+        }
+        let app = test_app().await.with_todos(repository).build();
+
+        let req = build_req_with_empty("/todos/complete-all?q=ir", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(2, body["completed"]);
+
+        let req = build_req_with_empty("/todos/completed", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(2, body["deleted"]);
+
+        let req = build_req_with_empty("/todos", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let page = res_to_todo_page(res).await;
+        assert_eq!(
+            TodoPage::new(vec![Todo::new(2, "second".to_string(), vec![])], None, 1)
+                .with_timestamps_of(&page),
+            page
+        );
+    }
+
+    #[tokio::test]
+    async fn should_created_label() {
+        let expected = Label::new(1, "should_created_label".to_string());
+        let req = build_req_with_json(
+            "/labels",
+            Method::POST,
+            r#"{ "name": "should_created_label" }"#.to_string(),
+        );
+        let res = test_app().await.build().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let label = res_to_label(res).await;
+        assert_eq!(expected, label);
+    }
+
+    #[tokio::test]
+    async fn should_conflict_on_duplicate_label() {
+        let repository = LabelRepositoryForMemory::new();
+        repository
+            .create(TEST_USER_ID, CreateLabel::new("duplicate".to_string()))
+            .await
+            .expect("failed create label");
+        let req = build_req_with_json(
+            "/labels",
+            Method::POST,
+            r#"{ "name": "duplicate" }"#.to_string(),
+        );
+        let res = test_app()
+            .await
+            .with_labels(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_get_all_labels() {
+        let expected = Label::new(1, "should_get_all_labels".to_string());
+        let repository = LabelRepositoryForMemory::new();
+        repository
+            .create(
+                TEST_USER_ID,
+                CreateLabel::new("should_get_all_labels".to_string()),
+            )
+            .await
+            .expect("failed create label");
+        let req = build_req_with_empty("/labels", Method::GET);
+        let res = test_app()
+            .await
+            .with_labels(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let labels: Vec<Label> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
+        assert_eq!(vec![expected], labels);
+    }
+
+    #[tokio::test]
+    async fn should_update_label() {
+        let expected = Label::new(1, "should_update_label".to_string());
+        let repository = LabelRepositoryForMemory::new();
+        repository
+            .create(
+                TEST_USER_ID,
+                CreateLabel::new("before_update_label".to_string()),
+            )
+            .await
+            .expect("failed create label");
+        let req = build_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r#"{ "name": "should_update_label" }"#.to_string(),
+        );
+        let res = test_app()
+            .await
+            .with_labels(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        let label = res_to_label(res).await;
+        assert_eq!(expected, label);
+    }
+
+    #[tokio::test]
+    async fn should_delete_label() {
+        let repository = LabelRepositoryForMemory::new();
+        repository
+            .create(
+                TEST_USER_ID,
+                CreateLabel::new("should_delete_label".to_string()),
+            )
+            .await
+            .expect("failed create label");
+        let req = build_req_with_empty("/labels/1", Method::DELETE);
+        let res = test_app()
+            .await
+            .with_labels(repository)
+            .build()
+            .oneshot(req)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+}
@@ -1,17 +1,22 @@
-mod handlers;
-mod repositories;
-
-use crate::repositories::{TodoRepository, TodoRepositoryForDB};
-use axum::{
-    extract::Extension,
-    routing::{get, post},
-    Router,
-};
 use dotenv::dotenv;
-use handlers::{all_todo, create_todo, delete_todo, find_todo, update_todo};
 use sqlx::PgPool;
+use std::env;
 use std::net::SocketAddr;
-use std::{env, sync::Arc};
+use todo_rust::{
+    create_app, idempotency,
+    repositories::{
+        self,
+        calendar_object::{CalendarObjectRepositoryForDB, CalendarObjectRepositoryForSqlite},
+        idempotency::{IdempotencyRepositoryForDB, IdempotencyRepositoryForSqlite},
+        label::{LabelRepositoryForDB, LabelRepositoryForSqlite},
+        project::{ProjectRepositoryForDB, ProjectRepositoryForSqlite},
+        todo::{TodoRepositoryForDB, TodoRepositoryForSqlite},
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+        webhook::{WebhookRepositoryForDB, WebhookRepositoryForSqlite},
+    },
+    trash,
+    webhooks::{self, DeliveryPolicy},
+};
 
 #[tokio::main]
 async fn main() {
@@ -20,160 +25,74 @@ async fn main() {
     tracing_subscriber::fmt::init();
     dotenv().ok();
 
-    let user = env::var("POSTGRES_USER").unwrap_or("postgres".to_string());
-    let password = env::var("POSTGRES_PASSWORD").unwrap_or("postgres".to_string());
-    let db = env::var("POSTGRES_DB").unwrap_or("postgres".to_string());
-    let host = env::var("POSTGRES_HOSTNAME").unwrap_or("localhost".to_string());
-    let port = env::var("POSTGRES_PORT").unwrap_or("5432".to_string());
-    let database_url = &format!(
-        "postgresql://{}:{}@{}:{}/{}",
-        user, password, host, port, db
-    );
+    let database_url = database_url();
     tracing::debug!("start connect database...");
-    let pool = PgPool::connect(database_url).await.expect(&format!(
-        "fail to connect database, url is [{}]",
-        database_url
-    ));
-    let repository = TodoRepositoryForDB::new(pool.clone());
-    let app = create_app(repository);
+    let app = if database_url.starts_with("sqlite:") {
+        let pool = repositories::connect_sqlite(&database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail to connect database, url is [{}]", database_url));
+        let todo_repository = TodoRepositoryForSqlite::new(pool.clone());
+        trash::spawn_purge(todo_repository.clone(), trash::retention());
+        let idempotency_repository = IdempotencyRepositoryForSqlite::new(pool.clone());
+        idempotency::spawn_purge(idempotency_repository.clone());
+        let project_repository = ProjectRepositoryForSqlite::new(todo_repository.clone());
+        let calendar_object_repository =
+            CalendarObjectRepositoryForSqlite::new(todo_repository.clone());
+        let webhook_repository = WebhookRepositoryForSqlite::new(pool.clone());
+        webhooks::spawn_dispatch(&todo_repository, webhook_repository.clone());
+        webhooks::spawn_delivery(webhook_repository.clone(), DeliveryPolicy::default());
+        create_app(
+            todo_repository,
+            LabelRepositoryForSqlite::new(pool.clone()),
+            UserRepositoryForSqlite::new(pool.clone()),
+            project_repository,
+            idempotency_repository,
+            webhook_repository,
+            calendar_object_repository,
+        )
+    } else {
+        let pool = PgPool::connect(&database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail to connect database, url is [{}]", database_url));
+        let todo_repository = TodoRepositoryForDB::new(pool.clone());
+        trash::spawn_purge(todo_repository.clone(), trash::retention());
+        let idempotency_repository = IdempotencyRepositoryForDB::new(pool.clone());
+        idempotency::spawn_purge(idempotency_repository.clone());
+        let project_repository = ProjectRepositoryForDB::new(todo_repository.clone());
+        let calendar_object_repository =
+            CalendarObjectRepositoryForDB::new(todo_repository.clone());
+        let webhook_repository = WebhookRepositoryForDB::new(pool.clone());
+        webhooks::spawn_dispatch(&todo_repository, webhook_repository.clone());
+        webhooks::spawn_delivery(webhook_repository.clone(), DeliveryPolicy::default());
+        create_app(
+            todo_repository,
+            LabelRepositoryForDB::new(pool.clone()),
+            UserRepositoryForDB::new(pool.clone()),
+            project_repository,
+            idempotency_repository,
+            webhook_repository,
+            calendar_object_repository,
+        )
+    };
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     tracing::debug!("listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
-fn create_app<T: TodoRepository>(repository: T) -> Router {
-    Router::new()
-        .route("/", get(root))
-        .route("/todos", post(create_todo::<T>).get(all_todo::<T>))
-        .route(
-            "/todos/:id",
-            get(find_todo::<T>)
-                .delete(delete_todo::<T>)
-                .patch(update_todo::<T>),
-        )
-        .layer(Extension(Arc::new(repository)))
-}
-
-async fn root() -> &'static str {
-    "Hello, World!"
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::repositories::{test_utils::TodoRepositoryForMemory, CreateTodo, Todo};
-    use axum::response::Response;
-    use axum::{
-        body::Body,
-        http::{header, Method, Request, StatusCode},
-    };
-    use tower::ServiceExt;
-
-    fn build_todo_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
-        Request::builder()
-            .uri(path)
-            .method(method)
-            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-            .body(Body::from(json_body))
-            .unwrap()
-    }
-
-    fn build_todo_req_with_empty(path: &str, method: Method) -> Request<Body> {
-        Request::builder()
-            .uri(path)
-            .method(method)
-            .body(Body::empty())
-            .unwrap()
-    }
-
-    async fn res_to_todo(res: Response) -> Todo {
-        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
-            .await
-            .unwrap();
-        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
-        let todo: Todo = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Todo instance. body: {}", body));
-        todo
-    }
-
-    #[tokio::test]
-    async fn should_return_hello_world() {
-        let repository = TodoRepositoryForMemory::new();
-        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
-            .await
-            .unwrap();
-        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
-        assert_eq!(body, "Hello, World!");
-    }
-
-    #[tokio::test]
-    async fn should_created_todo() {
-        let expected = Todo::new(1, "should_return_created_todo".to_string());
-        let repository = TodoRepositoryForMemory::new();
-        let req = build_todo_req_with_json(
-            "/todos",
-            Method::POST,
-            r#"{ "text": "should_return_created_todo" }"#.to_string(),
-        );
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let todo = res_to_todo(res).await;
-        assert_eq!(expected, todo);
-    }
-
-    #[tokio::test]
-    async fn should_get_all_todos() {
-        let expected = Todo::new(1, "should_get_all_todos".to_string());
-        let repository = TodoRepositoryForMemory::new();
-        repository
-            .create(CreateTodo::new("should_get_all_todos".to_string()))
-            .await
-            .expect("failed create todo");
-        let req = build_todo_req_with_empty("/todos", Method::GET);
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
-            .await
-            .unwrap();
-        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
-        let todo: Vec<Todo> = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Todo instance. body: {}", body));
-        assert_eq!(vec![expected], todo);
-    }
-
-    #[tokio::test]
-    async fn should_update_todo() {
-        let expected = Todo::new(1, "should_update_todo".to_string());
-        let repository = TodoRepositoryForMemory::new();
-        repository
-            .create(CreateTodo::new("before_update_todo".to_string()))
-            .await
-            .expect("failed create todo");
-        let req = build_todo_req_with_json(
-            "/todos/1",
-            Method::PATCH,
-            r#"{
-                "id": 1,
-                "text": "should_update_todo",
-                "completed": false
-            }"#
-            .to_string(),
-        );
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let todo = res_to_todo(res).await;
-        assert_eq!(expected, todo);
-    }
-
-    #[tokio::test]
-    async fn should_delete_todo() {
-        let repository = TodoRepositoryForMemory::new();
-        repository
-            .create(CreateTodo::new("should_delete_todo".to_string()))
-            .await
-            .expect("failed create todo");
-        let req = build_todo_req_with_empty("/todos/1", Method::DELETE);
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        assert_eq!(StatusCode::NO_CONTENT, res.status());
+// `DATABASE_URL` picks the backend by its scheme (`sqlite:` or `postgres:`); without it
+// the Postgres url is assembled from the `POSTGRES_*` variables.
+fn database_url() -> String {
+    if let Ok(database_url) = env::var("DATABASE_URL") {
+        return database_url;
     }
+    let user = env::var("POSTGRES_USER").unwrap_or("postgres".to_string());
+    let password = env::var("POSTGRES_PASSWORD").unwrap_or("postgres".to_string());
+    let db = env::var("POSTGRES_DB").unwrap_or("postgres".to_string());
+    let host = env::var("POSTGRES_HOSTNAME").unwrap_or("localhost".to_string());
+    let port = env::var("POSTGRES_PORT").unwrap_or("5432".to_string());
+    format!(
+        "postgresql://{}:{}@{}:{}/{}",
+        user, password, host, port, db
+    )
 }
@@ -0,0 +1,264 @@
+use crate::handlers::{calendar, label, project, todo, user, webhook, ws, Problem};
+use crate::repositories::{
+    changes::ChangeKind,
+    label::{CreateLabel, Label, UpdateLabel},
+    project::{CreateProject, Project, ProjectDeleteMode, UpdateProject},
+    todo::{
+        CreateTodo, Priority, SortOrder, Todo, TodoAction, TodoBatch, TodoEvent, TodoEventPage,
+        TodoExpand, TodoOperation, TodoOperationResult, TodoPage, TodoSearchHit, TodoSort,
+        UpdateTodo,
+    },
+    user::{Credentials, User},
+    webhook::{CreateWebhook, DeliveryState, UpdateWebhook, Webhook, WebhookDelivery},
+};
+use crate::transfer::{Format, ImportReport, LineError};
+use axum::{
+    response::{Html, IntoResponse},
+    Json,
+};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+const DOCS: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>todo-rust API</title>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+  </head>
+  <body>
+    <redoc spec-url="/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js"></script>
+  </body>
+</html>
+"#;
+
+/// The API as served by `create_app`. Every route needs its handler listed in
+/// `paths`, see `should_document_every_route`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::root,
+        openapi_json,
+        docs,
+        user::signup,
+        user::login,
+        todo::create_todo,
+        todo::all_todo,
+        todo::search_todo,
+        todo::todo_events,
+        todo::batch_todo,
+        todo::export_todo,
+        todo::import_todo,
+        todo::complete_all_todo,
+        todo::clear_completed_todo,
+        todo::find_todo,
+        todo::update_todo,
+        todo::delete_todo,
+        todo::restore_todo,
+        todo::undo_todo,
+        todo::redo_todo,
+        todo::history_todo,
+        todo::all_audit,
+        todo::all_trash,
+        todo::purge_todo,
+        ws::websocket,
+        label::create_label,
+        label::all_label,
+        label::find_label,
+        label::update_label,
+        label::delete_label,
+        project::create_project,
+        project::all_project,
+        project::find_project,
+        project::update_project,
+        project::delete_project,
+        project::create_project_todo,
+        project::all_project_todo,
+        calendar::create_calendar_token,
+        calendar::delete_calendar_token,
+        calendar::calendar_feed,
+        webhook::create_webhook,
+        webhook::all_webhook,
+        webhook::find_webhook,
+        webhook::update_webhook,
+        webhook::delete_webhook,
+        webhook::all_webhook_delivery,
+    ),
+    components(schemas(
+        Problem,
+        User,
+        Credentials,
+        user::Session,
+        Todo,
+        Priority,
+        CreateTodo,
+        UpdateTodo,
+        TodoPage,
+        TodoSort,
+        SortOrder,
+        TodoExpand,
+        TodoSearchHit,
+        TodoOperation,
+        TodoOperationResult,
+        TodoBatch,
+        TodoAction,
+        TodoEvent,
+        TodoEventPage,
+        Format,
+        ImportReport,
+        LineError,
+        Label,
+        CreateLabel,
+        UpdateLabel,
+        Project,
+        CreateProject,
+        UpdateProject,
+        ProjectDeleteMode,
+        calendar::CalendarFeed,
+        Webhook,
+        CreateWebhook,
+        UpdateWebhook,
+        WebhookDelivery,
+        DeliveryState,
+        ChangeKind,
+    )),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    tags(
+        (name = "users", description = "Sign up and log in"),
+        (name = "todos", description = "Todos, their history and the trash"),
+        (name = "labels", description = "Labels shared by all users"),
+        (name = "projects", description = "Projects and the todos in them"),
+        (name = "calendar", description = "The todos as an iCalendar feed"),
+        (name = "webhooks", description = "Todo changes posted to other services"),
+        (name = "docs", description = "This document"),
+    ),
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Token from `POST /login`"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "docs",
+    responses(
+        (status = 200, description = "This document", body = Object),
+    ),
+    security(()),
+)]
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+#[utoipa::path(
+    get,
+    path = "/docs",
+    tag = "docs",
+    responses(
+        (status = 200, description = "Redoc page rendering this document", body = String, content_type = "text/html"),
+    ),
+    security(()),
+)]
+pub async fn docs() -> Html<&'static str> {
+    Html(DOCS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+
+    const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    // The `(path, method)` pairs registered in `create_app`, read from its source
+    // so a new route cannot be missed.
+    fn routes() -> Vec<(String, String)> {
+        let source = include_str!("lib.rs");
+        let start = source.find("fn create_app").unwrap();
+        let end = start + source[start..].find("\n}\n").unwrap();
+        let mut routes = vec![];
+        for route in source[start..end].split(".route(").skip(1) {
+            let path = route.split('"').nth(1).unwrap();
+            // `/todos/:id` is `/todos/{id}` in OpenAPI.
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(name) => format!("{{{}}}", name),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for (index, _) in route.match_indices('(') {
+                let before = &route[..index];
+                let method = METHODS.iter().find(|method| {
+                    before.ends_with(*method)
+                        && !before[..before.len() - method.len()]
+                            .ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                });
+                if let Some(method) = method {
+                    routes.push((path.clone(), method.to_string()));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn should_document_every_route() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let routes = routes();
+        assert!(routes.contains(&("/todos/{id}".to_string(), "patch".to_string())));
+
+        let undocumented: Vec<_> = routes
+            .iter()
+            .filter(|(path, method)| spec["paths"][path][method].is_null())
+            .collect();
+        assert!(
+            undocumented.is_empty(),
+            "routes missing from ApiDoc: {:?}",
+            undocumented
+        );
+    }
+
+    #[test]
+    fn should_document_validation_constraints() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &spec["components"]["schemas"];
+        for name in ["CreateTodo", "UpdateTodo"] {
+            let text = &schemas[name]["properties"]["text"];
+            assert_eq!(text["minLength"], Value::from(1), "{}", name);
+            assert_eq!(text["maxLength"], Value::from(100), "{}", name);
+        }
+        assert_eq!(
+            schemas["CreateTodo"]["required"],
+            serde_json::json!(["text"])
+        );
+        let batch = &schemas["TodoBatch"]["properties"]["operations"];
+        assert_eq!(batch["maxItems"], Value::from(100));
+
+        let not_found = &spec["paths"]["/todos/{id}"]["get"]["responses"]["404"];
+        assert_eq!(
+            not_found["content"]["application/problem+json"]["schema"]["$ref"],
+            "#/components/schemas/Problem"
+        );
+    }
+}
@@ -0,0 +1,500 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    database::{HasArguments, HasValueRef},
+    encode::IsNull,
+    error::BoxDynError,
+    Database, Decode, Encode, Type,
+};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+// Months without the day of the due date are skipped rather than clamped, so a
+// rule anchored on Feb 29 may have to look several years ahead.
+const MAX_MONTH_STEPS: u32 = 48;
+// Larger intervals would step past the dates chrono can represent.
+const MAX_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RecurrenceError {
+    #[error("FREQ is required")]
+    MissingFrequency,
+    #[error("Unsupported FREQ: [{0}]")]
+    UnsupportedFrequency(String),
+    #[error("Unsupported rule part: [{0}]")]
+    UnsupportedPart(String),
+    #[error("Invalid value for {0}: [{1}]")]
+    InvalidValue(&'static str, String),
+    #[error("COUNT and UNTIL cannot be combined")]
+    CountWithUntil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// The subset of an RFC 5545 RRULE we support: `FREQ` (DAILY, WEEKLY or MONTHLY)
+/// with optional `INTERVAL`, `BYDAY`, `COUNT` and `UNTIL`, e.g.
+/// `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10`.
+///
+/// `COUNT` is the number of occurrences left, including the current one, and
+/// `INTERVAL` goes up to 1000.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Recurrence {
+    freq: Frequency,
+    interval: u32,
+    by_day: Vec<Weekday>,
+    count: Option<u32>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl Recurrence {
+    /// The occurrence following the one due at `due`, together with the rule the
+    /// next todo carries on with. `None` once `COUNT` or `UNTIL` is exhausted.
+    pub fn next_occurrence(&self, due: DateTime<Utc>) -> Option<(DateTime<Utc>, Recurrence)> {
+        if self.count.is_some_and(|count| count <= 1) {
+            return None;
+        }
+        let next = match self.freq {
+            Frequency::Daily => self.next_daily(due),
+            Frequency::Weekly => self.next_weekly(due),
+            Frequency::Monthly => self.next_monthly(due),
+        }?;
+        if self.until.is_some_and(|until| next > until) {
+            return None;
+        }
+        let rule = Recurrence {
+            count: self.count.map(|count| count - 1),
+            ..self.clone()
+        };
+        Some((next, rule))
+    }
+
+    fn matches_day(&self, date: DateTime<Utc>) -> bool {
+        self.by_day.is_empty() || self.by_day.contains(&date.weekday())
+    }
+
+    // Steps that would leave the dates chrono can represent end the rule.
+    fn next_daily(&self, due: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // Every weekday the interval can reach comes up within seven steps.
+        (1..=7)
+            .map_while(|n| {
+                let step = Duration::try_days(i64::from(self.interval) * n)?;
+                due.checked_add_signed(step)
+            })
+            .find(|date| self.matches_day(*date))
+    }
+
+    fn next_weekly(&self, due: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let step = Duration::try_weeks(i64::from(self.interval))?;
+        if self.by_day.is_empty() {
+            return due.checked_add_signed(step);
+        }
+        let offset = due.weekday().num_days_from_monday() as i64;
+        let week_start = due.checked_sub_signed(Duration::days(offset))?;
+        let next_week = week_start.checked_add_signed(step)?;
+        (offset + 1..7)
+            .map_while(|day| week_start.checked_add_signed(Duration::days(day)))
+            .chain((0..7).map_while(|day| next_week.checked_add_signed(Duration::days(day))))
+            .find(|date| self.matches_day(*date))
+    }
+
+    fn next_monthly(&self, due: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let time = due.time();
+        if self.by_day.is_empty() {
+            return (1..=MAX_MONTH_STEPS)
+                .map_while(|n| n.checked_mul(self.interval))
+                .filter_map(|months| add_months(due.date_naive(), months, due.day()))
+                .map(|date| at(date, time))
+                .next();
+        }
+        let later_this_month = due
+            .date_naive()
+            .iter_days()
+            .skip(1)
+            .take_while(|date| date.month() == due.month())
+            .map(|date| at(date, time))
+            .find(|date| self.matches_day(*date));
+        later_this_month.or_else(|| {
+            add_months(due.date_naive(), self.interval, 1)?
+                .iter_days()
+                .map(|date| at(date, time))
+                .find(|date| self.matches_day(*date))
+        })
+    }
+}
+
+fn add_months(date: NaiveDate, months: u32, day: u32) -> Option<NaiveDate> {
+    let index = (date.year() * 12 + date.month0() as i32).checked_add(months.try_into().ok()?)?;
+    NaiveDate::from_ymd_opt(index.div_euclid(12), index.rem_euclid(12) as u32 + 1, day)
+}
+
+fn at(date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_time(time))
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday(code: &str) -> Result<Weekday, RecurrenceError> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(RecurrenceError::InvalidValue("BYDAY", code.to_string())),
+    }
+}
+
+// UNTIL is either a UTC date-time (`20261231T235959Z`) or a date, which covers
+// the whole day.
+fn parse_until(value: &str) -> Result<DateTime<Utc>, RecurrenceError> {
+    let invalid = || RecurrenceError::InvalidValue("UNTIL", value.to_string());
+    if let Some(value) = value.strip_suffix('Z') {
+        return chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .map(|until| Utc.from_utc_datetime(&until))
+            .map_err(|_| invalid());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+    let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).ok_or_else(invalid)?;
+    Ok(at(date, end_of_day))
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+        let mut freq = None;
+        let mut interval = 1;
+        let mut by_day = vec![];
+        let mut count = None;
+        let mut until = None;
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| RecurrenceError::UnsupportedPart(part.to_string()))?;
+            let value = value.to_uppercase();
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(RecurrenceError::UnsupportedFrequency(value)),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=MAX_INTERVAL).contains(interval))
+                        .ok_or(RecurrenceError::InvalidValue("INTERVAL", value))?
+                }
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<_, _>>()?
+                }
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|count| *count >= 1)
+                            .ok_or(RecurrenceError::InvalidValue("COUNT", value))?,
+                    )
+                }
+                "UNTIL" => until = Some(parse_until(&value)?),
+                _ => return Err(RecurrenceError::UnsupportedPart(part.to_string())),
+            }
+        }
+        if count.is_some() && until.is_some() {
+            return Err(RecurrenceError::CountWithUntil);
+        }
+
+        Ok(Recurrence {
+            freq: freq.ok_or(RecurrenceError::MissingFrequency)?,
+            interval,
+            by_day,
+            count,
+            until,
+        })
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let freq = match self.freq {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", freq)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(|day| weekday_code(*day)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for Recurrence {
+    type Error = RecurrenceError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Recurrence> for String {
+    fn from(value: Recurrence) -> Self {
+        value.to_string()
+    }
+}
+
+// Stored as its RRULE text in both Postgres and SQLite.
+impl<DB: Database> Type<DB> for Recurrence
+where
+    String: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <String as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <String as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'q, DB: Database> Encode<'q, DB> for Recurrence
+where
+    String: Encode<'q, DB>,
+{
+    fn encode_by_ref(&self, buf: &mut <DB as HasArguments<'q>>::ArgumentBuffer) -> IsNull {
+        self.to_string().encode(buf)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for Recurrence
+where
+    String: Decode<'r, DB>,
+{
+    fn decode(value: <DB as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
+        Ok(String::decode(value)?.parse()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn rule(s: &str) -> Recurrence {
+        s.parse().expect("failed parse rule")
+    }
+
+    fn occurrences(rule: &Recurrence, due: DateTime<Utc>, n: usize) -> Vec<DateTime<Utc>> {
+        let mut dates = vec![];
+        let mut current = (due, rule.clone());
+        while dates.len() < n {
+            match current.1.next_occurrence(current.0) {
+                Some(next) => {
+                    dates.push(next.0);
+                    current = next;
+                }
+                None => break,
+            }
+        }
+        dates
+    }
+
+    #[test]
+    fn should_round_trip_rules() {
+        for s in [
+            "FREQ=DAILY",
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH",
+            "FREQ=MONTHLY;COUNT=3",
+            "FREQ=DAILY;UNTIL=20261231T090000Z",
+        ] {
+            assert_eq!(rule(s).to_string(), s);
+        }
+        assert_eq!(
+            rule("RRULE:freq=weekly;byday=fr;interval=1").to_string(),
+            "FREQ=WEEKLY;BYDAY=FR"
+        );
+    }
+
+    #[test]
+    fn should_reject_unsupported_rules() {
+        assert_eq!(
+            "BYDAY=MO".parse::<Recurrence>(),
+            Err(RecurrenceError::MissingFrequency)
+        );
+        assert_eq!(
+            "FREQ=YEARLY".parse::<Recurrence>(),
+            Err(RecurrenceError::UnsupportedFrequency("YEARLY".to_string()))
+        );
+        assert!(matches!(
+            "FREQ=DAILY;BYHOUR=9".parse::<Recurrence>(),
+            Err(RecurrenceError::UnsupportedPart(_))
+        ));
+        assert!(matches!(
+            "FREQ=DAILY;INTERVAL=0".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidValue("INTERVAL", _))
+        ));
+        assert!(matches!(
+            "FREQ=WEEKLY;BYDAY=XX".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidValue("BYDAY", _))
+        ));
+        assert_eq!(
+            "FREQ=DAILY;COUNT=2;UNTIL=20261231".parse::<Recurrence>(),
+            Err(RecurrenceError::CountWithUntil)
+        );
+    }
+
+    #[test]
+    fn should_step_daily_with_interval() {
+        let due = date("2026-10-15T09:00:00Z");
+        assert_eq!(
+            occurrences(&rule("FREQ=DAILY;INTERVAL=3"), due, 2),
+            vec![date("2026-10-18T09:00:00Z"), date("2026-10-21T09:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn should_refuse_huge_intervals() {
+        assert!(matches!(
+            "FREQ=DAILY;INTERVAL=100000000".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidValue("INTERVAL", _))
+        ));
+        assert!(matches!(
+            "FREQ=MONTHLY;INTERVAL=4294967295".parse::<Recurrence>(),
+            Err(RecurrenceError::InvalidValue("INTERVAL", _))
+        ));
+        assert_eq!(rule("FREQ=DAILY;INTERVAL=1000").interval, MAX_INTERVAL);
+    }
+
+    #[test]
+    fn should_end_rules_that_step_out_of_range() {
+        let due = DateTime::<Utc>::MAX_UTC - Duration::days(10);
+        assert!(occurrences(&rule("FREQ=DAILY;INTERVAL=1000"), due, 1).is_empty());
+        assert!(occurrences(&rule("FREQ=WEEKLY;INTERVAL=1000"), due, 1).is_empty());
+        let last_days = DateTime::<Utc>::MAX_UTC - Duration::days(2);
+        assert!(occurrences(&rule("FREQ=WEEKLY;INTERVAL=1000;BYDAY=MO"), last_days, 1).is_empty());
+        assert!(occurrences(&rule("FREQ=MONTHLY;INTERVAL=1000"), due, 1).is_empty());
+
+        // The parser caps the interval, the stepping does not rely on it.
+        let huge = Recurrence {
+            interval: u32::MAX,
+            ..rule("FREQ=MONTHLY")
+        };
+        let due = date("2026-10-15T09:00:00Z");
+        assert!(occurrences(&huge, due, 1).is_empty());
+        for freq in [Frequency::Daily, Frequency::Weekly] {
+            let huge = Recurrence {
+                freq,
+                ..huge.clone()
+            };
+            assert!(occurrences(&huge, due, 1).is_empty());
+        }
+    }
+
+    #[test]
+    fn should_skip_days_not_in_byday() {
+        // 2026-10-16 is a Friday.
+        let due = date("2026-10-16T18:30:00Z");
+        assert_eq!(
+            occurrences(&rule("FREQ=DAILY;BYDAY=MO,TU,WE,TH,FR"), due, 2),
+            vec![date("2026-10-19T18:30:00Z"), date("2026-10-20T18:30:00Z")]
+        );
+    }
+
+    #[test]
+    fn should_step_weekly_through_byday() {
+        // 2026-10-12 is a Monday.
+        let due = date("2026-10-12T08:00:00Z");
+        assert_eq!(
+            occurrences(&rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH"), due, 3),
+            vec![
+                date("2026-10-15T08:00:00Z"),
+                date("2026-10-26T08:00:00Z"),
+                date("2026-10-29T08:00:00Z"),
+            ]
+        );
+        assert_eq!(
+            occurrences(&rule("FREQ=WEEKLY"), due, 1),
+            vec![date("2026-10-19T08:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn should_skip_months_without_the_day() {
+        let due = date("2026-01-31T12:00:00Z");
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY"), due, 2),
+            vec![date("2026-03-31T12:00:00Z"), date("2026-05-31T12:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn should_step_monthly_through_byday() {
+        // 2026-10-30 is a Friday, the last one of the month.
+        let due = date("2026-10-30T10:00:00Z");
+        assert_eq!(
+            occurrences(&rule("FREQ=MONTHLY;BYDAY=FR"), due, 2),
+            vec![date("2026-11-06T10:00:00Z"), date("2026-11-13T10:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn should_stop_after_count() {
+        let due = date("2026-10-15T09:00:00Z");
+        let rule = rule("FREQ=DAILY;COUNT=3");
+        assert_eq!(
+            occurrences(&rule, due, 10),
+            vec![date("2026-10-16T09:00:00Z"), date("2026-10-17T09:00:00Z")]
+        );
+        let (_, next) = rule.next_occurrence(due).unwrap();
+        assert_eq!(next.to_string(), "FREQ=DAILY;COUNT=2");
+    }
+
+    #[test]
+    fn should_stop_after_until() {
+        let due = date("2026-10-15T09:00:00Z");
+        assert_eq!(
+            occurrences(&rule("FREQ=WEEKLY;UNTIL=20261029"), due, 10),
+            vec![date("2026-10-22T09:00:00Z"), date("2026-10-29T09:00:00Z")]
+        );
+    }
+}
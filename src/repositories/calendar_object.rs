@@ -0,0 +1,471 @@
+use super::{
+    todo::{CreateTodo, Todo, TodoRepository, TodoRepositoryForDB, TodoRepositoryForSqlite},
+    RepositoryError,
+};
+use axum::async_trait;
+use sqlx::FromRow;
+
+#[async_trait]
+pub trait CalendarObjectRepository:
+    Clone + std::marker::Send + std::marker::Sync + 'static
+{
+    /// Every todo of the owner that has a resource name of its own.
+    async fn all(&self, owner_id: i32) -> Result<Vec<CalendarObject>, RepositoryError>;
+    /// The todo a client gave the name `name`, if any.
+    async fn find(
+        &self,
+        owner_id: i32,
+        name: &str,
+    ) -> Result<Option<CalendarObject>, RepositoryError>;
+    /// The name and UID a client gave the todo `todo_id`, if any.
+    async fn find_by_todo(
+        &self,
+        owner_id: i32,
+        todo_id: i32,
+    ) -> Result<Option<CalendarObject>, RepositoryError>;
+    /// Creates a todo a CalDAV client sent, together with the name and UID it
+    /// gave the todo, all or nothing. Replaces whatever todo had the name before.
+    async fn create(
+        &self,
+        owner_id: i32,
+        name: String,
+        uid: String,
+        payload: CreateTodo,
+    ) -> Result<Todo, RepositoryError>;
+}
+
+/// A todo as a CalDAV client knows it, e.g. `{"name": "0B1F.ics", "uid": "0B1F"}`.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct CalendarObject {
+    pub todo_id: i32,
+    pub name: String,
+    pub uid: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarObjectRepositoryForDB {
+    todos: TodoRepositoryForDB,
+}
+
+impl CalendarObjectRepositoryForDB {
+    /// Shares the database and the change feed of `todos`.
+    pub fn new(todos: TodoRepositoryForDB) -> Self {
+        CalendarObjectRepositoryForDB { todos }
+    }
+}
+
+const SAVE_QUERY: &str = r#"
+    insert into calendar_objects (owner_id, name, todo_id, uid)
+    values ($1, $2, $3, $4)
+    on conflict (owner_id, name) do update set
+        todo_id = excluded.todo_id,
+        uid = excluded.uid
+"#;
+
+#[async_trait]
+impl CalendarObjectRepository for CalendarObjectRepositoryForDB {
+    async fn all(&self, owner_id: i32) -> Result<Vec<CalendarObject>, RepositoryError> {
+        let objects = sqlx::query_as::<_, CalendarObject>(
+            r#"
+                select todo_id, name, uid from calendar_objects
+                where owner_id = $1
+                order by todo_id asc
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(self.todos.pool())
+        .await?;
+
+        Ok(objects)
+    }
+    async fn find(
+        &self,
+        owner_id: i32,
+        name: &str,
+    ) -> Result<Option<CalendarObject>, RepositoryError> {
+        let object = sqlx::query_as::<_, CalendarObject>(
+            r#"
+                select todo_id, name, uid from calendar_objects
+                where owner_id = $1 and name = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(name)
+        .fetch_optional(self.todos.pool())
+        .await?;
+
+        Ok(object)
+    }
+    async fn find_by_todo(
+        &self,
+        owner_id: i32,
+        todo_id: i32,
+    ) -> Result<Option<CalendarObject>, RepositoryError> {
+        let object = sqlx::query_as::<_, CalendarObject>(
+            r#"
+                select todo_id, name, uid from calendar_objects
+                where owner_id = $1 and todo_id = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(todo_id)
+        .fetch_optional(self.todos.pool())
+        .await?;
+
+        Ok(object)
+    }
+    async fn create(
+        &self,
+        owner_id: i32,
+        name: String,
+        uid: String,
+        payload: CreateTodo,
+    ) -> Result<Todo, RepositoryError> {
+        let mut changes = vec![];
+        let mut tx = self.todos.pool().begin().await?;
+        let todo = TodoRepositoryForDB::create_in(&mut tx, &mut changes, owner_id, payload).await?;
+        sqlx::query(SAVE_QUERY)
+            .bind(owner_id)
+            .bind(name)
+            .bind(todo.id())
+            .bind(uid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        self.todos.changes().publish(changes);
+
+        Ok(todo)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarObjectRepositoryForSqlite {
+    todos: TodoRepositoryForSqlite,
+}
+
+impl CalendarObjectRepositoryForSqlite {
+    /// Shares the database and the change feed of `todos`.
+    pub fn new(todos: TodoRepositoryForSqlite) -> Self {
+        CalendarObjectRepositoryForSqlite { todos }
+    }
+}
+
+#[async_trait]
+impl CalendarObjectRepository for CalendarObjectRepositoryForSqlite {
+    async fn all(&self, owner_id: i32) -> Result<Vec<CalendarObject>, RepositoryError> {
+        let objects = sqlx::query_as::<_, CalendarObject>(
+            r#"
+                select todo_id, name, uid from calendar_objects
+                where owner_id = $1
+                order by todo_id asc
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(self.todos.pool())
+        .await?;
+
+        Ok(objects)
+    }
+    async fn find(
+        &self,
+        owner_id: i32,
+        name: &str,
+    ) -> Result<Option<CalendarObject>, RepositoryError> {
+        let object = sqlx::query_as::<_, CalendarObject>(
+            r#"
+                select todo_id, name, uid from calendar_objects
+                where owner_id = $1 and name = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(name)
+        .fetch_optional(self.todos.pool())
+        .await?;
+
+        Ok(object)
+    }
+    async fn find_by_todo(
+        &self,
+        owner_id: i32,
+        todo_id: i32,
+    ) -> Result<Option<CalendarObject>, RepositoryError> {
+        let object = sqlx::query_as::<_, CalendarObject>(
+            r#"
+                select todo_id, name, uid from calendar_objects
+                where owner_id = $1 and todo_id = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(todo_id)
+        .fetch_optional(self.todos.pool())
+        .await?;
+
+        Ok(object)
+    }
+    async fn create(
+        &self,
+        owner_id: i32,
+        name: String,
+        uid: String,
+        payload: CreateTodo,
+    ) -> Result<Todo, RepositoryError> {
+        let mut changes = vec![];
+        let mut tx = self.todos.pool().begin().await?;
+        let todo =
+            TodoRepositoryForSqlite::create_in(&mut tx, &mut changes, owner_id, payload).await?;
+        sqlx::query(SAVE_QUERY)
+            .bind(owner_id)
+            .bind(name)
+            .bind(todo.id())
+            .bind(uid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        self.todos.changes().publish(changes);
+
+        Ok(todo)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        test_utils::{connect_postgres, connect_sqlite, find_or_create_user},
+        todo::test_utils::TodoRepositoryForMemory,
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+    };
+    use chrono::Utc;
+
+    async fn calendar_object_scenario<T: TodoRepository, C: CalendarObjectRepository>(
+        todos: T,
+        repository: C,
+        owner_id: i32,
+    ) {
+        let stamp = Utc::now().timestamp_nanos_opt().unwrap();
+        let name = format!("{}.ics", stamp);
+        let todo = repository
+            .create(
+                owner_id,
+                name.clone(),
+                stamp.to_string(),
+                CreateTodo::new("first".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+        let found = todos
+            .find(owner_id, todo.id())
+            .await
+            .expect("[todo find] returned error");
+        assert_eq!(found.text(), "first");
+        let object = CalendarObject {
+            todo_id: todo.id(),
+            name: name.clone(),
+            uid: stamp.to_string(),
+        };
+        let all = repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error");
+        assert!(all.contains(&object));
+        let found = repository
+            .find(owner_id, &name)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(found, Some(object.clone()));
+        let found = repository
+            .find_by_todo(owner_id, object.todo_id)
+            .await
+            .expect("[find_by_todo] returned error");
+        assert_eq!(found, Some(object.clone()));
+        let found = repository
+            .find(owner_id + 1, &name)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(found, None, "names are kept per owner");
+
+        let todo = repository
+            .create(
+                owner_id,
+                name.clone(),
+                format!("{}-moved", stamp),
+                CreateTodo::new("second".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+        let moved = CalendarObject {
+            todo_id: todo.id(),
+            uid: format!("{}-moved", stamp),
+            ..object.clone()
+        };
+        let all = repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error");
+        assert!(
+            all.contains(&moved),
+            "creating under a taken name replaces its todo"
+        );
+        assert!(!all.contains(&object));
+        let found = repository
+            .find_by_todo(owner_id, object.todo_id)
+            .await
+            .expect("[find_by_todo] returned error");
+        assert_eq!(found, None);
+
+        let missing = CreateTodo::new("third".to_string(), vec![]).with_parent(i32::MAX);
+        let res = repository
+            .create(
+                owner_id,
+                format!("{}-3.ics", stamp),
+                stamp.to_string(),
+                missing,
+            )
+            .await;
+        assert!(matches!(res, Err(RepositoryError::Validation(_))));
+        let all = repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error");
+        assert!(
+            all.iter()
+                .all(|object| object.name != format!("{}-3.ics", stamp)),
+            "no name without its todo"
+        );
+    }
+
+    #[tokio::test]
+    async fn calendar_object_scenario_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "caldav@example.com").await;
+        let todos = TodoRepositoryForDB::new(pool);
+        calendar_object_scenario(
+            todos.clone(),
+            CalendarObjectRepositoryForDB::new(todos),
+            owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn calendar_object_scenario_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "caldav@example.com").await;
+        let todos = TodoRepositoryForSqlite::new(pool);
+        calendar_object_scenario(
+            todos.clone(),
+            CalendarObjectRepositoryForSqlite::new(todos),
+            owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn calendar_object_scenario_for_memory() {
+        let todos = TodoRepositoryForMemory::new(vec![]);
+        calendar_object_scenario(
+            todos.clone(),
+            test_utils::CalendarObjectRepositoryForMemory::with_todos(todos),
+            1,
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::todo::test_utils::TodoRepositoryForMemory;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    type CalendarObjectDatas = HashMap<(i32, String), CalendarObject>;
+
+    #[derive(Debug, Clone)]
+    pub struct CalendarObjectRepositoryForMemory {
+        store: Arc<RwLock<CalendarObjectDatas>>,
+        todos: TodoRepositoryForMemory,
+    }
+
+    impl Default for CalendarObjectRepositoryForMemory {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl CalendarObjectRepositoryForMemory {
+        /// Creates todos in a store of its own, for apps that do not serve CalDAV.
+        pub fn new() -> Self {
+            Self::with_todos(TodoRepositoryForMemory::new(vec![]))
+        }
+
+        pub fn with_todos(todos: TodoRepositoryForMemory) -> Self {
+            CalendarObjectRepositoryForMemory {
+                store: Arc::default(),
+                todos,
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, CalendarObjectDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, CalendarObjectDatas> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl CalendarObjectRepository for CalendarObjectRepositoryForMemory {
+        async fn all(&self, owner_id: i32) -> Result<Vec<CalendarObject>, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut objects: Vec<CalendarObject> = store
+                .iter()
+                .filter(|((owner, _), _)| *owner == owner_id)
+                .map(|(_, object)| object.clone())
+                .collect();
+            objects.sort_by_key(|object| object.todo_id);
+            Ok(objects)
+        }
+        async fn find(
+            &self,
+            owner_id: i32,
+            name: &str,
+        ) -> Result<Option<CalendarObject>, RepositoryError> {
+            let store = self.read_store_ref();
+            Ok(store.get(&(owner_id, name.to_string())).cloned())
+        }
+        async fn find_by_todo(
+            &self,
+            owner_id: i32,
+            todo_id: i32,
+        ) -> Result<Option<CalendarObject>, RepositoryError> {
+            let store = self.read_store_ref();
+            let object = store
+                .iter()
+                .find(|((owner, _), object)| *owner == owner_id && object.todo_id == todo_id)
+                .map(|(_, object)| object.clone());
+            Ok(object)
+        }
+        async fn create(
+            &self,
+            owner_id: i32,
+            name: String,
+            uid: String,
+            payload: CreateTodo,
+        ) -> Result<Todo, RepositoryError> {
+            let todo = self.todos.create(owner_id, payload).await?;
+            let object = CalendarObject {
+                todo_id: todo.id(),
+                name: name.clone(),
+                uid,
+            };
+            self.write_store_ref().insert((owner_id, name), object);
+            Ok(todo)
+        }
+    }
+}
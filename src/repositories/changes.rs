@@ -0,0 +1,221 @@
+use super::todo::Todo;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// How many changes are kept for subscribers that resume, and how far a
+/// subscriber may fall behind before it misses some.
+const CAPACITY: usize = 1000;
+
+tokio::task_local! {
+    static ORIGIN: u64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// A todo that appeared, changed or went away, as seen by its owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TodoChange {
+    /// Position in the feed, handed out by `TodoChanges::publish`.
+    pub id: u64,
+    pub owner_id: i32,
+    pub kind: ChangeKind,
+    pub todo: Todo,
+    /// Who made the change, when it was made within `TodoChanges::scope`.
+    pub origin: Option<u64>,
+}
+
+impl TodoChange {
+    /// The change between two snapshots of a todo, `None` standing for a todo
+    /// that does not exist. Todos in the trash count as gone, so restoring one
+    /// creates it again and changes within the trash are not visible at all.
+    pub fn new(owner_id: i32, before: Option<&Todo>, after: Option<&Todo>) -> Option<Self> {
+        let visible = |todo: &&Todo| todo.deleted_at().is_none();
+        let (kind, todo) = match (before.filter(visible), after.filter(visible)) {
+            (None, Some(after)) => (ChangeKind::Created, after),
+            (Some(_), Some(after)) => (ChangeKind::Updated, after),
+            // The trashed version still carries the latest fields.
+            (Some(before), None) => (ChangeKind::Deleted, after.unwrap_or(before)),
+            (None, None) => return None,
+        };
+        Some(Self {
+            id: 0,
+            owner_id,
+            kind,
+            todo: todo.clone(),
+            origin: None,
+        })
+    }
+}
+
+/// Feed of the changes made through a todo repository. The repository
+/// publishes them once they are committed.
+#[derive(Debug, Clone)]
+pub struct TodoChanges {
+    log: Arc<Mutex<ChangeLog>>,
+    sender: broadcast::Sender<TodoChange>,
+}
+
+#[derive(Debug, Default)]
+struct ChangeLog {
+    last_id: u64,
+    recent: VecDeque<TodoChange>,
+}
+
+pub struct Subscription {
+    /// The changes after the `Last-Event-ID` asked for, `None` when some of them
+    /// are no longer kept.
+    pub replay: Option<Vec<TodoChange>>,
+    /// Every change published from now on.
+    pub receiver: broadcast::Receiver<TodoChange>,
+}
+
+impl Default for TodoChanges {
+    fn default() -> Self {
+        Self {
+            log: Arc::default(),
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl TodoChanges {
+    /// Runs `future`, tagging the changes published while it runs with `origin`
+    /// so whoever made them can tell them apart from the rest of the feed.
+    pub async fn scope<F: Future>(origin: u64, future: F) -> F::Output {
+        ORIGIN.scope(origin, future).await
+    }
+
+    pub fn publish(&self, changes: Vec<TodoChange>) {
+        let origin = ORIGIN.try_with(|origin| *origin).ok();
+        let mut log = self.log.lock().unwrap();
+        for mut change in changes {
+            log.last_id += 1;
+            change.id = log.last_id;
+            change.origin = origin;
+            if log.recent.len() == CAPACITY {
+                log.recent.pop_front();
+            }
+            log.recent.push_back(change.clone());
+            // Only fails while nobody is subscribed.
+            let _ = self.sender.send(change);
+        }
+    }
+
+    /// Subscribes to the feed. With `last_event_id`, the changes published after
+    /// it are replayed first. The log is locked meanwhile, so each change is
+    /// either replayed or received, never both.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription {
+        let log = self.log.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let replay = match last_event_id {
+            None => Some(vec![]),
+            Some(last_event_id) => {
+                let oldest = log
+                    .recent
+                    .front()
+                    .map_or(log.last_id + 1, |change| change.id);
+                // An id from before a restart can be ahead of the feed.
+                (last_event_id <= log.last_id && last_event_id + 1 >= oldest).then(|| {
+                    log.recent
+                        .iter()
+                        .filter(|change| change.id > last_event_id)
+                        .cloned()
+                        .collect()
+                })
+            }
+        };
+
+        Subscription { replay, receiver }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn created(owner_id: i32, id: i32) -> TodoChange {
+        let todo = Todo::new(id, format!("todo {}", id), vec![]);
+        TodoChange::new(owner_id, None, Some(&todo)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_replay_and_broadcast_changes() {
+        let changes = TodoChanges::default();
+        changes.publish(vec![created(1, 1), created(1, 2)]);
+
+        let mut subscription = changes.subscribe(Some(1));
+        let replay = subscription.replay.unwrap();
+        assert_eq!(replay.len(), 1);
+        assert_eq!((replay[0].id, replay[0].todo.id()), (2, 2));
+
+        changes.publish(vec![created(2, 3)]);
+        let received = subscription.receiver.recv().await.unwrap();
+        assert_eq!((received.id, received.owner_id), (3, 2));
+
+        assert_eq!(changes.subscribe(Some(3)).replay, Some(vec![]));
+        assert_eq!(changes.subscribe(None).replay, Some(vec![]));
+        // Ahead of the feed, as after a restart.
+        assert_eq!(changes.subscribe(Some(4)).replay, None);
+    }
+
+    #[tokio::test]
+    async fn should_tag_changes_with_origin() {
+        let changes = TodoChanges::default();
+        let mut receiver = changes.subscribe(None).receiver;
+
+        TodoChanges::scope(7, async { changes.publish(vec![created(1, 1)]) }).await;
+        changes.publish(vec![created(1, 2)]);
+
+        assert_eq!(receiver.recv().await.unwrap().origin, Some(7));
+        assert_eq!(receiver.recv().await.unwrap().origin, None);
+    }
+
+    #[test]
+    fn should_not_resume_from_dropped_changes() {
+        let changes = TodoChanges::default();
+        changes.publish((1..=CAPACITY as i32 + 2).map(|id| created(1, id)).collect());
+
+        assert_eq!(changes.subscribe(Some(1)).replay, None);
+        let replay = changes.subscribe(Some(2)).replay.unwrap();
+        assert_eq!(replay.len(), CAPACITY);
+        assert_eq!(replay[0].id, 3);
+    }
+
+    #[test]
+    fn should_tell_kind_of_change() {
+        let todo = Todo::new(1, "todo".to_string(), vec![]);
+        let trashed = todo.clone().trashed();
+        let kind = |before: Option<&Todo>, after: Option<&Todo>| {
+            TodoChange::new(1, before, after).map(|change| change.kind)
+        };
+
+        assert_eq!(kind(None, Some(&todo)), Some(ChangeKind::Created));
+        assert_eq!(kind(Some(&todo), Some(&todo)), Some(ChangeKind::Updated));
+        assert_eq!(kind(Some(&todo), Some(&trashed)), Some(ChangeKind::Deleted));
+        assert_eq!(kind(Some(&trashed), Some(&todo)), Some(ChangeKind::Created));
+        assert_eq!(kind(Some(&trashed), None), None);
+    }
+}
@@ -0,0 +1,431 @@
+use super::RepositoryError;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{types::Json, FromRow, PgPool, SqlitePool};
+
+#[async_trait]
+pub trait IdempotencyRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// Claims `key` for a request with `request_hash` until `expires_at`. Returns
+    /// the request that holds the key if it is taken and has not expired yet, and
+    /// `None` once the key is claimed.
+    async fn reserve(
+        &self,
+        owner_id: i32,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKey>, RepositoryError>;
+    /// Stores the response to replay for a claimed key.
+    async fn complete(
+        &self,
+        owner_id: i32,
+        key: &str,
+        status: u16,
+        body: Value,
+    ) -> Result<(), RepositoryError>;
+    /// Gives up a claim, so the request can be retried with the same key.
+    async fn release(&self, owner_id: i32, key: &str) -> Result<(), RepositoryError>;
+    /// Deletes, for all owners, every key that expired before `now`.
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, RepositoryError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyRepositoryForDB {
+    pool: PgPool,
+}
+
+impl IdempotencyRepositoryForDB {
+    pub fn new(pool: PgPool) -> Self {
+        IdempotencyRepositoryForDB { pool }
+    }
+}
+
+// Inserts the key, or takes over an expired one. Returns nothing when the key is
+// held by a request that has not expired yet.
+const RESERVE_QUERY: &str = r#"
+    insert into idempotency_keys (owner_id, key, request_hash, created_at, expires_at)
+    values ($1, $2, $3, $4, $5)
+    on conflict (owner_id, key) do update set
+        request_hash = excluded.request_hash,
+        status = null,
+        body = null,
+        created_at = excluded.created_at,
+        expires_at = excluded.expires_at
+    where idempotency_keys.expires_at <= excluded.created_at
+    returning key
+"#;
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryForDB {
+    async fn reserve(
+        &self,
+        owner_id: i32,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKey>, RepositoryError> {
+        let claimed: Option<String> = sqlx::query_scalar(RESERVE_QUERY)
+            .bind(owner_id)
+            .bind(key)
+            .bind(request_hash)
+            .bind(Utc::now())
+            .bind(expires_at)
+            .fetch_optional(&self.pool)
+            .await?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+        let holder = sqlx::query_as::<_, IdempotencyKey>(
+            r#"
+                select request_hash, status, body from idempotency_keys
+                where owner_id = $1 and key = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RepositoryError::Conflict(format!("key {} was just released", key)))?;
+
+        Ok(Some(holder))
+    }
+    async fn complete(
+        &self,
+        owner_id: i32,
+        key: &str,
+        status: u16,
+        body: Value,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                update idempotency_keys set status = $3, body = $4
+                where owner_id = $1 and key = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(key)
+        .bind(status as i32)
+        .bind(Json(body))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn release(&self, owner_id: i32, key: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                delete from idempotency_keys where owner_id = $1 and key = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(
+            r#"
+                delete from idempotency_keys where expires_at <= $1
+            "#,
+        )
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl IdempotencyRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        IdempotencyRepositoryForSqlite { pool }
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryForSqlite {
+    async fn reserve(
+        &self,
+        owner_id: i32,
+        key: &str,
+        request_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKey>, RepositoryError> {
+        let claimed: Option<String> = sqlx::query_scalar(RESERVE_QUERY)
+            .bind(owner_id)
+            .bind(key)
+            .bind(request_hash)
+            .bind(Utc::now())
+            .bind(expires_at)
+            .fetch_optional(&self.pool)
+            .await?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+        let holder = sqlx::query_as::<_, IdempotencyKey>(
+            r#"
+                select request_hash, status, body from idempotency_keys
+                where owner_id = $1 and key = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| RepositoryError::Conflict(format!("key {} was just released", key)))?;
+
+        Ok(Some(holder))
+    }
+    async fn complete(
+        &self,
+        owner_id: i32,
+        key: &str,
+        status: u16,
+        body: Value,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                update idempotency_keys set status = $3, body = $4
+                where owner_id = $1 and key = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(key)
+        .bind(status as i32)
+        .bind(Json(body))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn release(&self, owner_id: i32, key: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                delete from idempotency_keys where owner_id = $1 and key = $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(
+            r#"
+                delete from idempotency_keys where expires_at <= $1
+            "#,
+        )
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// The request an idempotency key was first used with.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct IdempotencyKey {
+    pub request_hash: String,
+    /// The stored response, `None` while the request is still running.
+    pub status: Option<i32>,
+    pub body: Option<Json<Value>>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        test_utils::{connect_postgres, connect_sqlite, find_or_create_user},
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+    };
+    use chrono::Duration;
+    use serde_json::json;
+
+    async fn idempotency_scenario<T: IdempotencyRepository>(repository: T, owner_id: i32) {
+        let key = format!("scenario-{}", Utc::now().timestamp_nanos_opt().unwrap());
+        let expires_at = Utc::now() + Duration::hours(1);
+        let holder = repository
+            .reserve(owner_id, &key, "first", expires_at)
+            .await
+            .expect("[reserve] returned error");
+        assert_eq!(holder, None);
+
+        let holder = repository
+            .reserve(owner_id, &key, "second", expires_at)
+            .await
+            .expect("[reserve] returned error");
+        assert_eq!(
+            holder,
+            Some(IdempotencyKey {
+                request_hash: "first".to_string(),
+                status: None,
+                body: None,
+            })
+        );
+
+        repository
+            .complete(owner_id, &key, 201, json!({ "id": 1 }))
+            .await
+            .expect("[complete] returned error");
+        let holder = repository
+            .reserve(owner_id, &key, "first", expires_at)
+            .await
+            .expect("[reserve] returned error")
+            .expect("[reserve] completed key was claimed again");
+        assert_eq!(holder.status, Some(201));
+        assert_eq!(holder.body, Some(Json(json!({ "id": 1 }))));
+
+        let released = format!("{}-released", key);
+        repository
+            .reserve(owner_id, &released, "first", expires_at)
+            .await
+            .expect("[reserve] returned error");
+        repository
+            .release(owner_id, &released)
+            .await
+            .expect("[release] returned error");
+        let holder = repository
+            .reserve(owner_id, &released, "second", expires_at)
+            .await
+            .expect("[reserve] returned error");
+        assert_eq!(holder, None, "a released key can be claimed again");
+
+        let expired = format!("{}-expired", key);
+        repository
+            .reserve(owner_id, &expired, "first", Utc::now() - Duration::hours(1))
+            .await
+            .expect("[reserve] returned error");
+        let holder = repository
+            .reserve(owner_id, &expired, "second", expires_at)
+            .await
+            .expect("[reserve] returned error");
+        assert_eq!(holder, None, "an expired key can be claimed again");
+
+        let old = format!("{}-old", key);
+        repository
+            .reserve(owner_id, &old, "first", Utc::now() - Duration::hours(1))
+            .await
+            .expect("[reserve] returned error");
+        let purged = repository
+            .purge_expired(Utc::now())
+            .await
+            .expect("[purge_expired] returned error");
+        assert!(purged >= 1);
+        let holder = repository
+            .reserve(owner_id, &key, "first", expires_at)
+            .await
+            .expect("[reserve] returned error");
+        assert!(holder.is_some(), "keys that did not expire are kept");
+    }
+
+    #[tokio::test]
+    async fn idempotency_scenario_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "idempotency@example.com").await;
+        idempotency_scenario(IdempotencyRepositoryForDB::new(pool), owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn idempotency_scenario_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "idempotency@example.com").await;
+        idempotency_scenario(IdempotencyRepositoryForSqlite::new(pool), owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn idempotency_scenario_for_memory() {
+        idempotency_scenario(test_utils::IdempotencyRepositoryForMemory::new(), 1).await;
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockWriteGuard},
+    };
+
+    type IdempotencyDatas = HashMap<(i32, String), (IdempotencyKey, DateTime<Utc>)>;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct IdempotencyRepositoryForMemory {
+        store: Arc<RwLock<IdempotencyDatas>>,
+    }
+
+    impl IdempotencyRepositoryForMemory {
+        pub fn new() -> Self {
+            IdempotencyRepositoryForMemory {
+                store: Arc::default(),
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, IdempotencyDatas> {
+            self.store.write().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl IdempotencyRepository for IdempotencyRepositoryForMemory {
+        async fn reserve(
+            &self,
+            owner_id: i32,
+            key: &str,
+            request_hash: &str,
+            expires_at: DateTime<Utc>,
+        ) -> Result<Option<IdempotencyKey>, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let id = (owner_id, key.to_string());
+            if let Some((holder, held_until)) = store.get(&id) {
+                if *held_until > Utc::now() {
+                    return Ok(Some(holder.clone()));
+                }
+            }
+            let holder = IdempotencyKey {
+                request_hash: request_hash.to_string(),
+                status: None,
+                body: None,
+            };
+            store.insert(id, (holder, expires_at));
+            Ok(None)
+        }
+        async fn complete(
+            &self,
+            owner_id: i32,
+            key: &str,
+            status: u16,
+            body: Value,
+        ) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            if let Some((holder, _)) = store.get_mut(&(owner_id, key.to_string())) {
+                holder.status = Some(status as i32);
+                holder.body = Some(Json(body));
+            }
+            Ok(())
+        }
+        async fn release(&self, owner_id: i32, key: &str) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            store.remove(&(owner_id, key.to_string()));
+            Ok(())
+        }
+        async fn purge_expired(&self, now: DateTime<Utc>) -> Result<u64, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let before = store.len();
+            store.retain(|_, (_, expires_at)| *expires_at > now);
+            Ok((before - store.len()) as u64)
+        }
+    }
+}
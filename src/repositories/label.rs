@@ -0,0 +1,533 @@
+use super::RepositoryError;
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, SqlitePool};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[async_trait]
+pub trait LabelRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, owner_id: i32, payload: CreateLabel) -> Result<Label, RepositoryError>;
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Label, RepositoryError>;
+    async fn all(&self, owner_id: i32) -> Result<Vec<Label>, RepositoryError>;
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateLabel,
+    ) -> Result<Label, RepositoryError>;
+    async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForDB {
+    pool: PgPool,
+}
+
+impl LabelRepositoryForDB {
+    pub fn new(pool: PgPool) -> Self {
+        LabelRepositoryForDB { pool }
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForDB {
+    async fn create(&self, owner_id: i32, payload: CreateLabel) -> Result<Label, RepositoryError> {
+        let optional_label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where name = $1 and owner_id = $2
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(owner_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(label) = optional_label {
+            return Err(RepositoryError::Conflict(format!(
+                "label name already exists, id is {}",
+                label.id
+            )));
+        }
+
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                insert into labels (owner_id, name)
+                values ($1, $2)
+                returning *
+            "#,
+        )
+        .bind(owner_id)
+        .bind(payload.name.clone())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(label)
+    }
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Label, RepositoryError> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(label)
+    }
+    async fn all(&self, owner_id: i32) -> Result<Vec<Label>, RepositoryError> {
+        let labels = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels
+                where owner_id = $1
+                order by labels.id asc;
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(labels)
+    }
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateLabel,
+    ) -> Result<Label, RepositoryError> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                update labels set name = $1
+                where id = $2 and owner_id = $3
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(label)
+    }
+    async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+        // Only takes the label off todos of the same owner, as it is on no others.
+        let result = sqlx::query(
+            r#"
+                delete from labels where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl LabelRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        LabelRepositoryForSqlite { pool }
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForSqlite {
+    async fn create(&self, owner_id: i32, payload: CreateLabel) -> Result<Label, RepositoryError> {
+        let optional_label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where name = $1 and owner_id = $2
+            "#,
+        )
+        .bind(payload.name.clone())
+        .bind(owner_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(label) = optional_label {
+            return Err(RepositoryError::Conflict(format!(
+                "label name already exists, id is {}",
+                label.id
+            )));
+        }
+
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                insert into labels (owner_id, name)
+                values ($1, $2)
+                returning *
+            "#,
+        )
+        .bind(owner_id)
+        .bind(payload.name.clone())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(label)
+    }
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Label, RepositoryError> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(label)
+    }
+    async fn all(&self, owner_id: i32) -> Result<Vec<Label>, RepositoryError> {
+        let labels = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels
+                where owner_id = $1
+                order by labels.id asc;
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(labels)
+    }
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateLabel,
+    ) -> Result<Label, RepositoryError> {
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                update labels set name = $1
+                where id = $2 and owner_id = $3
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(label)
+    }
+    async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+        // Only takes the label off todos of the same owner, as it is on no others.
+        let result = sqlx::query(
+            r#"
+                delete from labels where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash, FromRow, ToSchema)]
+pub struct Label {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct CreateLabel {
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    #[schema(min_length = 1, max_length = 100)]
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct UpdateLabel {
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    #[schema(min_length = 1, max_length = 100)]
+    name: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        test_utils::{connect_postgres, connect_sqlite, find_or_create_user},
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+    };
+
+    async fn label_crud_scenario<T: LabelRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        let name = "[label_crud_scenario] label name";
+
+        let created = repository
+            .create(owner_id, CreateLabel::new(name.to_string()))
+            .await
+            .expect("[create] returned error");
+        assert_eq!(created.name, name);
+
+        let duplicated = repository
+            .create(owner_id, CreateLabel::new(name.to_string()))
+            .await;
+        assert!(duplicated.is_err());
+
+        let label = repository
+            .find(owner_id, created.id)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(created, label);
+        let res = repository.find(other_owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let labels = repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error");
+        assert!(labels.contains(&created));
+        let labels = repository
+            .all(other_owner_id)
+            .await
+            .expect("[all] returned error");
+        assert!(!labels.contains(&created));
+
+        let theirs = repository
+            .create(other_owner_id, CreateLabel::new(name.to_string()))
+            .await
+            .expect("[create] returned error");
+        assert_ne!(theirs.id, created.id, "names are unique per owner");
+
+        let updated_name = "[label_crud_scenario] updated name";
+        let res = repository
+            .update(
+                other_owner_id,
+                created.id,
+                UpdateLabel::new(updated_name.to_string()),
+            )
+            .await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let label = repository
+            .update(
+                owner_id,
+                created.id,
+                UpdateLabel::new(updated_name.to_string()),
+            )
+            .await
+            .expect("[update] returned error");
+        assert_eq!(created.id, label.id);
+        assert_eq!(label.name, updated_name);
+
+        let res = repository.delete(other_owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        repository
+            .delete(owner_id, label.id)
+            .await
+            .expect("[delete] returned error");
+        let res = repository.find(owner_id, created.id).await;
+        assert!(res.is_err());
+
+        let res = repository.delete(owner_id, created.id).await;
+        assert!(res.is_err());
+
+        let recreated = repository
+            .create(owner_id, CreateLabel::new(name.to_string()))
+            .await
+            .expect("[create] returned error");
+        assert_ne!(recreated.id, created.id, "ids are not reused");
+        for (owner, id) in [(owner_id, recreated.id), (other_owner_id, theirs.id)] {
+            repository
+                .delete(owner, id)
+                .await
+                .expect("[delete] returned error");
+        }
+    }
+
+    #[tokio::test]
+    async fn label_crud_scenario_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "label@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "label-other@example.com").await;
+        label_crud_scenario(LabelRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn label_crud_scenario_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "label@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "label-other@example.com").await;
+        label_crud_scenario(
+            LabelRepositoryForSqlite::new(pool),
+            owner_id,
+            other_owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn label_crud_scenario_for_memory() {
+        label_crud_scenario(test_utils::LabelRepositoryForMemory::new(), 1, 2).await;
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    impl Label {
+        pub fn new(id: i32, name: String) -> Self {
+            Self { id, name }
+        }
+    }
+
+    impl CreateLabel {
+        pub fn new(name: String) -> Self {
+            Self { name }
+        }
+    }
+
+    impl UpdateLabel {
+        pub fn new(name: String) -> Self {
+            Self { name }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct LabelDatas {
+        // Every label together with the id of its owner.
+        labels: HashMap<i32, (i32, Label)>,
+        // Ids are never handed out again, not even after a delete.
+        last_id: i32,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct LabelRepositoryForMemory {
+        store: Arc<RwLock<LabelDatas>>,
+    }
+
+    impl LabelRepositoryForMemory {
+        pub fn new() -> Self {
+            LabelRepositoryForMemory {
+                store: Arc::default(),
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, LabelDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, LabelDatas> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl LabelRepository for LabelRepositoryForMemory {
+        async fn create(
+            &self,
+            owner_id: i32,
+            payload: CreateLabel,
+        ) -> Result<Label, RepositoryError> {
+            let mut store = self.write_store_ref();
+            if let Some((_, label)) = store
+                .labels
+                .values()
+                .find(|(owner, label)| *owner == owner_id && label.name == payload.name)
+            {
+                return Err(RepositoryError::Conflict(format!(
+                    "label name already exists, id is {}",
+                    label.id
+                )));
+            }
+            store.last_id += 1;
+            let label = Label::new(store.last_id, payload.name);
+            store.labels.insert(label.id, (owner_id, label.clone()));
+            Ok(label)
+        }
+        async fn find(&self, owner_id: i32, id: i32) -> Result<Label, RepositoryError> {
+            let store = self.read_store_ref();
+            store
+                .labels
+                .get(&id)
+                .filter(|(owner, _)| *owner == owner_id)
+                .map(|(_, label)| label.clone())
+                .ok_or(RepositoryError::NotFound(id))
+        }
+        async fn all(&self, owner_id: i32) -> Result<Vec<Label>, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut labels: Vec<Label> = store
+                .labels
+                .values()
+                .filter(|(owner, _)| *owner == owner_id)
+                .map(|(_, label)| label.clone())
+                .collect();
+            labels.sort_by_key(|label| label.id);
+            Ok(labels)
+        }
+        async fn update(
+            &self,
+            owner_id: i32,
+            id: i32,
+            payload: UpdateLabel,
+        ) -> Result<Label, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let (_, label) = store
+                .labels
+                .get_mut(&id)
+                .filter(|(owner, _)| *owner == owner_id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            label.name = payload.name;
+            Ok(label.clone())
+        }
+        async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            if store
+                .labels
+                .get(&id)
+                .is_none_or(|(owner, _)| *owner != owner_id)
+            {
+                return Err(RepositoryError::NotFound(id));
+            }
+            store.labels.remove(&id);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,118 @@
+pub mod calendar_object;
+pub mod changes;
+pub mod idempotency;
+pub mod label;
+pub mod project;
+pub mod todo;
+pub mod user;
+pub mod webhook;
+
+use sqlx::{
+    error::ErrorKind,
+    sqlite::{SqliteConnectOptions, SqlitePool},
+};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RepositoryError {
+    #[error("Unexpected Error: [{0}]")]
+    Unexpected(String),
+    #[error("NotFound, id is {0}")]
+    NotFound(i32),
+    #[error("VersionMismatch, id is {0}")]
+    VersionMismatch(i32),
+    #[error("Conflict: [{0}]")]
+    Conflict(String),
+    #[error("Unavailable: [{0}]")]
+    Unavailable(String),
+    #[error("Validation error: [{0}]")]
+    Validation(String),
+}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::Database(ref db) => match db.kind() {
+                ErrorKind::UniqueViolation => RepositoryError::Conflict(db.message().to_string()),
+                ErrorKind::ForeignKeyViolation
+                | ErrorKind::NotNullViolation
+                | ErrorKind::CheckViolation => {
+                    RepositoryError::Validation(db.message().to_string())
+                }
+                _ => RepositoryError::Unexpected(e.to_string()),
+            },
+            sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_) => RepositoryError::Unavailable(e.to_string()),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        }
+    }
+}
+
+pub async fn connect_sqlite(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await?;
+    sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+    Ok(pool)
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::user::UserRepository;
+    use dotenv::dotenv;
+    use sqlx::{sqlite::SqlitePoolOptions, PgPool, SqlitePool};
+    use std::env;
+
+    pub async fn connect_postgres() -> PgPool {
+        dotenv().ok();
+        let user = env::var("POSTGRES_USER").unwrap_or("postgres".to_string());
+        let password = env::var("POSTGRES_PASSWORD").unwrap_or("postgres".to_string());
+        let db = env::var("POSTGRES_DB").unwrap_or("postgres".to_string());
+        let host = env::var("POSTGRES_HOSTNAME").unwrap_or("localhost".to_string());
+        let port = env::var("POSTGRES_PORT").unwrap_or("5432".to_string());
+        let database_url = &format!(
+            "postgresql://{}:{}@{}:{}/{}",
+            user, password, host, port, db
+        );
+        PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url))
+    }
+
+    // Every connection to `sqlite::memory:` opens its own database, so the pool is
+    // pinned to a single connection that is never recycled.
+    pub async fn connect_sqlite() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .expect("fail connect sqlite memory database");
+        sqlx::migrate!("./migrations/sqlite")
+            .run(&pool)
+            .await
+            .expect("fail migrate sqlite memory database");
+        pool
+    }
+
+    pub async fn find_or_create_user<U: UserRepository>(repository: &U, email: &str) -> i32 {
+        match repository
+            .find_by_email(email)
+            .await
+            .expect("[user find] returned error")
+        {
+            Some(user) => user.id,
+            None => {
+                repository
+                    .create(email.to_string(), "hash".to_string())
+                    .await
+                    .expect("[user create] returned error")
+                    .id
+            }
+        }
+    }
+}
@@ -0,0 +1,671 @@
+use super::{
+    todo::{TodoRepository, TodoRepositoryForDB, TodoRepositoryForSqlite},
+    RepositoryError,
+};
+use axum::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[async_trait]
+pub trait ProjectRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(
+        &self,
+        owner_id: i32,
+        payload: CreateProject,
+    ) -> Result<Project, RepositoryError>;
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Project, RepositoryError>;
+    async fn all(&self, owner_id: i32) -> Result<Vec<Project>, RepositoryError>;
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateProject,
+    ) -> Result<Project, RepositoryError>;
+    /// Deletes a project. Its todos move to the inbox, and with `Cascade` go to
+    /// the trash from there, so restoring one brings it back to the inbox.
+    async fn delete(
+        &self,
+        owner_id: i32,
+        id: i32,
+        mode: ProjectDeleteMode,
+    ) -> Result<(), RepositoryError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForDB {
+    todos: TodoRepositoryForDB,
+}
+
+impl ProjectRepositoryForDB {
+    /// Shares the database and the change feed of `todos`.
+    pub fn new(todos: TodoRepositoryForDB) -> Self {
+        ProjectRepositoryForDB { todos }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForDB {
+    async fn create(
+        &self,
+        owner_id: i32,
+        payload: CreateProject,
+    ) -> Result<Project, RepositoryError> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                insert into projects (owner_id, name, created_at)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(owner_id)
+        .bind(payload.name)
+        .bind(Utc::now())
+        .fetch_one(self.todos.pool())
+        .await?;
+
+        Ok(project)
+    }
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Project, RepositoryError> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(self.todos.pool())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(project)
+    }
+    async fn all(&self, owner_id: i32) -> Result<Vec<Project>, RepositoryError> {
+        let projects = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects
+                where owner_id = $1
+                order by id asc
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(self.todos.pool())
+        .await?;
+
+        Ok(projects)
+    }
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateProject,
+    ) -> Result<Project, RepositoryError> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                update projects set name = $1
+                where id = $2 and owner_id = $3
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(self.todos.pool())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(project)
+    }
+    async fn delete(
+        &self,
+        owner_id: i32,
+        id: i32,
+        mode: ProjectDeleteMode,
+    ) -> Result<(), RepositoryError> {
+        let mut changes = vec![];
+        let mut tx = self.todos.pool().begin().await?;
+        let todo_ids: Vec<i32> = sqlx::query_scalar(
+            r#"
+                select id from todos
+                where project_id = $1 and owner_id = $2 and deleted_at is null
+                order by id asc
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        // The todos fall back to the inbox through `on delete set null`.
+        let result = sqlx::query(
+            r#"
+                delete from projects where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+        if mode == ProjectDeleteMode::Cascade {
+            let now = Utc::now().trunc_subsecs(6);
+            // A todo trashed along with its parent counts no more.
+            for todo_id in todo_ids {
+                TodoRepositoryForDB::trash_in(&mut tx, &mut changes, owner_id, todo_id, now, None)
+                    .await?;
+            }
+        }
+        tx.commit().await?;
+        self.todos.changes().publish(changes);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForSqlite {
+    todos: TodoRepositoryForSqlite,
+}
+
+impl ProjectRepositoryForSqlite {
+    /// Shares the database and the change feed of `todos`.
+    pub fn new(todos: TodoRepositoryForSqlite) -> Self {
+        ProjectRepositoryForSqlite { todos }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForSqlite {
+    async fn create(
+        &self,
+        owner_id: i32,
+        payload: CreateProject,
+    ) -> Result<Project, RepositoryError> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                insert into projects (owner_id, name, created_at)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(owner_id)
+        .bind(payload.name)
+        .bind(Utc::now())
+        .fetch_one(self.todos.pool())
+        .await?;
+
+        Ok(project)
+    }
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Project, RepositoryError> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(self.todos.pool())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(project)
+    }
+    async fn all(&self, owner_id: i32) -> Result<Vec<Project>, RepositoryError> {
+        let projects = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects
+                where owner_id = $1
+                order by id asc
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(self.todos.pool())
+        .await?;
+
+        Ok(projects)
+    }
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateProject,
+    ) -> Result<Project, RepositoryError> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                update projects set name = $1
+                where id = $2 and owner_id = $3
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .bind(owner_id)
+        .fetch_one(self.todos.pool())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::from(e),
+        })?;
+
+        Ok(project)
+    }
+    async fn delete(
+        &self,
+        owner_id: i32,
+        id: i32,
+        mode: ProjectDeleteMode,
+    ) -> Result<(), RepositoryError> {
+        let mut changes = vec![];
+        let mut tx = self.todos.pool().begin().await?;
+        let todo_ids: Vec<i32> = sqlx::query_scalar(
+            r#"
+                select id from todos
+                where project_id = $1 and owner_id = $2 and deleted_at is null
+                order by id asc
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        // The todos fall back to the inbox through `on delete set null`.
+        let result = sqlx::query(
+            r#"
+                delete from projects where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+        if mode == ProjectDeleteMode::Cascade {
+            let now = Utc::now().trunc_subsecs(6);
+            // A todo trashed along with its parent counts no more.
+            for todo_id in todo_ids {
+                TodoRepositoryForSqlite::trash_in(
+                    &mut tx,
+                    &mut changes,
+                    owner_id,
+                    todo_id,
+                    now,
+                    None,
+                )
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        self.todos.changes().publish(changes);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct Project {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct CreateProject {
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    #[schema(min_length = 1, max_length = 100)]
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct UpdateProject {
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    #[schema(min_length = 1, max_length = 100)]
+    name: String,
+}
+
+/// What happens to the todos of a deleted project: they either move to the
+/// inbox (no project) or go to the trash.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectDeleteMode {
+    #[default]
+    Inbox,
+    Cascade,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteProjectQuery {
+    #[serde(default)]
+    pub todos: ProjectDeleteMode,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        changes::ChangeKind,
+        test_utils::{connect_postgres, connect_sqlite, find_or_create_user},
+        todo::{CreateTodo, TodoAction, TodoEvent, UpdateTodo},
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+    };
+
+    async fn project_scenario<P: ProjectRepository, T: TodoRepository>(
+        repository: P,
+        todos: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        for project in repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error")
+        {
+            repository
+                .delete(owner_id, project.id, ProjectDeleteMode::Cascade)
+                .await
+                .expect("[cleanup] returned error");
+        }
+
+        let created = repository
+            .create(owner_id, CreateProject::new("Sprint 12".to_string()))
+            .await
+            .expect("[create] returned error");
+        assert_eq!(created.name, "Sprint 12");
+
+        let duplicated = repository
+            .create(owner_id, CreateProject::new("Sprint 12".to_string()))
+            .await;
+        assert!(matches!(duplicated, Err(RepositoryError::Conflict(_))));
+
+        let project = repository
+            .find(owner_id, created.id)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(created, project);
+        let res = repository.find(other_owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let projects = repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error");
+        assert_eq!(projects, vec![created.clone()]);
+
+        let project = repository
+            .update(
+                owner_id,
+                created.id,
+                UpdateProject::new("Sprint 13".to_string()),
+            )
+            .await
+            .expect("[update] returned error");
+        assert_eq!(project.name, "Sprint 13");
+
+        let res = todos
+            .create(
+                other_owner_id,
+                CreateTodo::new("[project_scenario] todo".to_string(), vec![])
+                    .in_project(created.id),
+            )
+            .await;
+        assert!(matches!(res, Err(RepositoryError::Validation(_))));
+
+        let kept = todos
+            .create(
+                owner_id,
+                CreateTodo::new("[project_scenario] kept".to_string(), vec![])
+                    .in_project(created.id),
+            )
+            .await
+            .expect("[todo create] returned error");
+        repository
+            .delete(owner_id, created.id, ProjectDeleteMode::Inbox)
+            .await
+            .expect("[delete] returned error");
+        let kept = todos
+            .find(owner_id, kept.id())
+            .await
+            .expect("[todo find] returned error");
+        assert_eq!(kept.project_id(), None);
+
+        let other = repository
+            .create(owner_id, CreateProject::new("Groceries".to_string()))
+            .await
+            .expect("[create] returned error");
+        let moved = todos
+            .update(
+                owner_id,
+                kept.id(),
+                UpdateTodo::move_to_project(Some(other.id)),
+                None,
+            )
+            .await
+            .expect("[todo update] returned error");
+        assert_eq!(moved.project_id(), Some(other.id));
+        let res = repository
+            .delete(other_owner_id, other.id, ProjectDeleteMode::Cascade)
+            .await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let mut feed = todos.changes().subscribe(None).receiver;
+        repository
+            .delete(owner_id, other.id, ProjectDeleteMode::Cascade)
+            .await
+            .expect("[delete] returned error");
+        let res = todos.find(owner_id, kept.id()).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let change = feed.recv().await.expect("[feed] closed");
+        assert_eq!(change.kind, ChangeKind::Deleted);
+        assert_eq!(change.todo.id(), kept.id());
+        let trash = todos.trash(owner_id).await.expect("[trash] returned error");
+        assert!(trash.iter().any(|todo| todo.id() == kept.id()));
+        let events = todos
+            .history(owner_id, kept.id())
+            .await
+            .expect("[history] returned error");
+        assert_eq!(
+            events.last().map(TodoEvent::action),
+            Some(TodoAction::Delete)
+        );
+        let restored = todos
+            .undo(owner_id, kept.id())
+            .await
+            .expect("[undo] returned error");
+        assert_eq!(restored.project_id(), None, "back to the inbox");
+    }
+
+    #[tokio::test]
+    async fn project_scenario_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "project@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "project-other@example.com").await;
+        let todos = TodoRepositoryForDB::new(pool);
+        project_scenario(
+            ProjectRepositoryForDB::new(todos.clone()),
+            todos,
+            owner_id,
+            other_owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn project_scenario_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "project@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "project-other@example.com").await;
+        let todos = TodoRepositoryForSqlite::new(pool);
+        project_scenario(
+            ProjectRepositoryForSqlite::new(todos.clone()),
+            todos,
+            owner_id,
+            other_owner_id,
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::todo::test_utils::TodoRepositoryForMemory;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    impl CreateProject {
+        pub fn new(name: String) -> Self {
+            Self { name }
+        }
+    }
+
+    impl UpdateProject {
+        pub fn new(name: String) -> Self {
+            Self { name }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct ProjectDatas {
+        projects: HashMap<i32, (i32, Project)>,
+        // Ids are never handed out again, not even after a delete.
+        last_id: i32,
+    }
+
+    // Moves the todos of a deleted project in `todos`, as the database does.
+    #[derive(Debug, Clone)]
+    pub struct ProjectRepositoryForMemory {
+        store: Arc<RwLock<ProjectDatas>>,
+        todos: TodoRepositoryForMemory,
+    }
+
+    impl Default for ProjectRepositoryForMemory {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl ProjectRepositoryForMemory {
+        /// Keeps todos in a store of its own, for apps that do not delete projects.
+        pub fn new() -> Self {
+            Self::with_todos(TodoRepositoryForMemory::new(vec![]))
+        }
+
+        pub fn with_todos(todos: TodoRepositoryForMemory) -> Self {
+            ProjectRepositoryForMemory {
+                store: Arc::default(),
+                todos,
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, ProjectDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, ProjectDatas> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl ProjectRepository for ProjectRepositoryForMemory {
+        async fn create(
+            &self,
+            owner_id: i32,
+            payload: CreateProject,
+        ) -> Result<Project, RepositoryError> {
+            let mut store = self.write_store_ref();
+            if store
+                .projects
+                .values()
+                .any(|(owner, project)| *owner == owner_id && project.name == payload.name)
+            {
+                return Err(RepositoryError::Conflict(format!(
+                    "project name already exists: {}",
+                    payload.name
+                )));
+            }
+            store.last_id += 1;
+            let project = Project {
+                id: store.last_id,
+                name: payload.name,
+                created_at: Utc::now(),
+            };
+            store
+                .projects
+                .insert(project.id, (owner_id, project.clone()));
+            Ok(project)
+        }
+        async fn find(&self, owner_id: i32, id: i32) -> Result<Project, RepositoryError> {
+            let store = self.read_store_ref();
+            store
+                .projects
+                .get(&id)
+                .filter(|(owner, _)| *owner == owner_id)
+                .map(|(_, project)| project.clone())
+                .ok_or(RepositoryError::NotFound(id))
+        }
+        async fn all(&self, owner_id: i32) -> Result<Vec<Project>, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut projects: Vec<Project> = store
+                .projects
+                .values()
+                .filter(|(owner, _)| *owner == owner_id)
+                .map(|(_, project)| project.clone())
+                .collect();
+            projects.sort_by_key(|project| project.id);
+            Ok(projects)
+        }
+        async fn update(
+            &self,
+            owner_id: i32,
+            id: i32,
+            payload: UpdateProject,
+        ) -> Result<Project, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let (_, project) = store
+                .projects
+                .get_mut(&id)
+                .filter(|(owner, _)| *owner == owner_id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            project.name = payload.name;
+            Ok(project.clone())
+        }
+        async fn delete(
+            &self,
+            owner_id: i32,
+            id: i32,
+            mode: ProjectDeleteMode,
+        ) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            store
+                .projects
+                .get(&id)
+                .filter(|(owner, _)| *owner == owner_id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            store.projects.remove(&id);
+            self.todos
+                .leave_project(owner_id, id, mode == ProjectDeleteMode::Cascade);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,4542 @@
+use super::{
+    changes::{TodoChange, TodoChanges},
+    label::Label,
+    RepositoryError,
+};
+use crate::recurrence::Recurrence;
+use axum::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use sqlx::{
+    types::Json, Database, Encode, Executor, FromRow, PgConnection, PgPool, Postgres, QueryBuilder,
+    Sqlite, SqliteConnection, SqlitePool, Type,
+};
+use std::{collections::HashMap, str::FromStr};
+use thiserror::Error;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationErrors};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+
+#[async_trait]
+pub trait TodoRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, owner_id: i32, payload: CreateTodo) -> Result<Todo, RepositoryError>;
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError>;
+    async fn all(&self, owner_id: i32, query: TodoQuery) -> Result<TodoPage, RepositoryError>;
+    /// With a `version`, fails with `VersionMismatch` unless the todo is still at it.
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateTodo,
+        version: Option<i32>,
+    ) -> Result<Todo, RepositoryError>;
+    /// With a `version`, fails with `VersionMismatch` unless the todo is still at it.
+    async fn delete(
+        &self,
+        owner_id: i32,
+        id: i32,
+        version: Option<i32>,
+    ) -> Result<(), RepositoryError>;
+    /// Trashed todos, most recently deleted first.
+    async fn trash(&self, owner_id: i32) -> Result<Vec<Todo>, RepositoryError>;
+    /// Takes a todo out of the trash, together with the subtree deleted along with it.
+    async fn restore(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError>;
+    /// Permanently deletes a todo from the trash.
+    async fn purge(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError>;
+    /// Permanently deletes, for all owners, every todo trashed before `before`. There
+    /// is no actor, so this is not recorded in the history.
+    async fn purge_expired(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError>;
+    /// Changes made to the todo `id`, oldest first. Kept after the todo is purged.
+    async fn history(&self, owner_id: i32, id: i32) -> Result<Vec<TodoEvent>, RepositoryError>;
+    /// Changes made by the owner to any of their todos, newest first.
+    async fn audit(
+        &self,
+        owner_id: i32,
+        query: AuditQuery,
+    ) -> Result<TodoEventPage, RepositoryError>;
+    /// Reverts the most recent update or deletion of the todo to how it was
+    /// before. Fails with `Conflict` when there is nothing to undo or the todo
+    /// changed since that was recorded.
+    async fn undo(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError>;
+    /// Applies the most recently undone change again.
+    async fn redo(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError>;
+    /// Applies all operations in order, or none of them if one fails.
+    async fn batch(
+        &self,
+        owner_id: i32,
+        operations: Vec<TodoOperation>,
+    ) -> Result<Vec<TodoOperationResult>, BatchError>;
+    /// Completes every open todo matching the filters of `query` and returns how
+    /// many there were. Sorting and pagination are ignored.
+    async fn complete_all(&self, owner_id: i32, query: TodoQuery) -> Result<u64, RepositoryError>;
+    /// Moves every completed todo matching the filters of `query` to the trash,
+    /// together with everything below it, and returns how many todos were moved.
+    /// Sorting and pagination are ignored.
+    async fn clear_completed(
+        &self,
+        owner_id: i32,
+        query: TodoQuery,
+    ) -> Result<u64, RepositoryError>;
+    /// Every todo below `id`, at any depth, ordered by id.
+    async fn descendants(&self, owner_id: i32, id: i32) -> Result<Vec<Todo>, RepositoryError>;
+    /// Full-text search, best match first.
+    async fn search(
+        &self,
+        owner_id: i32,
+        query: TodoSearchQuery,
+    ) -> Result<Vec<TodoSearchHit>, RepositoryError>;
+    /// Feed of the changes committed through this repository, for all owners.
+    fn changes(&self) -> &TodoChanges;
+}
+
+// Postgres and SQLite share all queries but the ones of the hooks each backend
+// implements on its own: `CONTAINS`, `lock_in` and `search_hits`.
+macro_rules! sql_todo_repository {
+    ($name:ident, $db:ty, $pool:ty, $conn:ty) => {
+        #[derive(Debug, Clone)]
+        pub struct $name {
+            pool: $pool,
+            changes: TodoChanges,
+        }
+
+        impl $name {
+            pub fn new(pool: $pool) -> Self {
+                $name {
+                    pool,
+                    changes: TodoChanges::default(),
+                }
+            }
+
+            pub(crate) fn pool(&self) -> &$pool {
+                &self.pool
+            }
+
+            async fn attach_labels<'e, E>(
+                executor: E,
+                todos: Vec<Todo>,
+            ) -> Result<Vec<Todo>, RepositoryError>
+            where
+                E: Executor<'e, Database = $db>,
+            {
+                if todos.is_empty() {
+                    return Ok(todos);
+                }
+                let mut builder = QueryBuilder::new(
+                    r#"
+                        select todo_labels.todo_id, labels.id, labels.name
+                        from todo_labels
+                        inner join labels on labels.id = todo_labels.label_id
+                        where todo_labels.todo_id in (
+                    "#,
+                );
+                let mut ids = builder.separated(", ");
+                for todo in todos.iter() {
+                    ids.push_bind(todo.id);
+                }
+                builder.push(") order by labels.id asc");
+                let rows = builder
+                    .build_query_as::<TodoLabelFromRow>()
+                    .fetch_all(executor)
+                    .await?;
+
+                Ok(assign_labels(todos, rows))
+            }
+
+            // A todo can only be filed under a project of its own owner.
+            async fn ensure_project(
+                conn: &mut $conn,
+                owner_id: i32,
+                project_id: Option<i32>,
+            ) -> Result<(), RepositoryError> {
+                let Some(project_id) = project_id else {
+                    return Ok(());
+                };
+                let found = sqlx::query(
+                    r#"
+                        select id from projects where id = $1 and owner_id = $2
+                    "#,
+                )
+                .bind(project_id)
+                .bind(owner_id)
+                .fetch_optional(conn)
+                .await?;
+                if found.is_none() {
+                    return Err(RepositoryError::Validation(format!(
+                        "project not found, id is {}",
+                        project_id
+                    )));
+                }
+
+                Ok(())
+            }
+
+            // A todo can only carry labels of its own owner.
+            async fn ensure_labels(
+                conn: &mut $conn,
+                owner_id: i32,
+                labels: &[i32],
+            ) -> Result<(), RepositoryError> {
+                if labels.is_empty() {
+                    return Ok(());
+                }
+                let mut builder = QueryBuilder::new("select id from labels where owner_id = ");
+                builder.push_bind(owner_id);
+                builder.push(" and id in (");
+                let mut ids = builder.separated(", ");
+                for id in labels {
+                    ids.push_bind(*id);
+                }
+                builder.push(")");
+                let found: Vec<i32> = builder.build_query_scalar().fetch_all(conn).await?;
+                check_labels(labels, &found)
+            }
+
+            // Puts the labels on the todo `id`, leaving out those that do not exist.
+            async fn insert_labels(
+                conn: &mut $conn,
+                id: i32,
+                labels: &[i32],
+            ) -> Result<(), RepositoryError> {
+                if labels.is_empty() {
+                    return Ok(());
+                }
+                let mut builder =
+                    QueryBuilder::new("insert into todo_labels (todo_id, label_id) select ");
+                builder.push_bind(id);
+                builder.push(", id from labels where id in (");
+                let mut ids = builder.separated(", ");
+                for id in labels {
+                    ids.push_bind(*id);
+                }
+                builder.push(")");
+                builder.build().execute(conn).await?;
+
+                Ok(())
+            }
+
+            // The parent has to belong to the same owner and must not sit below the todo
+            // being moved, otherwise the hierarchy would turn into a cycle.
+            async fn ensure_parent(
+                conn: &mut $conn,
+                owner_id: i32,
+                id: Option<i32>,
+                parent_id: Option<i32>,
+            ) -> Result<(), RepositoryError> {
+                let Some(parent_id) = parent_id else {
+                    return Ok(());
+                };
+                let ancestors: Vec<i32> = sqlx::query_scalar(ANCESTORS_QUERY)
+                    .bind(parent_id)
+                    .bind(owner_id)
+                    .fetch_all(conn)
+                    .await?;
+                check_parent(id, parent_id, &ancestors)
+            }
+
+            // The `*_in` functions do the work of the matching trait methods on a
+            // connection the caller owns, so a batch can share one transaction.
+            pub(crate) async fn create_in(
+                conn: &mut $conn,
+                changes: &mut Vec<TodoChange>,
+                owner_id: i32,
+                payload: CreateTodo,
+            ) -> Result<Todo, RepositoryError> {
+                Self::ensure_project(conn, owner_id, payload.project_id).await?;
+                Self::ensure_parent(conn, owner_id, None, payload.parent_id).await?;
+                Self::ensure_labels(conn, owner_id, &payload.labels).await?;
+                let todo = sqlx::query_as::<_, Todo>(
+                    r#"
+                        insert into todos (
+                            owner_id, text, completed, priority, due_at, created_at, updated_at,
+                            project_id, parent_id, auto_complete, recurrence, completed_at
+                        )
+                        values ($1, $2, $10, $3, $4, $5, $5, $6, $7, $8, $9, $11)
+                        returning *
+                    "#,
+                )
+                .bind(owner_id)
+                .bind(payload.text.clone())
+                .bind(payload.priority.unwrap_or_default())
+                .bind(payload.due_at)
+                .bind(Utc::now())
+                .bind(payload.project_id)
+                .bind(payload.parent_id)
+                .bind(payload.auto_complete.unwrap_or_default())
+                .bind(payload.recurrence)
+                .bind(payload.completed_at.is_some())
+                .bind(payload.completed_at)
+                .fetch_one(&mut *conn)
+                .await?;
+
+                Self::insert_labels(conn, todo.id, &payload.labels).await?;
+
+                let todo = Self::attach_labels(&mut *conn, vec![todo]).await?.remove(0);
+                Self::record_in(
+                    conn,
+                    changes,
+                    owner_id,
+                    TodoAction::Create,
+                    None,
+                    Some(&todo),
+                )
+                .await?;
+
+                Ok(todo)
+            }
+
+            async fn update_in(
+                conn: &mut $conn,
+                changes: &mut Vec<TodoChange>,
+                owner_id: i32,
+                id: i32,
+                payload: UpdateTodo,
+                version: Option<i32>,
+            ) -> Result<Todo, RepositoryError> {
+                Self::lock_in(conn, owner_id, id).await?;
+                let before = Self::find_in(conn, owner_id, id).await?;
+                if version.is_some_and(|version| version != before.version) {
+                    return Err(RepositoryError::VersionMismatch(id));
+                }
+                Self::ensure_project(conn, owner_id, payload.project_id.flatten()).await?;
+                Self::ensure_parent(conn, owner_id, Some(id), payload.parent_id.flatten()).await?;
+                if let Some(labels) = &payload.labels {
+                    Self::ensure_labels(conn, owner_id, labels).await?;
+                }
+                // Postgres keeps microseconds, so `now` is truncated to survive the round
+                // trip unchanged and be comparable with the returned `completed_at`.
+                let now = Utc::now().trunc_subsecs(6);
+                let todo = sqlx::query_as::<_, Todo>(
+                    r#"
+                        update todos set
+                            text = coalesce($1, text),
+                            completed = coalesce($2, completed),
+                            completed_at = case
+                                when $2 is null then completed_at
+                                when $2 then coalesce(completed_at, $3)
+                                else null
+                            end,
+                            priority = coalesce($4, priority),
+                            due_at = case when $5 then $6 else due_at end,
+                            project_id = case when $9 then $10 else project_id end,
+                            parent_id = case when $11 then $12 else parent_id end,
+                            auto_complete = coalesce($13, auto_complete),
+                            recurrence = case when $14 then $15 else recurrence end,
+                            updated_at = $3,
+                            version = version + 1
+                        where id = $7 and owner_id = $8 and deleted_at is null
+                            and ($16 is null or version = $16)
+                        returning *
+                    "#,
+                )
+                .bind(payload.text)
+                .bind(payload.completed)
+                .bind(now)
+                .bind(payload.priority)
+                .bind(payload.due_at.is_some())
+                .bind(payload.due_at.flatten())
+                .bind(id)
+                .bind(owner_id)
+                .bind(payload.project_id.is_some())
+                .bind(payload.project_id.flatten())
+                .bind(payload.parent_id.is_some())
+                .bind(payload.parent_id.flatten())
+                .bind(payload.auto_complete)
+                .bind(payload.recurrence.is_some())
+                .bind(payload.recurrence.flatten())
+                .bind(version)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| match e {
+                    // Found a moment ago, so the guard on `version` is what missed.
+                    sqlx::Error::RowNotFound if version.is_some() => RepositoryError::VersionMismatch(id),
+                    sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+                    _ => RepositoryError::from(e),
+                })?;
+
+                if let Some(labels) = payload.labels {
+                    sqlx::query(
+                        r#"
+                            delete from todo_labels where todo_id=$1
+                        "#,
+                    )
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+
+                    Self::insert_labels(conn, id, &labels).await?;
+                }
+
+                let todo = Self::attach_labels(&mut *conn, vec![todo]).await?.remove(0);
+                Self::record_in(
+                    conn,
+                    changes,
+                    owner_id,
+                    TodoAction::Update,
+                    Some(&before),
+                    Some(&todo),
+                )
+                .await?;
+
+                if let Some((due_at, recurrence)) = todo.next_occurrence(now) {
+                    let next_id: i32 = sqlx::query_scalar(NEXT_OCCURRENCE_QUERY)
+                        .bind(id)
+                        .bind(due_at)
+                        .bind(now)
+                        .bind(recurrence)
+                        .fetch_one(&mut *conn)
+                        .await?;
+                    sqlx::query(COPY_LABELS_QUERY)
+                        .bind(next_id)
+                        .bind(id)
+                        .execute(&mut *conn)
+                        .await?;
+                    let next = Self::find_in(conn, owner_id, next_id).await?;
+                    Self::record_in(
+                        conn,
+                        changes,
+                        owner_id,
+                        TodoAction::Create,
+                        None,
+                        Some(&next),
+                    )
+                    .await?;
+                }
+
+                let mut parent_id = todo.parent_id;
+                while let Some(id) = parent_id {
+                    Self::lock_in(conn, owner_id, id).await?;
+                    let before = Self::find_in(conn, owner_id, id).await?;
+                    let Some(next) = sqlx::query_scalar::<_, Option<i32>>(ROLL_UP_QUERY)
+                        .bind(id)
+                        .bind(now)
+                        .fetch_optional(&mut *conn)
+                        .await?
+                    else {
+                        break;
+                    };
+                    let after = Self::find_in(conn, owner_id, id).await?;
+                    Self::record_in(
+                        conn,
+                        changes,
+                        owner_id,
+                        TodoAction::Update,
+                        Some(&before),
+                        Some(&after),
+                    )
+                    .await?;
+                    parent_id = next;
+                }
+
+                Ok(todo)
+            }
+
+            async fn delete_in(
+                conn: &mut $conn,
+                changes: &mut Vec<TodoChange>,
+                owner_id: i32,
+                id: i32,
+                version: Option<i32>,
+            ) -> Result<(), RepositoryError> {
+                let now = Utc::now().trunc_subsecs(6);
+                let trashed = Self::trash_in(conn, changes, owner_id, id, now, version).await?;
+                if trashed == 0 {
+                    // Tells a missing todo apart from one that is at another version.
+                    Self::find_in(conn, owner_id, id).await?;
+                    return Err(RepositoryError::VersionMismatch(id));
+                }
+
+                Ok(())
+            }
+
+            // Moves `id` and its subtree to the trash and returns how many todos that were.
+            // With a `version`, nothing is moved unless `id` is still at it.
+            pub(crate) async fn trash_in(
+                conn: &mut $conn,
+                changes: &mut Vec<TodoChange>,
+                owner_id: i32,
+                id: i32,
+                now: DateTime<Utc>,
+                version: Option<i32>,
+            ) -> Result<usize, RepositoryError> {
+                let todos = sqlx::query_as::<_, Todo>(TRASH_QUERY)
+                    .bind(id)
+                    .bind(owner_id)
+                    .bind(now)
+                    .bind(version)
+                    .fetch_all(&mut *conn)
+                    .await?;
+                let todos = Self::attach_labels(&mut *conn, todos).await?;
+                for todo in todos.iter() {
+                    let before = Todo {
+                        deleted_at: None,
+                        ..todo.clone()
+                    };
+                    Self::record_in(
+                        conn,
+                        changes,
+                        owner_id,
+                        TodoAction::Delete,
+                        Some(&before),
+                        Some(todo),
+                    )
+                    .await?;
+                }
+
+                Ok(todos.len())
+            }
+
+            async fn find_in(
+                conn: &mut $conn,
+                owner_id: i32,
+                id: i32,
+            ) -> Result<Todo, RepositoryError> {
+                let todo = sqlx::query_as::<_, Todo>(
+                    r#"
+                        select * from todos where id=$1 and owner_id=$2 and deleted_at is null
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(|e| match e {
+                    sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+                    _ => RepositoryError::from(e),
+                })?;
+                let todo = Self::attach_labels(conn, vec![todo]).await?.remove(0);
+
+                Ok(todo)
+            }
+
+            // Appends to the history. The todo the event is about is `after`, or `before`
+            // when it is gone.
+            async fn record_in(
+                conn: &mut $conn,
+                changes: &mut Vec<TodoChange>,
+                actor_id: i32,
+                action: TodoAction,
+                before: Option<&Todo>,
+                after: Option<&Todo>,
+            ) -> Result<(), RepositoryError> {
+                changes.extend(TodoChange::new(actor_id, before, after));
+                let Some(todo_id) = after.or(before).map(|todo| todo.id) else {
+                    return Ok(());
+                };
+                sqlx::query(RECORD_QUERY)
+                    .bind(todo_id)
+                    .bind(actor_id)
+                    .bind(action)
+                    .bind(before.map(Json))
+                    .bind(after.map(Json))
+                    .bind(Utc::now())
+                    .bind(None::<i32>)
+                    .execute(conn)
+                    .await?;
+
+                Ok(())
+            }
+
+            async fn apply_in(
+                conn: &mut $conn,
+                changes: &mut Vec<TodoChange>,
+                owner_id: i32,
+                operation: TodoOperation,
+            ) -> Result<TodoOperationResult, RepositoryError> {
+                match operation {
+                    TodoOperation::Create { todo } => Self::create_in(conn, changes, owner_id, todo)
+                        .await
+                        .map(|todo| TodoOperationResult::Create { todo }),
+                    TodoOperation::Update { id, todo } => {
+                        Self::update_in(conn, changes, owner_id, id, todo, None)
+                            .await
+                            .map(|todo| TodoOperationResult::Update { todo })
+                    }
+                    TodoOperation::Delete { id } => Self::delete_in(conn, changes, owner_id, id, None)
+                        .await
+                        .map(|_| TodoOperationResult::Delete { id }),
+                }
+            }
+
+            // Takes the todo to the snapshot picked by `revert_target` and records that as
+            // `action`.
+            async fn revert(
+                &self,
+                owner_id: i32,
+                id: i32,
+                action: TodoAction,
+            ) -> Result<Todo, RepositoryError> {
+                let mut tx = self.pool.begin().await?;
+                Self::lock_in(&mut tx, owner_id, id).await?;
+                let events = sqlx::query_as::<_, TodoEvent>(
+                    r#"
+                        select * from todo_events where todo_id=$1 and actor_id=$2
+                        order by id asc
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_all(&mut *tx)
+                .await?;
+                let current = sqlx::query_as::<_, Todo>(
+                    r#"
+                        select * from todos where id=$1 and owner_id=$2
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RepositoryError::NotFound(id))?;
+                let current = Self::attach_labels(&mut *tx, vec![current])
+                    .await?
+                    .remove(0);
+                let (reverts, snapshot) = revert_target(&events, action, &current)?;
+                if snapshot.deleted_at.is_none() {
+                    Self::ensure_project(&mut tx, owner_id, snapshot.project_id).await?;
+                    Self::ensure_parent(&mut tx, owner_id, Some(id), snapshot.parent_id).await?;
+                }
+                let todo = sqlx::query_as::<_, Todo>(REVERT_QUERY)
+                    .bind(snapshot.text)
+                    .bind(snapshot.completed)
+                    .bind(snapshot.completed_at)
+                    .bind(snapshot.priority)
+                    .bind(snapshot.due_at)
+                    .bind(snapshot.project_id)
+                    .bind(snapshot.parent_id)
+                    .bind(snapshot.auto_complete)
+                    .bind(snapshot.recurrence)
+                    .bind(snapshot.deleted_at)
+                    .bind(Utc::now().trunc_subsecs(6))
+                    .bind(id)
+                    .bind(current.updated_at)
+                    .bind(current.deleted_at)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .ok_or_else(|| {
+                        RepositoryError::Conflict(format!("todo was changed in the meantime, id is {}", id))
+                    })?;
+
+                sqlx::query(
+                    r#"
+                        delete from todo_labels where todo_id=$1
+                    "#,
+                )
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+                // Labels deleted since the snapshot was taken stay off.
+                let label_ids: Vec<i32> = snapshot.labels.iter().map(|label| label.id).collect();
+                Self::insert_labels(&mut tx, id, &label_ids).await?;
+                let todo = Self::attach_labels(&mut *tx, vec![todo]).await?.remove(0);
+                sqlx::query(RECORD_QUERY)
+                    .bind(id)
+                    .bind(owner_id)
+                    .bind(action)
+                    .bind(Some(Json(&current)))
+                    .bind(Some(Json(&todo)))
+                    .bind(Utc::now())
+                    .bind(Some(reverts))
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                self.changes.publish(
+                    TodoChange::new(owner_id, Some(&current), Some(&todo))
+                        .into_iter()
+                        .collect(),
+                );
+
+                Ok(todo)
+            }
+        }
+
+        #[async_trait]
+        impl TodoRepository for $name {
+            async fn create(&self, owner_id: i32, payload: CreateTodo) -> Result<Todo, RepositoryError> {
+                let mut changes = vec![];
+                let mut tx = self.pool.begin().await?;
+                let todo = Self::create_in(&mut tx, &mut changes, owner_id, payload).await?;
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(todo)
+            }
+            async fn find(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+                let mut tx = self.pool.begin().await?;
+                let todo = Self::find_in(&mut tx, owner_id, id).await?;
+                tx.commit().await?;
+
+                Ok(todo)
+            }
+            async fn all(&self, owner_id: i32, query: TodoQuery) -> Result<TodoPage, RepositoryError> {
+                let mut builder = QueryBuilder::new("select * from todos");
+                push_filters(&mut builder, owner_id, &query, Self::CONTAINS);
+                push_order(&mut builder, &query);
+                let todos = builder
+                    .build_query_as::<Todo>()
+                    .fetch_all(&self.pool)
+                    .await?;
+
+                let mut builder = QueryBuilder::new("select count(*) from todos");
+                push_filters(&mut builder, owner_id, &query, Self::CONTAINS);
+                let total: i64 = builder.build_query_scalar().fetch_one(&self.pool).await?;
+
+                let (todos, next_cursor) = paginate(todos, query.limit(), |todo| todo.id);
+                let items = Self::attach_labels(&self.pool, todos).await?;
+
+                Ok(TodoPage {
+                    items,
+                    next_cursor,
+                    total,
+                })
+            }
+            async fn update(
+                &self,
+                owner_id: i32,
+                id: i32,
+                payload: UpdateTodo,
+                version: Option<i32>,
+            ) -> Result<Todo, RepositoryError> {
+                let mut changes = vec![];
+                let mut tx = self.pool.begin().await?;
+                let todo = Self::update_in(&mut tx, &mut changes, owner_id, id, payload, version).await?;
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(todo)
+            }
+            async fn delete(
+                &self,
+                owner_id: i32,
+                id: i32,
+                version: Option<i32>,
+            ) -> Result<(), RepositoryError> {
+                let mut changes = vec![];
+                let mut tx = self.pool.begin().await?;
+                Self::delete_in(&mut tx, &mut changes, owner_id, id, version).await?;
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(())
+            }
+            async fn batch(
+                &self,
+                owner_id: i32,
+                operations: Vec<TodoOperation>,
+            ) -> Result<Vec<TodoOperationResult>, BatchError> {
+                let mut changes = vec![];
+                let mut tx = self.pool.begin().await.map_err(RepositoryError::from)?;
+                let mut results = vec![];
+                for (index, operation) in operations.into_iter().enumerate() {
+                    let result = match operation.resolve(&results) {
+                        Ok(operation) => Self::apply_in(&mut tx, &mut changes, owner_id, operation).await,
+                        Err(error) => Err(error),
+                    }
+                    .map_err(|error| BatchError::at(index, error))?;
+                    results.push(result);
+                }
+                tx.commit().await.map_err(RepositoryError::from)?;
+                self.changes.publish(changes);
+
+                Ok(results)
+            }
+            async fn complete_all(&self, owner_id: i32, query: TodoQuery) -> Result<u64, RepositoryError> {
+                let mut changes = vec![];
+                let query = TodoQuery {
+                    completed: Some(false),
+                    ..query
+                };
+                let mut tx = self.pool.begin().await?;
+                let mut builder = QueryBuilder::new("select id from todos");
+                push_filters(&mut builder, owner_id, &query, Self::CONTAINS);
+                builder.push(" order by id asc");
+                let ids: Vec<i32> = builder.build_query_scalar().fetch_all(&mut *tx).await?;
+                for id in ids.iter() {
+                    Self::update_in(
+                        &mut tx,
+                        &mut changes,
+                        owner_id,
+                        *id,
+                        UpdateTodo::complete(),
+                        None,
+                    )
+                    .await?;
+                }
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(ids.len() as u64)
+            }
+            async fn clear_completed(
+                &self,
+                owner_id: i32,
+                query: TodoQuery,
+            ) -> Result<u64, RepositoryError> {
+                let mut changes = vec![];
+                let query = TodoQuery {
+                    completed: Some(true),
+                    ..query
+                };
+                let mut tx = self.pool.begin().await?;
+                let mut builder = QueryBuilder::new("select id from todos");
+                push_filters(&mut builder, owner_id, &query, Self::CONTAINS);
+                builder.push(" order by id asc");
+                let ids: Vec<i32> = builder.build_query_scalar().fetch_all(&mut *tx).await?;
+                // A single timestamp keeps each subtree restorable as a whole; todos below an
+                // earlier one are already in the trash and simply not touched again.
+                let now = Utc::now().trunc_subsecs(6);
+                let mut trashed = 0;
+                for id in ids.iter() {
+                    trashed += Self::trash_in(&mut tx, &mut changes, owner_id, *id, now, None).await?;
+                }
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(trashed as u64)
+            }
+            async fn trash(&self, owner_id: i32) -> Result<Vec<Todo>, RepositoryError> {
+                let todos = sqlx::query_as::<_, Todo>(
+                    r#"
+                        select * from todos where owner_id=$1 and deleted_at is not null
+                        order by deleted_at desc, id desc
+                    "#,
+                )
+                .bind(owner_id)
+                .fetch_all(&self.pool)
+                .await?;
+                let todos = Self::attach_labels(&self.pool, todos).await?;
+
+                Ok(todos)
+            }
+            async fn restore(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+                let mut changes = vec![];
+                let mut tx = self.pool.begin().await?;
+                let (deleted_at, parent_id) = sqlx::query_as::<_, (DateTime<Utc>, Option<i32>)>(
+                    r#"
+                        select deleted_at, parent_id from todos
+                        where id=$1 and owner_id=$2 and deleted_at is not null
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RepositoryError::NotFound(id))?;
+                if let Some(parent_id) = parent_id {
+                    let parent = sqlx::query(
+                        r#"
+                            select id from todos where id=$1 and deleted_at is null
+                        "#,
+                    )
+                    .bind(parent_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                    if parent.is_none() {
+                        return Err(RepositoryError::Conflict(format!(
+                            "parent is in the trash, id is {}",
+                            parent_id
+                        )));
+                    }
+                }
+                let todos = sqlx::query_as::<_, Todo>(RESTORE_QUERY)
+                    .bind(id)
+                    .bind(owner_id)
+                    .bind(deleted_at)
+                    .fetch_all(&mut *tx)
+                    .await?;
+                let todos = Self::attach_labels(&mut *tx, todos).await?;
+                for todo in todos.iter() {
+                    let before = Todo {
+                        deleted_at: Some(deleted_at),
+                        ..todo.clone()
+                    };
+                    Self::record_in(
+                        &mut tx,
+                        &mut changes,
+                        owner_id,
+                        TodoAction::Restore,
+                        Some(&before),
+                        Some(todo),
+                    )
+                    .await?;
+                }
+                let todo = Self::find_in(&mut tx, owner_id, id).await?;
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(todo)
+            }
+            // Only the purged todo itself is recorded, its subtree goes along with it.
+            async fn purge(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+                let mut changes = vec![];
+                let mut tx = self.pool.begin().await?;
+                let todo = sqlx::query_as::<_, Todo>(
+                    r#"
+                        select * from todos where id=$1 and owner_id=$2 and deleted_at is not null
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(RepositoryError::NotFound(id))?;
+                let todo = Self::attach_labels(&mut *tx, vec![todo]).await?.remove(0);
+                sqlx::query(
+                    r#"
+                        delete from todos where id=$1
+                    "#,
+                )
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+                Self::record_in(
+                    &mut tx,
+                    &mut changes,
+                    owner_id,
+                    TodoAction::Purge,
+                    Some(&todo),
+                    None,
+                )
+                .await?;
+                tx.commit().await?;
+                self.changes.publish(changes);
+
+                Ok(())
+            }
+            async fn purge_expired(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+                let result = sqlx::query(
+                    r#"
+                        delete from todos where deleted_at < $1
+                    "#,
+                )
+                .bind(before)
+                .execute(&self.pool)
+                .await?;
+
+                Ok(result.rows_affected())
+            }
+            async fn history(&self, owner_id: i32, id: i32) -> Result<Vec<TodoEvent>, RepositoryError> {
+                let events = sqlx::query_as::<_, TodoEvent>(
+                    r#"
+                        select * from todo_events where todo_id=$1 and actor_id=$2
+                        order by id asc
+                    "#,
+                )
+                .bind(id)
+                .bind(owner_id)
+                .fetch_all(&self.pool)
+                .await?;
+                if events.is_empty() {
+                    return Err(RepositoryError::NotFound(id));
+                }
+
+                Ok(events)
+            }
+            async fn audit(
+                &self,
+                owner_id: i32,
+                query: AuditQuery,
+            ) -> Result<TodoEventPage, RepositoryError> {
+                let mut builder = QueryBuilder::new("select * from todo_events");
+                push_event_filters(&mut builder, owner_id, &query);
+                let events = builder
+                    .build_query_as::<TodoEvent>()
+                    .fetch_all(&self.pool)
+                    .await?;
+                let (items, next_cursor) = paginate(events, query.limit(), |event| event.id);
+
+                Ok(TodoEventPage { items, next_cursor })
+            }
+            async fn undo(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+                self.revert(owner_id, id, TodoAction::Undo).await
+            }
+            async fn redo(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+                self.revert(owner_id, id, TodoAction::Redo).await
+            }
+            async fn descendants(&self, owner_id: i32, id: i32) -> Result<Vec<Todo>, RepositoryError> {
+                let todos = sqlx::query_as::<_, Todo>(DESCENDANTS_QUERY)
+                    .bind(id)
+                    .bind(owner_id)
+                    .fetch_all(&self.pool)
+                    .await?;
+                let todos = Self::attach_labels(&self.pool, todos).await?;
+
+                Ok(todos)
+            }
+            async fn search(
+                &self,
+                owner_id: i32,
+                query: TodoSearchQuery,
+            ) -> Result<Vec<TodoSearchHit>, RepositoryError> {
+                let hits = Self::search_hits(&self.pool, owner_id, &query).await?;
+                let (todos, scores): (Vec<Todo>, Vec<_>) = hits
+                    .into_iter()
+                    .map(|hit| (hit.todo, (hit.rank, hit.snippet)))
+                    .unzip();
+                let todos = Self::attach_labels(&self.pool, todos).await?;
+
+                Ok(TodoSearchHit::zip(todos, scores))
+            }
+            fn changes(&self) -> &TodoChanges {
+                &self.changes
+            }
+        }
+    };
+}
+
+sql_todo_repository!(TodoRepositoryForDB, Postgres, PgPool, PgConnection);
+sql_todo_repository!(
+    TodoRepositoryForSqlite,
+    Sqlite,
+    SqlitePool,
+    SqliteConnection
+);
+
+impl TodoRepositoryForDB {
+    // The function that finds a string in another, for the `q` filter.
+    const CONTAINS: &'static str = "strpos";
+
+    // Locks the row of `id` until the transaction ends, so a todo read after it
+    // as `before` is still what the following change replaces.
+    async fn lock_in(
+        conn: &mut PgConnection,
+        owner_id: i32,
+        id: i32,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                select id from todos where id=$1 and owner_id=$2 for update
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn search_hits(
+        pool: &PgPool,
+        owner_id: i32,
+        query: &TodoSearchQuery,
+    ) -> Result<Vec<TodoSearchHit>, RepositoryError> {
+        let hits = sqlx::query_as::<_, TodoSearchHit>(
+            r#"
+                select todos.*,
+                    ts_rank(search_vector, query)::float8 as rank,
+                    ts_headline(
+                        'english', text, query, 'StartSel=<mark>, StopSel=</mark>'
+                    ) as snippet
+                from todos, websearch_to_tsquery('english', $1) as query
+                where owner_id = $2 and deleted_at is null and search_vector @@ query
+                order by rank desc, id desc
+                limit $3
+            "#,
+        )
+        .bind(query.q.clone())
+        .bind(owner_id)
+        .bind(query.limit())
+        .fetch_all(pool)
+        .await?;
+
+        Ok(hits)
+    }
+}
+
+impl TodoRepositoryForSqlite {
+    const CONTAINS: &'static str = "instr";
+
+    // SQLite has no row locks. Writing to the row takes the write lock of the
+    // database instead, so a todo read after it as `before` is still what the
+    // following change replaces.
+    async fn lock_in(
+        conn: &mut SqliteConnection,
+        owner_id: i32,
+        id: i32,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                update todos set id=id where id=$1 and owner_id=$2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn search_hits(
+        pool: &SqlitePool,
+        owner_id: i32,
+        query: &TodoSearchQuery,
+    ) -> Result<Vec<TodoSearchHit>, RepositoryError> {
+        let Some(fts_query) = fts5_query(&query.q) else {
+            return Ok(vec![]);
+        };
+        let hits = sqlx::query_as::<_, TodoSearchHit>(
+            r#"
+                select todos.*,
+                    -bm25(todos_fts) as rank,
+                    snippet(todos_fts, 0, '<mark>', '</mark>', '…', 16) as snippet
+                from todos_fts
+                inner join todos on todos.id = todos_fts.rowid
+                where todos_fts match $1 and todos.owner_id = $2 and todos.deleted_at is null
+                order by rank desc, todos.id desc
+                limit $3
+            "#,
+        )
+        .bind(fts_query)
+        .bind(owner_id)
+        .bind(query.limit())
+        .fetch_all(pool)
+        .await?;
+
+        Ok(hits)
+    }
+}
+
+// `$1` and all of its ancestors, provided `$1` belongs to the owner `$2`.
+const ANCESTORS_QUERY: &str = r#"
+    with recursive ancestors(id, parent_id) as (
+        select id, parent_id from todos where id = $1 and owner_id = $2 and deleted_at is null
+        union all
+        select todos.id, todos.parent_id from todos
+        inner join ancestors on todos.id = ancestors.parent_id
+    )
+    select id from ancestors
+"#;
+
+const DESCENDANTS_QUERY: &str = r#"
+    with recursive descendants(id) as (
+        select id from todos where parent_id = $1 and owner_id = $2 and deleted_at is null
+        union all
+        select todos.id from todos
+        inner join descendants on todos.parent_id = descendants.id
+        where todos.deleted_at is null
+    )
+    select * from todos where id in (select id from descendants)
+    order by id asc
+"#;
+
+// Copies the completed todo `$1` into its next occurrence.
+const NEXT_OCCURRENCE_QUERY: &str = r#"
+    insert into todos (
+        owner_id, text, completed, priority, due_at, created_at, updated_at,
+        project_id, parent_id, auto_complete, recurrence
+    )
+    select owner_id, text, false, priority, $2, $3, $3, project_id, parent_id, auto_complete, $4
+    from todos where id = $1
+    returning id
+"#;
+
+const COPY_LABELS_QUERY: &str = r#"
+    insert into todo_labels (todo_id, label_id)
+    select $1, label_id from todo_labels where todo_id = $2
+"#;
+
+// Completes `$1` when it opted into `auto_complete` and all of its children are
+// done. Returns its own parent so the caller can keep rolling up.
+const ROLL_UP_QUERY: &str = r#"
+    update todos set completed = true, completed_at = $2, updated_at = $2, version = version + 1
+    where id = $1
+        and auto_complete
+        and not completed
+        and deleted_at is null
+        and exists (
+            select 1 from todos as child
+            where child.parent_id = $1 and child.deleted_at is null
+        )
+        and not exists (
+            select 1 from todos as child
+            where child.parent_id = $1 and child.deleted_at is null and not child.completed
+        )
+    returning parent_id
+"#;
+
+// Moves `$1` and everything below it into the trash, stamped with `$3`, provided
+// `$1` is at the version `$4` (if given).
+const TRASH_QUERY: &str = r#"
+    with recursive subtree(id) as (
+        select id from todos
+        where id = $1 and owner_id = $2 and deleted_at is null
+            and ($4 is null or version = $4)
+        union all
+        select todos.id from todos
+        inner join subtree on todos.parent_id = subtree.id
+        where todos.deleted_at is null
+    )
+    update todos set deleted_at = $3 where id in (select id from subtree)
+    returning *
+"#;
+
+// Takes `$1` and whatever below it was trashed at the same time `$3` back out of
+// the trash. Todos trashed on their own before stay there.
+const RESTORE_QUERY: &str = r#"
+    with recursive subtree(id) as (
+        select id from todos where id = $1 and owner_id = $2
+        union all
+        select todos.id from todos
+        inner join subtree on todos.parent_id = subtree.id
+    )
+    update todos set deleted_at = null
+    where id in (select id from subtree) and deleted_at = $3
+    returning *
+"#;
+
+const RECORD_QUERY: &str = r#"
+    insert into todo_events (todo_id, actor_id, action, before, after, created_at, reverts)
+    values ($1, $2, $3, $4, $5, $6, $7)
+"#;
+
+// Puts the todo `$12` back to a snapshot. It only matches while the todo still
+// has the `updated_at` `$13` and `deleted_at` `$14` it was checked with, so a
+// change committed in between is not overwritten.
+const REVERT_QUERY: &str = r#"
+    update todos set
+        text = $1,
+        completed = $2,
+        completed_at = $3,
+        priority = $4,
+        due_at = $5,
+        project_id = $6,
+        parent_id = $7,
+        auto_complete = $8,
+        recurrence = $9,
+        deleted_at = $10,
+        updated_at = $11,
+        version = version + 1
+    where id = $12 and updated_at = $13
+        and (deleted_at = $14 or (deleted_at is null and $14 is null))
+    returning *
+"#;
+
+// Translates the `websearch_to_tsquery` syntax (plain words, "quoted phrases",
+// `-excluded` words and `or`) into an FTS5 query. `None` when nothing is left to
+// match on.
+fn fts5_query(q: &str) -> Option<String> {
+    let mut terms = vec![];
+    let mut excluded = vec![];
+    let mut rest = q.trim();
+    while !rest.is_empty() {
+        let negated = rest.starts_with('-');
+        if negated {
+            rest = &rest[1..];
+        }
+        let (term, tail) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+        rest = tail.trim_start();
+        let words: Vec<&str> = term
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        if words.is_empty() {
+            continue;
+        }
+        let phrase = format!("\"{}\"", words.join(" "));
+        if negated {
+            excluded.push(phrase);
+        } else if !term.eq_ignore_ascii_case("or") {
+            terms.push(phrase);
+        } else if terms.last().is_some_and(|term| term != "OR") {
+            terms.push("OR".to_string());
+        }
+    }
+    if terms.last().is_some_and(|term| term == "OR") {
+        terms.pop();
+    }
+    if terms.is_empty() {
+        return None;
+    }
+    let mut query = terms.join(" ");
+    for phrase in excluded {
+        query = format!("{} NOT {}", query, phrase);
+    }
+    Some(query)
+}
+
+fn check_parent(id: Option<i32>, parent_id: i32, ancestors: &[i32]) -> Result<(), RepositoryError> {
+    if ancestors.is_empty() {
+        return Err(RepositoryError::Validation(format!(
+            "parent not found, id is {}",
+            parent_id
+        )));
+    }
+    if id.is_some_and(|id| ancestors.contains(&id)) {
+        return Err(RepositoryError::Validation(format!(
+            "parent would create a cycle, id is {}",
+            parent_id
+        )));
+    }
+    Ok(())
+}
+
+fn check_labels(labels: &[i32], found: &[i32]) -> Result<(), RepositoryError> {
+    match labels.iter().find(|id| !found.contains(id)) {
+        Some(id) => Err(RepositoryError::Validation(format!(
+            "label not found, id is {}",
+            id
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn assign_labels(mut todos: Vec<Todo>, rows: Vec<TodoLabelFromRow>) -> Vec<Todo> {
+    let mut labels: HashMap<i32, Vec<Label>> = HashMap::new();
+    for row in rows {
+        labels.entry(row.todo_id).or_default().push(Label {
+            id: row.id,
+            name: row.name,
+        });
+    }
+    for todo in todos.iter_mut() {
+        todo.labels = labels.remove(&todo.id).unwrap_or_default();
+    }
+    todos
+}
+
+// `strpos` is the name of the substring position function of the target database.
+fn push_filters<'args, DB>(
+    builder: &mut QueryBuilder<'args, DB>,
+    owner_id: i32,
+    query: &TodoQuery,
+    strpos: &str,
+) where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    bool: Encode<'args, DB> + Type<DB>,
+    String: Encode<'args, DB> + Type<DB>,
+    Priority: Encode<'args, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'args, DB> + Type<DB>,
+{
+    builder
+        .push(" where deleted_at is null and owner_id = ")
+        .push_bind(owner_id);
+    if let Some(completed) = query.completed {
+        builder.push(" and completed = ").push_bind(completed);
+    }
+    if let Some(q) = &query.q {
+        builder
+            .push(format!(" and {strpos}(lower(text), lower("))
+            .push_bind(q.clone())
+            .push(")) > 0");
+    }
+    if let Some(priority) = query.priority {
+        builder.push(" and priority = ").push_bind(priority);
+    }
+    if let Some(due_before) = query.due_before {
+        builder.push(" and due_at < ").push_bind(due_before);
+    }
+    if let Some(due_after) = query.due_after {
+        builder.push(" and due_at >= ").push_bind(due_after);
+    }
+    if let Some(project_id) = query.project_id {
+        builder.push(" and project_id = ").push_bind(project_id);
+    }
+    match query.overdue {
+        Some(true) => {
+            builder
+                .push(" and completed = false and due_at < ")
+                .push_bind(Utc::now());
+        }
+        Some(false) => {
+            builder
+                .push(" and (completed = true or due_at is null or due_at >= ")
+                .push_bind(Utc::now())
+                .push(")");
+        }
+        None => {}
+    }
+}
+
+fn push_order<'args, DB>(builder: &mut QueryBuilder<'args, DB>, query: &TodoQuery)
+where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    i64: Encode<'args, DB> + Type<DB>,
+{
+    let column = query.sort().column();
+    let order = query.order();
+    if let Some(cursor) = query.cursor {
+        builder
+            .push(format!(
+                " and ({column}, id) {} (select {column}, id from todos where id = ",
+                order.comparator()
+            ))
+            .push_bind(cursor)
+            .push(")");
+    }
+    builder
+        .push(format!(
+            " order by {column} {}, id {} limit ",
+            order.keyword(),
+            order.keyword()
+        ))
+        .push_bind(query.limit() + 1);
+}
+
+// Trims a result fetched with `limit + 1` rows down to the page and its cursor.
+fn paginate<T>(mut items: Vec<T>, limit: i64, id: fn(&T) -> i32) -> (Vec<T>, Option<i32>) {
+    if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        let next_cursor = items.last().map(id);
+        (items, next_cursor)
+    } else {
+        (items, None)
+    }
+}
+
+// Replays the history of a todo to find what `action` (undo or redo) is about:
+// the event it takes back or applies again, and the snapshot that gets the todo
+// there. Undos and redos pair up with changes like a stack, and a new change
+// drops whatever could have been redone.
+fn revert_target(
+    events: &[TodoEvent],
+    action: TodoAction,
+    current: &Todo,
+) -> Result<(i32, Todo), RepositoryError> {
+    let mut undo: Vec<&TodoEvent> = vec![];
+    let mut redo: Vec<&TodoEvent> = vec![];
+    for event in events {
+        match event.action {
+            TodoAction::Undo => redo.extend(undo.pop()),
+            TodoAction::Redo => undo.extend(redo.pop()),
+            _ => {
+                undo.push(event);
+                redo.clear();
+            }
+        }
+    }
+    let target = match action {
+        TodoAction::Redo => redo.last().ok_or_else(|| {
+            RepositoryError::Conflict(format!("nothing to redo, id is {}", current.id))
+        })?,
+        _ => undo
+            .last()
+            .filter(|event| {
+                matches!(
+                    event.action,
+                    TodoAction::Update | TodoAction::Delete | TodoAction::Restore
+                )
+            })
+            .ok_or_else(|| {
+                RepositoryError::Conflict(format!("nothing to undo, id is {}", current.id))
+            })?,
+    };
+
+    // The snapshots only apply to the todo as the last recorded change left it.
+    let fingerprint =
+        serde_json::to_value(current).map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+    let unchanged = events
+        .last()
+        .and_then(|event| event.after.as_ref())
+        .is_some_and(|after| {
+            ["updated_at", "deleted_at"]
+                .iter()
+                .all(|field| after.0[field] == fingerprint[field])
+        });
+    if !unchanged {
+        return Err(RepositoryError::Conflict(format!(
+            "todo was changed in the meantime, id is {}",
+            current.id
+        )));
+    }
+
+    let snapshot = match action {
+        TodoAction::Redo => &target.after,
+        _ => &target.before,
+    };
+    let snapshot = snapshot.as_ref().ok_or_else(|| {
+        RepositoryError::Unexpected(format!("event {} has no snapshot", target.id))
+    })?;
+    let todo = serde_json::from_value(snapshot.0.clone())
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+
+    Ok((target.id, todo))
+}
+
+fn push_event_filters<'args, DB>(
+    builder: &mut QueryBuilder<'args, DB>,
+    actor_id: i32,
+    query: &AuditQuery,
+) where
+    DB: Database,
+    i32: Encode<'args, DB> + Type<DB>,
+    i64: Encode<'args, DB> + Type<DB>,
+    TodoAction: Encode<'args, DB> + Type<DB>,
+    DateTime<Utc>: Encode<'args, DB> + Type<DB>,
+{
+    builder.push(" where actor_id = ").push_bind(actor_id);
+    if let Some(todo_id) = query.todo_id {
+        builder.push(" and todo_id = ").push_bind(todo_id);
+    }
+    if let Some(action) = query.action {
+        builder.push(" and action = ").push_bind(action);
+    }
+    if let Some(since) = query.since {
+        builder.push(" and created_at >= ").push_bind(since);
+    }
+    if let Some(until) = query.until {
+        builder.push(" and created_at < ").push_bind(until);
+    }
+    if let Some(cursor) = query.cursor {
+        builder.push(" and id < ").push_bind(cursor);
+    }
+    builder
+        .push(" order by id desc limit ")
+        .push_bind(query.limit() + 1);
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct Todo {
+    id: i32,
+    text: String,
+    completed: bool,
+    priority: Priority,
+    due_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    /// Set while the todo is in the trash.
+    deleted_at: Option<DateTime<Utc>>,
+    /// Goes up with every update, see `ETag` on `GET /todos/:id`. Missing from
+    /// history snapshots recorded before it existed.
+    #[serde(default)]
+    version: i32,
+    project_id: Option<i32>,
+    parent_id: Option<i32>,
+    auto_complete: bool,
+    #[schema(value_type = Option<String>, example = "FREQ=WEEKLY;BYDAY=SA")]
+    recurrence: Option<Recurrence>,
+    #[sqlx(skip)]
+    labels: Vec<Label>,
+    /// Only filled in when the subtree was asked for with `?expand=children`.
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<Todo>>,
+}
+
+impl Todo {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn completed(&self) -> bool {
+        self.completed
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn due_at(&self) -> Option<DateTime<Utc>> {
+        self.due_at
+    }
+
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at
+    }
+
+    pub fn project_id(&self) -> Option<i32> {
+        self.project_id
+    }
+
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    pub fn auto_complete(&self) -> bool {
+        self.auto_complete
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    pub fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Nests `descendants` (as returned by `TodoRepository::descendants`) below
+    /// this todo.
+    pub fn with_subtree(mut self, descendants: Vec<Todo>) -> Self {
+        let mut by_parent: HashMap<i32, Vec<Todo>> = HashMap::new();
+        for todo in descendants {
+            if let Some(parent_id) = todo.parent_id {
+                by_parent.entry(parent_id).or_default().push(todo);
+            }
+        }
+        self.attach_children(&mut by_parent);
+        self
+    }
+
+    // `now` is the timestamp the update completed the todo with; a todo that was
+    // already done before does not repeat again.
+    fn next_occurrence(&self, now: DateTime<Utc>) -> Option<(DateTime<Utc>, Recurrence)> {
+        if self.completed_at != Some(now) {
+            return None;
+        }
+        self.recurrence
+            .as_ref()?
+            .next_occurrence(self.due_at.unwrap_or(now))
+    }
+
+    fn attach_children(&mut self, by_parent: &mut HashMap<i32, Vec<Todo>>) {
+        let mut children = by_parent.remove(&self.id).unwrap_or_default();
+        for child in children.iter_mut() {
+            child.attach_children(by_parent);
+        }
+        self.children = Some(children);
+    }
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Hash, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "priority", rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, FromRow, ToSchema)]
+pub struct TodoSearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    todo: Todo,
+    /// Relevance, higher is better. Only comparable within one result list.
+    rank: f64,
+    /// Matching part of the text, with matches wrapped in `<mark>` tags.
+    snippet: String,
+}
+
+impl Priority {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            "high" => Ok(Priority::High),
+            "urgent" => Ok(Priority::Urgent),
+            _ => Err(format!(
+                "unknown priority {}, expected low, normal, high or urgent",
+                s
+            )),
+        }
+    }
+}
+
+impl TodoSearchHit {
+    fn zip(todos: Vec<Todo>, scores: Vec<(f64, String)>) -> Vec<TodoSearchHit> {
+        todos
+            .into_iter()
+            .zip(scores)
+            .map(|(todo, (rank, snippet))| TodoSearchHit {
+                todo,
+                rank,
+                snippet,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TodoSearchQuery {
+    /// Web search syntax: words, "quoted phrases", `-excluded` and `or`.
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[param(min_length = 1)]
+    q: String,
+    #[validate(range(min = 1, max = 100, message = "Out of range"))]
+    #[param(minimum = 1, maximum = 100)]
+    limit: Option<i64>,
+}
+
+impl TodoSearchQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct TodoPage {
+    items: Vec<Todo>,
+    next_cursor: Option<i32>,
+    total: i64,
+}
+
+impl TodoPage {
+    pub fn items(&self) -> &[Todo] {
+        &self.items
+    }
+
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+
+    pub fn next_cursor(&self) -> Option<i32> {
+        self.next_cursor
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoSort {
+    #[default]
+    Id,
+    Text,
+    CreatedAt,
+}
+
+impl TodoSort {
+    fn column(&self) -> &'static str {
+        match self {
+            TodoSort::Id => "id",
+            TodoSort::Text => "text",
+            TodoSort::CreatedAt => "created_at",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortOrder {
+    fn keyword(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+
+    fn comparator(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TodoQuery {
+    completed: Option<bool>,
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[param(min_length = 1)]
+    q: Option<String>,
+    sort: Option<TodoSort>,
+    order: Option<SortOrder>,
+    #[validate(range(min = 1, max = 100, message = "Out of range"))]
+    #[param(minimum = 1, maximum = 100)]
+    limit: Option<i64>,
+    cursor: Option<i32>,
+    priority: Option<Priority>,
+    due_before: Option<DateTime<Utc>>,
+    due_after: Option<DateTime<Utc>>,
+    overdue: Option<bool>,
+    project_id: Option<i32>,
+}
+
+impl TodoQuery {
+    /// Every todo, oldest first, in pages of the largest size from `cursor` on.
+    pub fn by_id(cursor: Option<i32>) -> Self {
+        Self {
+            sort: Some(TodoSort::Id),
+            order: Some(SortOrder::Asc),
+            limit: Some(MAX_LIMIT),
+            cursor,
+            ..Self::default()
+        }
+    }
+
+    pub fn in_project(self, project_id: i32) -> Self {
+        Self {
+            project_id: Some(project_id),
+            ..self
+        }
+    }
+
+    fn sort(&self) -> TodoSort {
+        self.sort.unwrap_or_default()
+    }
+
+    fn order(&self) -> SortOrder {
+        self.order.unwrap_or_default()
+    }
+
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+struct TodoLabelFromRow {
+    todo_id: i32,
+    id: i32,
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct CreateTodo {
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    #[schema(min_length = 1, max_length = 100)]
+    text: String,
+    #[serde(default)]
+    labels: Vec<i32>,
+    priority: Option<Priority>,
+    due_at: Option<DateTime<Utc>>,
+    project_id: Option<i32>,
+    parent_id: Option<i32>,
+    /// Complete this todo automatically once all of its children are done.
+    auto_complete: Option<bool>,
+    /// RRULE subset, e.g. `FREQ=WEEKLY;BYDAY=SA`. Completing the todo creates the
+    /// next occurrence.
+    #[schema(value_type = Option<String>, example = "FREQ=WEEKLY;BYDAY=SA")]
+    recurrence: Option<Recurrence>,
+    /// Set by imports and sync to create a todo that was completed elsewhere.
+    #[serde(skip)]
+    completed_at: Option<DateTime<Utc>>,
+    /// Set by imports to put the todo below the one created by an earlier
+    /// operation of the same batch. Only `batch` reads it.
+    #[serde(skip)]
+    parent_operation: Option<usize>,
+}
+
+impl CreateTodo {
+    pub fn new(text: String, labels: Vec<i32>) -> Self {
+        Self {
+            text,
+            labels,
+            priority: None,
+            due_at: None,
+            project_id: None,
+            parent_id: None,
+            auto_complete: None,
+            recurrence: None,
+            completed_at: None,
+            parent_operation: None,
+        }
+    }
+
+    pub fn with_priority(self, priority: Priority) -> Self {
+        Self {
+            priority: Some(priority),
+            ..self
+        }
+    }
+
+    pub fn with_due_at(self, due_at: DateTime<Utc>) -> Self {
+        Self {
+            due_at: Some(due_at),
+            ..self
+        }
+    }
+
+    pub fn in_project(self, project_id: i32) -> Self {
+        Self {
+            project_id: Some(project_id),
+            ..self
+        }
+    }
+
+    pub fn with_parent(self, parent_id: i32) -> Self {
+        Self {
+            parent_id: Some(parent_id),
+            ..self
+        }
+    }
+
+    /// Puts the todo below the one created by operation `index` of the same
+    /// batch, whose id is not known yet.
+    pub fn below_operation(self, index: usize) -> Self {
+        Self {
+            parent_operation: Some(index),
+            ..self
+        }
+    }
+
+    pub fn with_auto_complete(self, auto_complete: bool) -> Self {
+        Self {
+            auto_complete: Some(auto_complete),
+            ..self
+        }
+    }
+
+    pub fn project_id(&self) -> Option<i32> {
+        self.project_id
+    }
+
+    pub fn labels(&self) -> &[i32] {
+        &self.labels
+    }
+
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    /// Creates the todo as completed at `at`. Unlike completing it afterwards,
+    /// this does not create the next occurrence of a recurring todo.
+    pub fn completed_at(self, at: DateTime<Utc>) -> Self {
+        Self {
+            completed_at: Some(at),
+            ..self
+        }
+    }
+
+    pub fn with_recurrence(self, recurrence: Recurrence) -> Self {
+        Self {
+            recurrence: Some(recurrence),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, ToSchema)]
+pub struct UpdateTodo {
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    #[schema(min_length = 1, max_length = 100)]
+    text: Option<String>,
+    completed: Option<bool>,
+    labels: Option<Vec<i32>>,
+    priority: Option<Priority>,
+    /// `null` clears the due date, a missing field leaves it untouched.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    due_at: Option<Option<DateTime<Utc>>>,
+    /// `null` moves the todo to the inbox.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    project_id: Option<Option<i32>>,
+    /// `null` turns the todo back into a top-level one.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    parent_id: Option<Option<i32>>,
+    auto_complete: Option<bool>,
+    /// `null` stops the todo from repeating.
+    #[serde(default, deserialize_with = "deserialize_some")]
+    #[schema(value_type = Option<String>, example = "FREQ=WEEKLY;BYDAY=SA")]
+    recurrence: Option<Option<Recurrence>>,
+}
+
+impl UpdateTodo {
+    pub fn complete() -> Self {
+        Self {
+            completed: Some(true),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_text(self, text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..self
+        }
+    }
+
+    pub fn with_completed(self, completed: bool) -> Self {
+        Self {
+            completed: Some(completed),
+            ..self
+        }
+    }
+
+    pub fn with_priority(self, priority: Priority) -> Self {
+        Self {
+            priority: Some(priority),
+            ..self
+        }
+    }
+
+    /// `None` clears the due date.
+    pub fn with_due_at(self, due_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            due_at: Some(due_at),
+            ..self
+        }
+    }
+
+    /// `None` stops the todo from repeating.
+    pub fn with_recurrence(self, recurrence: Option<Recurrence>) -> Self {
+        Self {
+            recurrence: Some(recurrence),
+            ..self
+        }
+    }
+}
+
+/// One step of `POST /todos/batch`, e.g. `{"op": "update", "id": 1, "todo": {...}}`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum TodoOperation {
+    Create { todo: CreateTodo },
+    Update { id: i32, todo: UpdateTodo },
+    Delete { id: i32 },
+}
+
+impl TodoOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoOperation::Create { .. } => "create",
+            TodoOperation::Update { .. } => "update",
+            TodoOperation::Delete { .. } => "delete",
+        }
+    }
+
+    // Turns a parent given as an earlier operation of the batch into its id.
+    fn resolve(self, results: &[TodoOperationResult]) -> Result<Self, RepositoryError> {
+        let TodoOperation::Create { todo } = self else {
+            return Ok(self);
+        };
+        let Some(index) = todo.parent_operation else {
+            return Ok(TodoOperation::Create { todo });
+        };
+        match results.get(index) {
+            Some(TodoOperationResult::Create { todo: parent }) => Ok(TodoOperation::Create {
+                todo: CreateTodo {
+                    parent_id: Some(parent.id),
+                    parent_operation: None,
+                    ..todo
+                },
+            }),
+            _ => Err(RepositoryError::Validation(format!(
+                "parent is not created before, operation is {}",
+                index
+            ))),
+        }
+    }
+}
+
+impl Validate for TodoOperation {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            TodoOperation::Create { todo } => todo.validate(),
+            TodoOperation::Update { todo, .. } => todo.validate(),
+            TodoOperation::Delete { .. } => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum TodoOperationResult {
+    Create { todo: Todo },
+    Update { todo: Todo },
+    Delete { id: i32 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct TodoBatch {
+    #[validate(length(min = 1, max = 100, message = "Out of range"))]
+    #[validate]
+    #[schema(min_items = 1, max_items = 100)]
+    pub operations: Vec<TodoOperation>,
+}
+
+/// A batch that was rolled back.
+#[derive(Debug, Error)]
+#[error("{error}")]
+pub struct BatchError {
+    /// Position of the operation that failed, `None` when the transaction itself
+    /// could not be opened or committed.
+    pub index: Option<usize>,
+    pub error: RepositoryError,
+}
+
+impl BatchError {
+    fn at(index: usize, error: RepositoryError) -> Self {
+        Self {
+            index: Some(index),
+            error,
+        }
+    }
+}
+
+impl From<RepositoryError> for BatchError {
+    fn from(error: RepositoryError) -> Self {
+        Self { index: None, error }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "todo_action", rename_all = "lowercase")]
+pub enum TodoAction {
+    Create,
+    Update,
+    /// Moved to the trash.
+    Delete,
+    Restore,
+    Purge,
+    Undo,
+    Redo,
+}
+
+/// One entry of the append-only history of a todo.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, FromRow, ToSchema)]
+pub struct TodoEvent {
+    id: i32,
+    todo_id: i32,
+    actor_id: i32,
+    action: TodoAction,
+    /// The todo as it was before the change, `null` for `create`.
+    #[schema(value_type = Option<Object>)]
+    before: Option<Json<Value>>,
+    /// The todo after the change, `null` for `purge`.
+    #[schema(value_type = Option<Object>)]
+    after: Option<Json<Value>>,
+    created_at: DateTime<Utc>,
+    /// For `undo` and `redo`, the event that was taken back or applied again.
+    reverts: Option<i32>,
+}
+
+impl TodoEvent {
+    pub fn action(&self) -> TodoAction {
+        self.action
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct TodoEventPage {
+    items: Vec<TodoEvent>,
+    next_cursor: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    todo_id: Option<i32>,
+    action: Option<TodoAction>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    #[validate(range(min = 1, max = 100, message = "Out of range"))]
+    #[param(minimum = 1, maximum = 100)]
+    limit: Option<i64>,
+    cursor: Option<i32>,
+}
+
+impl AuditQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoExpand {
+    Children,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FindTodoQuery {
+    pub expand: Option<TodoExpand>,
+}
+
+fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::test_utils::TodoRepositoryForMemory;
+    use super::*;
+    use crate::repositories::{
+        changes::ChangeKind,
+        label::{CreateLabel, LabelRepository, LabelRepositoryForDB, LabelRepositoryForSqlite},
+        test_utils::{connect_postgres, connect_sqlite, find_or_create_user},
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+    };
+    use chrono::{Duration, SubsecRound};
+
+    async fn find_or_create_label(pool: &PgPool, owner_id: i32, name: &str) -> Label {
+        let label_repository = LabelRepositoryForDB::new(pool.clone());
+        match label_repository
+            .create(owner_id, CreateLabel::new(name.to_string()))
+            .await
+        {
+            Ok(label) => label,
+            Err(_) => label_repository
+                .all(owner_id)
+                .await
+                .expect("[label all] returned error")
+                .into_iter()
+                .find(|label| label.name == name)
+                .expect("[label all] label not found"),
+        }
+    }
+
+    // Behaviour every TodoRepository implementation has to agree on. `label` must
+    // already be known to the repository under test, and both owners must exist.
+    async fn todo_repository_contract<T: TodoRepository>(
+        repository: T,
+        label: Label,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        // Unique per run, so rows left behind by a failed run on a shared database
+        // do not turn up in the searches below.
+        let run = Utc::now().timestamp_nanos_opt().unwrap();
+        let text = format!("[contract] todo {}", run);
+        let updated_text = format!("[contract] updated {}", run);
+        let due_at = Utc::now().trunc_subsecs(0) - Duration::hours(1);
+
+        let created = repository
+            .create(owner_id, CreateTodo::new(text.clone(), vec![label.id]))
+            .await
+            .expect("[create] returned error");
+        assert_eq!(created.text, text);
+        assert!(!created.completed);
+        assert_eq!(created.priority, Priority::Normal);
+        assert_eq!(created.due_at, None);
+        assert_eq!(created.completed_at, None);
+        assert_eq!(created.labels, vec![label.clone()]);
+
+        let todo = repository
+            .find(owner_id, created.id)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(created, todo);
+
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some(text.clone()),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.items, vec![created.clone()]);
+        assert_eq!(page.total, 1);
+
+        let res = repository.find(other_owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(id)) if id == created.id));
+        let page = repository
+            .all(
+                other_owner_id,
+                TodoQuery {
+                    q: Some(text.clone()),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert!(page.items.is_empty());
+        let res = repository
+            .update(other_owner_id, created.id, UpdateTodo::default(), None)
+            .await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(id)) if id == created.id));
+        let res = repository.delete(other_owner_id, created.id, None).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(id)) if id == created.id));
+
+        let todo = repository
+            .update(
+                owner_id,
+                created.id,
+                UpdateTodo {
+                    completed: Some(true),
+                    priority: Some(Priority::High),
+                    due_at: Some(Some(due_at)),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        assert_eq!(todo.text, text);
+        assert!(todo.completed);
+        assert!(todo.completed_at.is_some());
+        assert!(todo.updated_at >= created.updated_at);
+        assert_eq!(todo.priority, Priority::High);
+        assert_eq!(todo.due_at, Some(due_at));
+        assert_eq!(todo.labels, vec![label.clone()]);
+
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some(text.clone()),
+                    priority: Some(Priority::High),
+                    due_before: Some(due_at + Duration::days(1)),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.items, vec![todo.clone()]);
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some(text.clone()),
+                    overdue: Some(true),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert!(page.items.is_empty());
+
+        let todo = repository
+            .update(
+                owner_id,
+                created.id,
+                UpdateTodo {
+                    text: Some(updated_text.clone()),
+                    labels: Some(vec![]),
+                    due_at: Some(None),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        assert_eq!(todo.text, updated_text);
+        assert!(todo.completed);
+        assert!(todo.labels.is_empty());
+        assert_eq!(todo.priority, Priority::High);
+        assert_eq!(todo.due_at, None);
+
+        let todo = repository
+            .update(
+                owner_id,
+                created.id,
+                UpdateTodo {
+                    completed: Some(false),
+                    due_at: Some(Some(due_at)),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        assert!(!todo.completed);
+        assert_eq!(todo.completed_at, None);
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some(updated_text.clone()),
+                    overdue: Some(true),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.items, vec![todo]);
+
+        repository
+            .delete(owner_id, created.id, None)
+            .await
+            .expect("[delete] returned error");
+
+        let res = repository.find(owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(id)) if id == created.id));
+        let res = repository
+            .update(
+                owner_id,
+                created.id,
+                UpdateTodo {
+                    completed: Some(false),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(id)) if id == created.id));
+        let res = repository.delete(owner_id, created.id, None).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(id)) if id == created.id));
+    }
+
+    #[tokio::test]
+    async fn todo_repository_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "contract@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "contract-other@example.com").await;
+        let label = find_or_create_label(&pool, owner_id, "[contract] label").await;
+        todo_repository_contract(
+            TodoRepositoryForDB::new(pool),
+            label,
+            owner_id,
+            other_owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn todo_repository_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "contract@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "contract-other@example.com").await;
+        let label = LabelRepositoryForSqlite::new(pool.clone())
+            .create(owner_id, CreateLabel::new("[contract] label".to_string()))
+            .await
+            .expect("[label create] returned error");
+        todo_repository_contract(
+            TodoRepositoryForSqlite::new(pool),
+            label,
+            owner_id,
+            other_owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn todo_repository_contract_for_memory() {
+        let label = Label::new(1, "[contract] label".to_string());
+        todo_repository_contract(
+            TodoRepositoryForMemory::new(vec![label.clone()]),
+            label,
+            1,
+            2,
+        )
+        .await;
+    }
+
+    async fn todo_hierarchy_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        let create = |text: &str| CreateTodo::new(format!("[hierarchy] {}", text), vec![]);
+        let root = repository
+            .create(owner_id, create("root"))
+            .await
+            .expect("[create] returned error");
+        let root = repository
+            .update(
+                owner_id,
+                root.id,
+                UpdateTodo {
+                    auto_complete: Some(true),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        let first = repository
+            .create(owner_id, create("first").below(root.id, true))
+            .await
+            .expect("[create] returned error");
+        let second = repository
+            .create(owner_id, create("second").below(root.id, false))
+            .await
+            .expect("[create] returned error");
+        let leaf = repository
+            .create(owner_id, create("leaf").below(first.id, false))
+            .await
+            .expect("[create] returned error");
+
+        let descendants = repository
+            .descendants(owner_id, root.id)
+            .await
+            .expect("[descendants] returned error");
+        let ids: Vec<i32> = descendants.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![first.id, second.id, leaf.id]);
+        let tree = root.clone().with_subtree(descendants);
+        let children = tree.children.unwrap();
+        assert_eq!(children.len(), 2);
+        let grandchildren = children[0].children.clone().unwrap();
+        assert_eq!(grandchildren, vec![leaf.clone().with_subtree(vec![])]);
+        assert_eq!(children[1].children, Some(vec![]));
+        let res = repository.descendants(other_owner_id, root.id).await;
+        assert!(res.is_ok_and(|todos| todos.is_empty()));
+
+        let res = repository
+            .update(
+                owner_id,
+                root.id,
+                UpdateTodo::move_below(Some(leaf.id)),
+                None,
+            )
+            .await;
+        assert!(matches!(res, Err(RepositoryError::Validation(_))));
+        let res = repository
+            .update(
+                owner_id,
+                first.id,
+                UpdateTodo::move_below(Some(first.id)),
+                None,
+            )
+            .await;
+        assert!(matches!(res, Err(RepositoryError::Validation(_))));
+        let res = repository
+            .create(other_owner_id, create("intruder").below(root.id, false))
+            .await;
+        assert!(matches!(res, Err(RepositoryError::Validation(_))));
+
+        let done = UpdateTodo {
+            completed: Some(true),
+            ..UpdateTodo::default()
+        };
+        repository
+            .update(owner_id, leaf.id, done.clone(), None)
+            .await
+            .expect("[update] returned error");
+        let first = repository
+            .find(owner_id, first.id)
+            .await
+            .expect("[find] returned error");
+        assert!(first.completed);
+        assert!(first.completed_at.is_some());
+        let root = repository
+            .find(owner_id, root.id)
+            .await
+            .expect("[find] returned error");
+        assert!(!root.completed);
+        repository
+            .update(owner_id, second.id, done, None)
+            .await
+            .expect("[update] returned error");
+        let root = repository
+            .find(owner_id, root.id)
+            .await
+            .expect("[find] returned error");
+        assert!(root.completed);
+
+        repository
+            .delete(owner_id, root.id, None)
+            .await
+            .expect("[delete] returned error");
+        let res = repository.find(owner_id, leaf.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let run = Utc::now().timestamp_nanos_opt().unwrap();
+        let finished = repository
+            .create(owner_id, create(&format!("finished {}", run)))
+            .await
+            .expect("[create] returned error");
+        repository
+            .create(owner_id, create("still open").below(finished.id, false))
+            .await
+            .expect("[create] returned error");
+        repository
+            .update(owner_id, finished.id, UpdateTodo::complete(), None)
+            .await
+            .expect("[update] returned error");
+        let cleared = repository
+            .clear_completed(
+                owner_id,
+                TodoQuery {
+                    q: Some(format!("finished {}", run)),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[clear_completed] returned error");
+        assert_eq!(cleared, 2, "todos below a cleared one are counted too");
+    }
+
+    async fn todo_recurrence_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        label: Label,
+    ) {
+        let text = "[recurrence] water plants";
+        let due_at = Utc::now().trunc_subsecs(0) + Duration::days(1);
+        let created = repository
+            .create(
+                owner_id,
+                CreateTodo {
+                    due_at: Some(due_at),
+                    recurrence: Some("FREQ=WEEKLY;COUNT=2".parse().unwrap()),
+                    ..CreateTodo::new(text.to_string(), vec![label.id])
+                },
+            )
+            .await
+            .expect("[create] returned error");
+        let query = TodoQuery {
+            q: Some(text.to_string()),
+            sort: Some(TodoSort::Id),
+            order: Some(SortOrder::Asc),
+            ..TodoQuery::default()
+        };
+        let done = UpdateTodo {
+            completed: Some(true),
+            ..UpdateTodo::default()
+        };
+
+        repository
+            .update(owner_id, created.id, done.clone(), None)
+            .await
+            .expect("[update] returned error");
+        let page = repository
+            .all(owner_id, query.clone())
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.total, 2);
+        let next = page.items[1].clone();
+        assert!(!next.completed);
+        assert_eq!(next.due_at, Some(due_at + Duration::weeks(1)));
+        assert_eq!(
+            next.recurrence.as_ref().unwrap().to_string(),
+            "FREQ=WEEKLY;COUNT=1"
+        );
+        assert_eq!(next.labels, vec![label]);
+
+        // Completing an already completed todo does not repeat it again.
+        repository
+            .update(owner_id, created.id, done.clone(), None)
+            .await
+            .expect("[update] returned error");
+        repository
+            .update(owner_id, next.id, done, None)
+            .await
+            .expect("[update] returned error");
+        let page = repository
+            .all(owner_id, query)
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.total, 2);
+
+        for todo in page.items {
+            repository
+                .delete(owner_id, todo.id, None)
+                .await
+                .expect("[delete] returned error");
+        }
+    }
+
+    #[tokio::test]
+    async fn todo_recurrence_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "recurrence@example.com").await;
+        let label = find_or_create_label(&pool, owner_id, "[recurrence] label").await;
+        todo_recurrence_contract(TodoRepositoryForDB::new(pool), owner_id, label).await;
+    }
+
+    #[tokio::test]
+    async fn todo_recurrence_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "recurrence@example.com").await;
+        let label = LabelRepositoryForSqlite::new(pool.clone())
+            .create(owner_id, CreateLabel::new("[recurrence] label".to_string()))
+            .await
+            .expect("[label create] returned error");
+        todo_recurrence_contract(TodoRepositoryForSqlite::new(pool), owner_id, label).await;
+    }
+
+    #[tokio::test]
+    async fn todo_recurrence_contract_for_memory() {
+        let label = Label::new(1, "[recurrence] label".to_string());
+        todo_recurrence_contract(TodoRepositoryForMemory::new(vec![label.clone()]), 1, label).await;
+    }
+
+    async fn todo_search_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        let mut created = vec![];
+        for text in ["Buy oat milk", "Buy bread", "Walk the dog"] {
+            let todo = repository
+                .create(owner_id, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("[create] returned error");
+            created.push(todo);
+        }
+        let search = |q: &str| TodoSearchQuery {
+            q: q.to_string(),
+            limit: None,
+        };
+
+        let hits = repository
+            .search(owner_id, search("milk"))
+            .await
+            .expect("[search] returned error");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].todo, created[0]);
+        assert!(hits[0].snippet.contains("<mark>milk</mark>"));
+        assert!(hits[0].rank > 0.0);
+
+        let hits = repository
+            .search(owner_id, search("buy -bread"))
+            .await
+            .expect("[search] returned error");
+        let ids: Vec<i32> = hits.iter().map(|hit| hit.todo.id).collect();
+        assert_eq!(ids, vec![created[0].id]);
+
+        let hits = repository
+            .search(owner_id, search("dog or bread"))
+            .await
+            .expect("[search] returned error");
+        assert_eq!(hits.len(), 2);
+
+        let hits = repository
+            .search(other_owner_id, search("milk"))
+            .await
+            .expect("[search] returned error");
+        assert!(hits.is_empty());
+
+        for todo in created {
+            repository
+                .delete(owner_id, todo.id, None)
+                .await
+                .expect("[delete] returned error");
+        }
+    }
+
+    #[tokio::test]
+    async fn todo_search_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "search@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "search-other@example.com").await;
+        todo_search_contract(TodoRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_search_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "search@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "search-other@example.com").await;
+        todo_search_contract(TodoRepositoryForSqlite::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_search_contract_for_memory() {
+        todo_search_contract(TodoRepositoryForMemory::new(vec![]), 1, 2).await;
+    }
+
+    async fn todo_batch_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        let filter = TodoQuery {
+            q: Some("[batch]".to_string()),
+            ..TodoQuery::default()
+        };
+        let first = repository
+            .create(
+                owner_id,
+                CreateTodo::new("[batch] first".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+
+        let results = repository
+            .batch(
+                owner_id,
+                vec![
+                    TodoOperation::Create {
+                        todo: CreateTodo::new("[batch] second".to_string(), vec![]),
+                    },
+                    TodoOperation::Update {
+                        id: first.id,
+                        todo: UpdateTodo {
+                            text: Some("[batch] first, renamed".to_string()),
+                            ..UpdateTodo::default()
+                        },
+                    },
+                ],
+            )
+            .await
+            .expect("[batch] returned error");
+        let second = match &results[..] {
+            [TodoOperationResult::Create { todo: second }, TodoOperationResult::Update { todo: renamed }] =>
+            {
+                assert_eq!(second.text, "[batch] second");
+                assert_eq!(renamed.text, "[batch] first, renamed");
+                second.clone()
+            }
+            _ => panic!("[batch] unexpected results: {:?}", results),
+        };
+
+        let res = repository
+            .batch(
+                owner_id,
+                vec![
+                    TodoOperation::Delete { id: first.id },
+                    TodoOperation::Create {
+                        todo: CreateTodo::new("[batch] never".to_string(), vec![]),
+                    },
+                    TodoOperation::Delete { id: i32::MAX },
+                ],
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(BatchError {
+                index: Some(2),
+                error: RepositoryError::NotFound(id),
+            }) if id == i32::MAX
+        ));
+        let page = repository
+            .all(owner_id, filter.clone())
+            .await
+            .expect("[all] returned error");
+        assert_eq!(
+            page.total, 2,
+            "a failed batch must not leave changes behind"
+        );
+
+        let res = repository
+            .batch(
+                other_owner_id,
+                vec![TodoOperation::Delete { id: second.id }],
+            )
+            .await;
+        assert!(matches!(
+            res,
+            Err(BatchError {
+                index: Some(0),
+                error: RepositoryError::NotFound(_),
+            })
+        ));
+
+        repository
+            .update(owner_id, first.id, UpdateTodo::complete(), None)
+            .await
+            .expect("[update] returned error");
+        let completed = repository
+            .complete_all(owner_id, filter.clone())
+            .await
+            .expect("[complete_all] returned error");
+        assert_eq!(completed, 1);
+        let todo = repository
+            .find(owner_id, second.id)
+            .await
+            .expect("[find] returned error");
+        assert!(todo.completed);
+        assert!(todo.completed_at.is_some());
+
+        let deleted = repository
+            .clear_completed(other_owner_id, filter.clone())
+            .await
+            .expect("[clear_completed] returned error");
+        assert_eq!(deleted, 0);
+        let deleted = repository
+            .clear_completed(owner_id, filter.clone())
+            .await
+            .expect("[clear_completed] returned error");
+        assert_eq!(deleted, 2);
+        let page = repository
+            .all(owner_id, filter)
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.total, 0);
+    }
+
+    #[tokio::test]
+    async fn todo_batch_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "batch@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "batch-other@example.com").await;
+        todo_batch_contract(TodoRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_batch_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "batch@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "batch-other@example.com").await;
+        todo_batch_contract(TodoRepositoryForSqlite::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_batch_contract_for_memory() {
+        todo_batch_contract(TodoRepositoryForMemory::new(vec![]), 1, 2).await;
+    }
+
+    async fn todo_trash_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        let create = |text: &str| CreateTodo::new(text.to_string(), vec![]);
+        let parent = repository
+            .create(owner_id, create("[trash] parent"))
+            .await
+            .expect("[create] returned error");
+        let child = repository
+            .create(owner_id, create("[trash] child").below(parent.id, false))
+            .await
+            .expect("[create] returned error");
+        let single = repository
+            .create(owner_id, create("[trash] single"))
+            .await
+            .expect("[create] returned error");
+
+        repository
+            .delete(owner_id, parent.id, None)
+            .await
+            .expect("[delete] returned error");
+        let res = repository.find(owner_id, child.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some("[trash]".to_string()),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.items, vec![single.clone()]);
+        let trash: Vec<i32> = repository
+            .trash(owner_id)
+            .await
+            .expect("[trash] returned error")
+            .iter()
+            .map(|todo| todo.id)
+            .collect();
+        assert!(trash.contains(&parent.id) && trash.contains(&child.id));
+        assert!(!trash.contains(&single.id));
+        let res = repository.delete(owner_id, parent.id, None).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let res = repository.restore(owner_id, child.id).await;
+        assert!(matches!(res, Err(RepositoryError::Conflict(_))));
+        let res = repository.restore(other_owner_id, parent.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let restored = repository
+            .restore(owner_id, parent.id)
+            .await
+            .expect("[restore] returned error");
+        assert_eq!(restored, parent);
+        let todo = repository
+            .find(owner_id, child.id)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(todo, child);
+
+        let res = repository.purge(owner_id, single.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        for todo in [&single, &parent] {
+            repository
+                .delete(owner_id, todo.id, None)
+                .await
+                .expect("[delete] returned error");
+        }
+        let res = repository.purge(other_owner_id, single.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let purged = repository
+            .purge_expired(Utc::now() - Duration::days(1))
+            .await
+            .expect("[purge_expired] returned error");
+        assert_eq!(purged, 0);
+        for todo in [&single, &parent] {
+            repository
+                .purge(owner_id, todo.id)
+                .await
+                .expect("[purge] returned error");
+        }
+        let res = repository.restore(owner_id, child.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn todo_trash_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "trash@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "trash-other@example.com").await;
+        todo_trash_contract(TodoRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_trash_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "trash@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "trash-other@example.com").await;
+        todo_trash_contract(TodoRepositoryForSqlite::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_trash_contract_for_memory() {
+        todo_trash_contract(TodoRepositoryForMemory::new(vec![]), 1, 2).await;
+    }
+
+    async fn todo_history_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        let since = Utc::now();
+        let todo = repository
+            .create(
+                owner_id,
+                CreateTodo::new("[history] draft".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+        repository
+            .update(
+                owner_id,
+                todo.id,
+                UpdateTodo {
+                    text: Some("[history] final".to_string()),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        repository
+            .delete(owner_id, todo.id, None)
+            .await
+            .expect("[delete] returned error");
+        repository
+            .restore(owner_id, todo.id)
+            .await
+            .expect("[restore] returned error");
+        repository
+            .delete(owner_id, todo.id, None)
+            .await
+            .expect("[delete] returned error");
+        repository
+            .purge(owner_id, todo.id)
+            .await
+            .expect("[purge] returned error");
+
+        let events = repository
+            .history(owner_id, todo.id)
+            .await
+            .expect("[history] returned error");
+        let actions: Vec<TodoAction> = events.iter().map(|event| event.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                TodoAction::Create,
+                TodoAction::Update,
+                TodoAction::Delete,
+                TodoAction::Restore,
+                TodoAction::Delete,
+                TodoAction::Purge,
+            ]
+        );
+        assert!(events.iter().all(|event| event.actor_id == owner_id));
+        assert_eq!(events[0].before, None);
+        let text = |snapshot: &Option<Json<Value>>| snapshot.as_ref().unwrap()["text"].clone();
+        assert_eq!(text(&events[0].after), "[history] draft");
+        assert_eq!(text(&events[1].before), "[history] draft");
+        assert_eq!(text(&events[1].after), "[history] final");
+        assert!(events[2].after.as_ref().unwrap()["deleted_at"].is_string());
+        assert_eq!(events[5].after, None);
+
+        let res = repository.history(other_owner_id, todo.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let page = repository
+            .audit(
+                owner_id,
+                AuditQuery {
+                    todo_id: Some(todo.id),
+                    action: Some(TodoAction::Delete),
+                    ..AuditQuery::default()
+                },
+            )
+            .await
+            .expect("[audit] returned error");
+        let ids: Vec<i32> = page.items.iter().map(|event| event.id).collect();
+        assert_eq!(ids, vec![events[4].id, events[2].id]);
+        let page = repository
+            .audit(
+                owner_id,
+                AuditQuery {
+                    since: Some(since),
+                    limit: Some(4),
+                    ..AuditQuery::default()
+                },
+            )
+            .await
+            .expect("[audit] returned error");
+        assert_eq!(page.items.len(), 4);
+        assert_eq!(page.next_cursor, Some(events[2].id));
+        let page = repository
+            .audit(
+                owner_id,
+                AuditQuery {
+                    since: Some(since),
+                    cursor: page.next_cursor,
+                    ..AuditQuery::default()
+                },
+            )
+            .await
+            .expect("[audit] returned error");
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next_cursor, None);
+
+        let res = repository
+            .batch(
+                owner_id,
+                vec![
+                    TodoOperation::Create {
+                        todo: CreateTodo::new("[history] rolled back".to_string(), vec![]),
+                    },
+                    TodoOperation::Delete { id: i32::MAX },
+                ],
+            )
+            .await;
+        assert!(res.is_err());
+        let page = repository
+            .audit(
+                owner_id,
+                AuditQuery {
+                    since: Some(since),
+                    ..AuditQuery::default()
+                },
+            )
+            .await
+            .expect("[audit] returned error");
+        assert_eq!(
+            page.items.len(),
+            6,
+            "a failed batch must not leave events behind"
+        );
+    }
+
+    #[tokio::test]
+    async fn todo_history_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "history@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "history-other@example.com").await;
+        todo_history_contract(TodoRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_history_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "history@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "history-other@example.com").await;
+        todo_history_contract(TodoRepositoryForSqlite::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_history_contract_for_memory() {
+        todo_history_contract(TodoRepositoryForMemory::new(vec![]), 1, 2).await;
+    }
+
+    async fn todo_undo_contract<T: TodoRepository>(
+        repository: T,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) -> Todo {
+        let todo = repository
+            .create(
+                owner_id,
+                CreateTodo::new("[undo] draft".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+        let res = repository.undo(owner_id, todo.id).await;
+        assert!(
+            matches!(res, Err(RepositoryError::Conflict(_))),
+            "creating is not undone"
+        );
+
+        repository
+            .update(
+                owner_id,
+                todo.id,
+                UpdateTodo {
+                    text: Some("[undo] final".to_string()),
+                    completed: Some(true),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        let undone = repository
+            .undo(owner_id, todo.id)
+            .await
+            .expect("[undo] returned error");
+        assert_eq!(undone.text, "[undo] draft");
+        assert!(!undone.completed);
+        assert_eq!(undone.completed_at, None);
+        let redone = repository
+            .redo(owner_id, todo.id)
+            .await
+            .expect("[redo] returned error");
+        assert_eq!(redone.text, "[undo] final");
+        assert!(redone.completed);
+        let res = repository.redo(owner_id, todo.id).await;
+        assert!(matches!(res, Err(RepositoryError::Conflict(_))));
+
+        repository
+            .undo(owner_id, todo.id)
+            .await
+            .expect("[undo] returned error");
+        repository
+            .update(
+                owner_id,
+                todo.id,
+                UpdateTodo {
+                    priority: Some(Priority::High),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        let res = repository.redo(owner_id, todo.id).await;
+        assert!(
+            matches!(res, Err(RepositoryError::Conflict(_))),
+            "a new change drops what could have been redone"
+        );
+
+        repository
+            .delete(owner_id, todo.id, None)
+            .await
+            .expect("[delete] returned error");
+        let undone = repository
+            .undo(owner_id, todo.id)
+            .await
+            .expect("[undo] returned error");
+        assert_eq!(undone.deleted_at, None);
+        assert_eq!(undone.priority, Priority::High);
+        repository
+            .find(owner_id, todo.id)
+            .await
+            .expect("[find] undone delete returned error");
+        let redone = repository
+            .redo(owner_id, todo.id)
+            .await
+            .expect("[redo] returned error");
+        assert!(redone.deleted_at.is_some());
+        let undone = repository
+            .undo(owner_id, todo.id)
+            .await
+            .expect("[undo] returned error");
+
+        let res = repository.undo(other_owner_id, todo.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let events = repository
+            .history(owner_id, todo.id)
+            .await
+            .expect("[history] returned error");
+        let last = events.last().unwrap();
+        assert_eq!(last.action, TodoAction::Undo);
+        assert_eq!(last.reverts, Some(events[events.len() - 4].id));
+
+        undone
+    }
+
+    // Changes the row behind the history's back, like a concurrent request would.
+    #[tokio::test]
+    async fn todo_undo_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "undo@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "undo-other@example.com").await;
+        let repository = TodoRepositoryForDB::new(pool.clone());
+        let todo = todo_undo_contract(repository.clone(), owner_id, other_owner_id).await;
+
+        sqlx::query("update todos set updated_at = now() where id = $1")
+            .bind(todo.id)
+            .execute(&pool)
+            .await
+            .expect("[touch] returned error");
+        let res = repository.undo(owner_id, todo.id).await;
+        assert!(matches!(res, Err(RepositoryError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn todo_undo_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "undo@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "undo-other@example.com").await;
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+        let todo = todo_undo_contract(repository.clone(), owner_id, other_owner_id).await;
+
+        sqlx::query("update todos set updated_at = $1 where id = $2")
+            .bind(Utc::now())
+            .bind(todo.id)
+            .execute(&pool)
+            .await
+            .expect("[touch] returned error");
+        let res = repository.undo(owner_id, todo.id).await;
+        assert!(matches!(res, Err(RepositoryError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn todo_undo_contract_for_memory() {
+        todo_undo_contract(TodoRepositoryForMemory::new(vec![]), 1, 2).await;
+    }
+
+    async fn todo_version_contract<T: TodoRepository>(repository: T, owner_id: i32) {
+        let todo = repository
+            .create(
+                owner_id,
+                CreateTodo::new("[version] draft".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+        assert_eq!(todo.version, 1);
+
+        let edit = UpdateTodo {
+            text: Some("[version] final".to_string()),
+            ..UpdateTodo::default()
+        };
+        let updated = repository
+            .update(owner_id, todo.id, edit.clone(), Some(1))
+            .await
+            .expect("[update] returned error");
+        assert_eq!(updated.version, 2);
+        let res = repository
+            .update(owner_id, todo.id, edit.clone(), Some(1))
+            .await;
+        assert!(matches!(res, Err(RepositoryError::VersionMismatch(_))));
+        let res = repository.update(owner_id, i32::MAX, edit, Some(1)).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let res = repository.delete(owner_id, todo.id, Some(1)).await;
+        assert!(matches!(res, Err(RepositoryError::VersionMismatch(_))));
+        repository
+            .find(owner_id, todo.id)
+            .await
+            .expect("[find] a mismatched delete must leave the todo alone");
+        repository
+            .delete(owner_id, todo.id, Some(2))
+            .await
+            .expect("[delete] returned error");
+        let res = repository.delete(owner_id, todo.id, Some(2)).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn todo_version_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "version@example.com").await;
+        todo_version_contract(TodoRepositoryForDB::new(pool), owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_version_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "version@example.com").await;
+        todo_version_contract(TodoRepositoryForSqlite::new(pool), owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_version_contract_for_memory() {
+        todo_version_contract(TodoRepositoryForMemory::new(vec![]), 1).await;
+    }
+
+    async fn todo_changes_contract<T: TodoRepository>(repository: T, owner_id: i32) {
+        let mut receiver = repository.changes().subscribe(None).receiver;
+
+        let todo = repository
+            .create(
+                owner_id,
+                CreateTodo::new("[changes] todo".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+        let res = repository
+            .update(owner_id, todo.id, UpdateTodo::complete(), Some(i32::MAX))
+            .await;
+        assert!(matches!(res, Err(RepositoryError::VersionMismatch(_))));
+        let updated = repository
+            .update(owner_id, todo.id, UpdateTodo::complete(), None)
+            .await
+            .expect("[update] returned error");
+        repository
+            .delete(owner_id, todo.id, None)
+            .await
+            .expect("[delete] returned error");
+        let restored = repository
+            .restore(owner_id, todo.id)
+            .await
+            .expect("[restore] returned error");
+        let undone = repository
+            .undo(owner_id, todo.id)
+            .await
+            .expect("[undo] returned error");
+
+        let mut received = vec![];
+        while let Ok(change) = receiver.try_recv() {
+            assert_eq!(change.owner_id, owner_id);
+            received.push((change.id, change.kind, change.todo));
+        }
+        assert_eq!(received.len(), 5, "a failed update must not be published");
+        assert_eq!(received[0], (1, ChangeKind::Created, todo));
+        assert_eq!(received[1], (2, ChangeKind::Updated, updated));
+        assert_eq!(received[2].1, ChangeKind::Deleted);
+        assert!(received[2].2.deleted_at.is_some());
+        assert_eq!(received[3], (4, ChangeKind::Created, restored));
+        assert_eq!(received[4].1, ChangeKind::Deleted);
+        assert_eq!(received[4].2, undone);
+
+        let replay = repository.changes().subscribe(Some(3)).replay.unwrap();
+        assert_eq!(
+            replay.iter().map(|change| change.id).collect::<Vec<_>>(),
+            [4, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn todo_changes_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "changes@example.com").await;
+        todo_changes_contract(TodoRepositoryForDB::new(pool), owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_changes_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "changes@example.com").await;
+        todo_changes_contract(TodoRepositoryForSqlite::new(pool), owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_changes_contract_for_memory() {
+        todo_changes_contract(TodoRepositoryForMemory::new(vec![]), 1).await;
+    }
+
+    #[test]
+    fn should_translate_websearch_syntax_to_fts5() {
+        assert_eq!(
+            fts5_query(r#"buy "oat milk" -bread"#),
+            Some(r#""buy" "oat milk" NOT "bread""#.to_string())
+        );
+        assert_eq!(
+            fts5_query("or dog or or cat or"),
+            Some(r#""dog" OR "cat""#.to_string())
+        );
+        assert_eq!(fts5_query("-bread"), None);
+        assert_eq!(fts5_query(r#""*" NEAR("#), Some(r#""NEAR""#.to_string()));
+    }
+
+    #[tokio::test]
+    async fn todo_hierarchy_contract_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "hierarchy@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "hierarchy-other@example.com").await;
+        todo_hierarchy_contract(TodoRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_hierarchy_contract_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "hierarchy@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "hierarchy-other@example.com").await;
+        todo_hierarchy_contract(TodoRepositoryForSqlite::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn todo_hierarchy_contract_for_memory() {
+        todo_hierarchy_contract(TodoRepositoryForMemory::new(vec![]), 1, 2).await;
+    }
+
+    #[tokio::test]
+    async fn todo_events_are_append_only_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "append-only@example.com").await;
+        let todo = TodoRepositoryForDB::new(pool.clone())
+            .create(
+                owner_id,
+                CreateTodo::new("[append-only]".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+
+        for query in [
+            "update todo_events set before = null where todo_id = $1",
+            "delete from todo_events where todo_id = $1",
+        ] {
+            let res = sqlx::query(query).bind(todo.id).execute(&pool).await;
+            assert!(res.is_err(), "{} must be refused", query);
+        }
+        let res = sqlx::query("delete from users where id = $1")
+            .bind(owner_id)
+            .execute(&pool)
+            .await;
+        assert!(res.is_err(), "users with a history cannot be deleted");
+    }
+
+    #[tokio::test]
+    async fn todo_events_are_append_only_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "append-only@example.com").await;
+        let todo = TodoRepositoryForSqlite::new(pool.clone())
+            .create(
+                owner_id,
+                CreateTodo::new("[append-only]".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+
+        for query in [
+            "update todo_events set before = null where todo_id = $1",
+            "delete from todo_events where todo_id = $1",
+        ] {
+            let res = sqlx::query(query).bind(todo.id).execute(&pool).await;
+            assert!(res.is_err(), "{} must be refused", query);
+        }
+        let res = sqlx::query("delete from users where id = $1")
+            .bind(owner_id)
+            .execute(&pool)
+            .await;
+        assert!(res.is_err(), "users with a history cannot be deleted");
+    }
+
+    #[tokio::test]
+    async fn concurrent_updates_keep_history_consistent_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "concurrent@example.com").await;
+        let repository = TodoRepositoryForDB::new(pool);
+        let created = repository
+            .create(
+                owner_id,
+                CreateTodo::new("[concurrent] 0".to_string(), vec![]),
+            )
+            .await
+            .expect("[create] returned error");
+
+        let updates: Vec<_> = (1..=8)
+            .map(|n| {
+                let repository = repository.clone();
+                tokio::spawn(async move {
+                    let payload = UpdateTodo {
+                        text: Some(format!("[concurrent] {}", n)),
+                        ..UpdateTodo::default()
+                    };
+                    repository.update(owner_id, created.id, payload, None).await
+                })
+            })
+            .collect();
+        for update in updates {
+            update.await.unwrap().expect("[update] returned error");
+        }
+
+        let history = repository
+            .history(owner_id, created.id)
+            .await
+            .expect("[history] returned error");
+        assert_eq!(history.len(), 9);
+        for pair in history.windows(2) {
+            let before = pair[1].before.as_ref().unwrap();
+            let after = pair[0].after.as_ref().unwrap();
+            assert_eq!(
+                before.0["text"], after.0["text"],
+                "each update starts from what the previous one left"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn todo_crud_scenario() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "todo_crud_scenario@example.com").await;
+        let other_owner_id =
+            find_or_create_user(&users, "todo_crud_scenario-other@example.com").await;
+        let label = find_or_create_label(&pool, owner_id, "[todo_crud_scenario] label").await;
+
+        let repository = TodoRepositoryForDB::new(pool.clone());
+        let text = "todo text";
+
+        let created = repository
+            .create(owner_id, CreateTodo::new(text.to_string(), vec![label.id]))
+            .await
+            .expect("[create] returned error");
+        assert_eq!(created.text, text);
+        assert!(!created.completed);
+        assert_eq!(created.labels, vec![label.clone()]);
+        let res = repository
+            .create(
+                other_owner_id,
+                CreateTodo::new(text.to_string(), vec![label.id]),
+            )
+            .await;
+        assert!(
+            matches!(res, Err(RepositoryError::Validation(_))),
+            "labels of other users cannot be used"
+        );
+
+        let todo = repository
+            .find(owner_id, created.id)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(created, todo);
+
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some(text.to_string()),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        let todo = page.items.first().unwrap();
+        assert_eq!(created, *todo);
+
+        let page = repository
+            .all(
+                owner_id,
+                TodoQuery {
+                    q: Some("TODO TEXT".to_string()),
+                    sort: Some(TodoSort::CreatedAt),
+                    limit: Some(1),
+                    ..TodoQuery::default()
+                },
+            )
+            .await
+            .expect("[all] returned error");
+        assert_eq!(page.items, vec![created.clone()]);
+        assert!(page.total >= 1);
+
+        let updated_text = "[crud_scenario] updated text";
+        let todo = repository
+            .update(
+                owner_id,
+                todo.id,
+                UpdateTodo {
+                    text: Some(updated_text.to_string()),
+                    completed: Some(true),
+                    labels: Some(vec![]),
+                    ..UpdateTodo::default()
+                },
+                None,
+            )
+            .await
+            .expect("[update] returned error");
+        assert_eq!(created.id, todo.id);
+        assert_eq!(todo.text, updated_text);
+        assert!(todo.labels.is_empty());
+
+        repository
+            .delete(owner_id, todo.id, None)
+            .await
+            .expect("[delete] returned error");
+        let res = repository.find(owner_id, created.id).await;
+        assert!(res.is_err());
+
+        let deleted_at: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"
+                select deleted_at from todos where id = $1
+            "#,
+        )
+        .bind(todo.id)
+        .fetch_one(&pool)
+        .await
+        .expect("[delete] todos fetch error");
+        assert!(deleted_at.is_some());
+
+        repository
+            .purge(owner_id, todo.id)
+            .await
+            .expect("[purge] returned error");
+
+        let todo_rows = sqlx::query(
+            r#"
+                select * from todos where id = $1
+            "#,
+        )
+        .bind(todo.id)
+        .fetch_all(&pool)
+        .await
+        .expect("[purge] todos fetch error");
+        assert!(todo_rows.is_empty());
+
+        let rows = sqlx::query(
+            r#"
+                select * from todo_labels where todo_id = $1
+            "#,
+        )
+        .bind(todo.id)
+        .fetch_all(&pool)
+        .await
+        .expect("[purge] todo_labels fetch error");
+        assert!(rows.is_empty());
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use axum::async_trait;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    use super::*;
+
+    impl Todo {
+        pub fn new(id: i32, text: String, labels: Vec<Label>) -> Self {
+            let now = Utc::now();
+            Self {
+                id,
+                text,
+                completed: false,
+                priority: Priority::default(),
+                due_at: None,
+                completed_at: None,
+                created_at: now,
+                updated_at: now,
+                deleted_at: None,
+                version: 1,
+                project_id: None,
+                parent_id: None,
+                auto_complete: false,
+                recurrence: None,
+                labels,
+                children: None,
+            }
+        }
+
+        pub fn children(&self) -> Option<&Vec<Todo>> {
+            self.children.as_ref()
+        }
+
+        pub fn trashed(self) -> Self {
+            Self {
+                deleted_at: Some(Utc::now()),
+                ..self
+            }
+        }
+
+        // Takes over the timestamps and the version the repository assigned to
+        // `other`, so the remaining fields can be compared with `assert_eq!`.
+        pub fn with_timestamps_of(self, other: &Todo) -> Self {
+            Self {
+                completed_at: other.completed_at,
+                created_at: other.created_at,
+                updated_at: other.updated_at,
+                version: other.version,
+                ..self
+            }
+        }
+    }
+
+    impl CreateTodo {
+        pub fn below(self, parent_id: i32, auto_complete: bool) -> Self {
+            Self {
+                parent_id: Some(parent_id),
+                auto_complete: Some(auto_complete),
+                ..self
+            }
+        }
+    }
+
+    impl UpdateTodo {
+        pub fn move_below(parent_id: Option<i32>) -> Self {
+            Self {
+                parent_id: Some(parent_id),
+                ..Self::default()
+            }
+        }
+
+        pub fn move_to_project(project_id: Option<i32>) -> Self {
+            Self {
+                project_id: Some(project_id),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl TodoQuery {
+        fn matches(&self, todo: &Todo) -> bool {
+            let now = Utc::now();
+            self.completed
+                .is_none_or(|completed| todo.completed == completed)
+                && self
+                    .q
+                    .as_ref()
+                    .is_none_or(|q| todo.text.to_lowercase().contains(&q.to_lowercase()))
+                && self
+                    .priority
+                    .is_none_or(|priority| todo.priority == priority)
+                && self
+                    .due_before
+                    .is_none_or(|due_before| todo.due_at.is_some_and(|due| due < due_before))
+                && self
+                    .due_after
+                    .is_none_or(|due_after| todo.due_at.is_some_and(|due| due >= due_after))
+                && self
+                    .project_id
+                    .is_none_or(|project_id| todo.project_id == Some(project_id))
+                && self.overdue.is_none_or(|overdue| {
+                    let is_overdue = !todo.completed && todo.due_at.is_some_and(|due| due < now);
+                    is_overdue == overdue
+                })
+        }
+    }
+
+    impl AuditQuery {
+        fn matches(&self, event: &TodoEvent) -> bool {
+            self.todo_id.is_none_or(|todo_id| event.todo_id == todo_id)
+                && self.action.is_none_or(|action| event.action == action)
+                && self.since.is_none_or(|since| event.created_at >= since)
+                && self.until.is_none_or(|until| event.created_at < until)
+                && self.cursor.is_none_or(|cursor| event.id < cursor)
+        }
+    }
+
+    impl TodoPage {
+        pub fn new(items: Vec<Todo>, next_cursor: Option<i32>, total: i64) -> Self {
+            Self {
+                items,
+                next_cursor,
+                total,
+            }
+        }
+
+        pub fn with_timestamps_of(self, other: &TodoPage) -> Self {
+            let items = self
+                .items
+                .into_iter()
+                .zip(other.items.iter())
+                .map(|(todo, other)| todo.with_timestamps_of(other))
+                .collect();
+            Self { items, ..self }
+        }
+    }
+
+    fn tokens(text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect()
+    }
+
+    fn highlight(text: &str, words: &[String]) -> String {
+        text.split(' ')
+            .map(|chunk| {
+                if tokens(chunk).iter().any(|token| words.contains(token)) {
+                    format!("<mark>{}</mark>", chunk)
+                } else {
+                    chunk.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct TodoDatas {
+        todos: HashMap<i32, Todo>,
+        owners: HashMap<i32, i32>,
+        events: Vec<TodoEvent>,
+        // Recorded but not published yet.
+        pending: Vec<TodoChange>,
+    }
+
+    impl TodoDatas {
+        // Counting would hand out an id again once a todo was deleted.
+        fn next_id(&self) -> i32 {
+            self.todos.keys().max().copied().unwrap_or_default() + 1
+        }
+
+        fn get(&self, owner_id: i32, id: i32) -> Option<&Todo> {
+            self.get_any(owner_id, id)
+                .filter(|todo| todo.deleted_at.is_none())
+        }
+
+        fn get_trashed(&self, owner_id: i32, id: i32) -> Option<&Todo> {
+            self.get_any(owner_id, id)
+                .filter(|todo| todo.deleted_at.is_some())
+        }
+
+        fn get_any(&self, owner_id: i32, id: i32) -> Option<&Todo> {
+            self.todos
+                .get(&id)
+                .filter(|_| self.owners.get(&id) == Some(&owner_id))
+        }
+
+        // Every todo of the owner that is not in the trash.
+        fn owned(&self, owner_id: i32) -> impl Iterator<Item = &Todo> {
+            self.todos.values().filter(move |todo| {
+                self.owners.get(&todo.id) == Some(&owner_id) && todo.deleted_at.is_none()
+            })
+        }
+
+        fn ancestors(&self, owner_id: i32, id: i32) -> Vec<i32> {
+            let mut ancestors = vec![];
+            let mut next = self.get(owner_id, id);
+            while let Some(todo) = next {
+                ancestors.push(todo.id);
+                next = todo.parent_id.and_then(|id| self.get(owner_id, id));
+            }
+            ancestors
+        }
+
+        fn descendants(&self, id: i32) -> Vec<i32> {
+            let mut descendants = vec![];
+            let mut queue = vec![id];
+            while let Some(parent_id) = queue.pop() {
+                for todo in self.todos.values() {
+                    if todo.parent_id == Some(parent_id) {
+                        descendants.push(todo.id);
+                        queue.push(todo.id);
+                    }
+                }
+            }
+            descendants.sort();
+            descendants
+        }
+
+        // Returns how many todos were moved, like `trash_in`.
+        fn trash(&mut self, actor_id: i32, id: i32, now: DateTime<Utc>) -> usize {
+            let mut trashed = 0;
+            for id in self.descendants(id).into_iter().chain([id]) {
+                let Some(todo) = self.todos.get_mut(&id) else {
+                    continue;
+                };
+                if todo.deleted_at.is_some() {
+                    continue;
+                }
+                let before = todo.clone();
+                todo.deleted_at = Some(now);
+                let after = todo.clone();
+                self.record(
+                    actor_id,
+                    TodoAction::Delete,
+                    Some(&before),
+                    Some(&after),
+                    None,
+                );
+                trashed += 1;
+            }
+            trashed
+        }
+
+        fn record(
+            &mut self,
+            actor_id: i32,
+            action: TodoAction,
+            before: Option<&Todo>,
+            after: Option<&Todo>,
+            reverts: Option<i32>,
+        ) {
+            self.pending
+                .extend(TodoChange::new(actor_id, before, after));
+            let snapshot = |todo: &Todo| Json(serde_json::to_value(todo).unwrap());
+            self.events.push(TodoEvent {
+                id: self.events.len() as i32 + 1,
+                todo_id: after.or(before).map(|todo| todo.id).unwrap_or_default(),
+                actor_id,
+                action,
+                before: before.map(snapshot),
+                after: after.map(snapshot),
+                created_at: Utc::now(),
+                reverts,
+            });
+        }
+
+        fn remove(&mut self, id: i32) {
+            for id in self.descendants(id).into_iter().chain([id]) {
+                self.todos.remove(&id);
+                self.owners.remove(&id);
+            }
+        }
+
+        fn roll_up(&mut self, actor_id: i32, mut parent_id: Option<i32>, now: DateTime<Utc>) {
+            while let Some(id) = parent_id {
+                let children: Vec<&Todo> = self
+                    .todos
+                    .values()
+                    .filter(|todo| todo.parent_id == Some(id) && todo.deleted_at.is_none())
+                    .collect();
+                let done = !children.is_empty() && children.iter().all(|todo| todo.completed);
+                let Some(parent) = self.todos.get_mut(&id) else {
+                    return;
+                };
+                if !done || !parent.auto_complete || parent.completed || parent.deleted_at.is_some()
+                {
+                    return;
+                }
+                let before = parent.clone();
+                parent.completed = true;
+                parent.completed_at = Some(now);
+                parent.updated_at = now;
+                parent.version += 1;
+                parent_id = parent.parent_id;
+                let after = parent.clone();
+                self.record(
+                    actor_id,
+                    TodoAction::Update,
+                    Some(&before),
+                    Some(&after),
+                    None,
+                );
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct TodoRepositoryForMemory {
+        store: Arc<RwLock<TodoDatas>>,
+        labels: Vec<Label>,
+        changes: TodoChanges,
+    }
+
+    impl TodoRepositoryForMemory {
+        pub fn new(labels: Vec<Label>) -> Self {
+            TodoRepositoryForMemory {
+                store: Arc::default(),
+                labels,
+                changes: TodoChanges::default(),
+            }
+        }
+
+        /// Stands in for the database when a project is deleted: its todos move to
+        /// the inbox, and with `trash` go to the trash from there.
+        pub fn leave_project(&self, owner_id: i32, project_id: i32, trash: bool) {
+            let mut store = self.write_store_ref();
+            let mut active = vec![];
+            for (id, todo) in store.todos.iter_mut() {
+                if todo.project_id != Some(project_id) {
+                    continue;
+                }
+                todo.project_id = None;
+                if todo.deleted_at.is_none() {
+                    active.push(*id);
+                }
+            }
+            active.retain(|id| store.owners.get(id) == Some(&owner_id));
+            active.sort();
+            if trash {
+                let now = Utc::now();
+                for id in active {
+                    store.trash(owner_id, id, now);
+                }
+            }
+            self.publish(&mut store);
+        }
+
+        fn publish(&self, store: &mut TodoDatas) {
+            self.changes.publish(std::mem::take(&mut store.pending));
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, TodoDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, TodoDatas> {
+            self.store.read().unwrap()
+        }
+
+        fn matching_ids(&self, owner_id: i32, query: &TodoQuery) -> Vec<i32> {
+            let store = self.read_store_ref();
+            let mut ids: Vec<i32> = store
+                .owned(owner_id)
+                .filter(|todo| query.matches(todo))
+                .map(|todo| todo.id)
+                .collect();
+            ids.sort();
+            ids
+        }
+
+        fn resolve_labels(&self, labels: Vec<i32>) -> Vec<Label> {
+            self.labels
+                .iter()
+                .filter(|label| labels.contains(&label.id))
+                .cloned()
+                .collect()
+        }
+
+        fn revert(
+            &self,
+            owner_id: i32,
+            id: i32,
+            action: TodoAction,
+        ) -> Result<Todo, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let current = store
+                .get_any(owner_id, id)
+                .cloned()
+                .ok_or(RepositoryError::NotFound(id))?;
+            let events: Vec<TodoEvent> = store
+                .events
+                .iter()
+                .filter(|event| event.todo_id == id && event.actor_id == owner_id)
+                .cloned()
+                .collect();
+            let (reverts, snapshot) = revert_target(&events, action, &current)?;
+            if let (None, Some(parent_id)) = (snapshot.deleted_at, snapshot.parent_id) {
+                check_parent(Some(id), parent_id, &store.ancestors(owner_id, parent_id))?;
+            }
+            let labels =
+                self.resolve_labels(snapshot.labels.iter().map(|label| label.id).collect());
+            let todo = Todo {
+                id,
+                created_at: current.created_at,
+                updated_at: Utc::now(),
+                version: current.version + 1,
+                labels,
+                children: None,
+                ..snapshot
+            };
+            store.todos.insert(id, todo.clone());
+            store.record(owner_id, action, Some(&current), Some(&todo), Some(reverts));
+            self.publish(&mut store);
+            Ok(todo)
+        }
+    }
+
+    #[async_trait]
+    impl TodoRepository for TodoRepositoryForMemory {
+        async fn create(
+            &self,
+            owner_id: i32,
+            payload: CreateTodo,
+        ) -> Result<Todo, RepositoryError> {
+            let mut store = self.write_store_ref();
+            if let Some(parent_id) = payload.parent_id {
+                check_parent(None, parent_id, &store.ancestors(owner_id, parent_id))?;
+            }
+            let id = store.next_id();
+            let labels = self.resolve_labels(payload.labels);
+            let todo = Todo {
+                priority: payload.priority.unwrap_or_default(),
+                due_at: payload.due_at,
+                project_id: payload.project_id,
+                parent_id: payload.parent_id,
+                auto_complete: payload.auto_complete.unwrap_or_default(),
+                recurrence: payload.recurrence,
+                completed: payload.completed_at.is_some(),
+                completed_at: payload.completed_at,
+                ..Todo::new(id, payload.text.clone(), labels)
+            };
+            store.todos.insert(id, todo.clone());
+            store.owners.insert(id, owner_id);
+            store.record(owner_id, TodoAction::Create, None, Some(&todo), None);
+            self.publish(&mut store);
+            Ok(todo)
+        }
+        async fn find(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+            let store = self.read_store_ref();
+            let todo = store
+                .get(owner_id, id)
+                .cloned()
+                .ok_or(RepositoryError::NotFound(id))?;
+            Ok(todo)
+        }
+        async fn all(&self, owner_id: i32, query: TodoQuery) -> Result<TodoPage, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut todos: Vec<Todo> = store
+                .owned(owner_id)
+                .filter(|todo| query.matches(todo))
+                .cloned()
+                .collect();
+            let total = todos.len() as i64;
+
+            // ids are handed out in creation order, so they double as created_at here.
+            todos.sort_by(|a, b| match query.sort() {
+                TodoSort::Text => a.text.cmp(&b.text).then(a.id.cmp(&b.id)),
+                TodoSort::Id | TodoSort::CreatedAt => a.id.cmp(&b.id),
+            });
+            if query.order() == SortOrder::Desc {
+                todos.reverse();
+            }
+            if let Some(cursor) = query.cursor {
+                let position = todos.iter().position(|todo| todo.id == cursor);
+                todos = match position {
+                    Some(position) => todos.split_off(position + 1),
+                    None => vec![],
+                };
+            }
+
+            let (items, next_cursor) = paginate(todos, query.limit(), |todo| todo.id);
+
+            Ok(TodoPage {
+                items,
+                next_cursor,
+                total,
+            })
+        }
+        async fn update(
+            &self,
+            owner_id: i32,
+            id: i32,
+            payload: UpdateTodo,
+            version: Option<i32>,
+        ) -> Result<Todo, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let todo = store
+                .get(owner_id, id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            if version.is_some_and(|version| version != todo.version) {
+                return Err(RepositoryError::VersionMismatch(id));
+            }
+            if let Some(Some(parent_id)) = payload.parent_id {
+                check_parent(Some(id), parent_id, &store.ancestors(owner_id, parent_id))?;
+            }
+            let now = Utc::now();
+            let completed_at = match payload.completed {
+                None => todo.completed_at,
+                Some(true) => todo.completed_at.or(Some(now)),
+                Some(false) => None,
+            };
+            let labels = match payload.labels {
+                Some(labels) => self.resolve_labels(labels),
+                None => todo.labels.clone(),
+            };
+            let todo = Todo {
+                id,
+                text: payload.text.unwrap_or(todo.text.clone()),
+                completed: payload.completed.unwrap_or(todo.completed),
+                priority: payload.priority.unwrap_or(todo.priority),
+                due_at: payload.due_at.unwrap_or(todo.due_at),
+                project_id: payload.project_id.unwrap_or(todo.project_id),
+                parent_id: payload.parent_id.unwrap_or(todo.parent_id),
+                auto_complete: payload.auto_complete.unwrap_or(todo.auto_complete),
+                recurrence: payload.recurrence.unwrap_or(todo.recurrence.clone()),
+                completed_at,
+                created_at: todo.created_at,
+                updated_at: now,
+                deleted_at: None,
+                version: todo.version + 1,
+                labels,
+                children: None,
+            };
+            let before = store.todos.insert(id, todo.clone());
+            store.record(
+                owner_id,
+                TodoAction::Update,
+                before.as_ref(),
+                Some(&todo),
+                None,
+            );
+            if let Some((due_at, recurrence)) = todo.next_occurrence(now) {
+                let next_id = store.next_id();
+                let next = Todo {
+                    id: next_id,
+                    completed: false,
+                    due_at: Some(due_at),
+                    completed_at: None,
+                    created_at: now,
+                    version: 1,
+                    recurrence: Some(recurrence),
+                    ..todo.clone()
+                };
+                store.todos.insert(next_id, next.clone());
+                store.owners.insert(next_id, owner_id);
+                store.record(owner_id, TodoAction::Create, None, Some(&next), None);
+            }
+            store.roll_up(owner_id, todo.parent_id, now);
+            self.publish(&mut store);
+            Ok(todo)
+        }
+        async fn delete(
+            &self,
+            owner_id: i32,
+            id: i32,
+            version: Option<i32>,
+        ) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            let todo = store
+                .get(owner_id, id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            if version.is_some_and(|version| version != todo.version) {
+                return Err(RepositoryError::VersionMismatch(id));
+            }
+            store.trash(owner_id, id, Utc::now());
+            self.publish(&mut store);
+            Ok(())
+        }
+        async fn trash(&self, owner_id: i32) -> Result<Vec<Todo>, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut todos: Vec<Todo> = store
+                .todos
+                .values()
+                .filter_map(|todo| store.get_trashed(owner_id, todo.id).cloned())
+                .collect();
+            todos.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(b.id.cmp(&a.id)));
+            Ok(todos)
+        }
+        async fn restore(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let todo = store
+                .get_trashed(owner_id, id)
+                .ok_or(RepositoryError::NotFound(id))?;
+            let deleted_at = todo.deleted_at;
+            if let Some(parent_id) = todo.parent_id {
+                if store.get(owner_id, parent_id).is_none() {
+                    return Err(RepositoryError::Conflict(format!(
+                        "parent is in the trash, id is {}",
+                        parent_id
+                    )));
+                }
+            }
+            for id in store.descendants(id).into_iter().chain([id]) {
+                let Some(todo) = store.todos.get_mut(&id) else {
+                    continue;
+                };
+                if todo.deleted_at != deleted_at {
+                    continue;
+                }
+                let before = todo.clone();
+                todo.deleted_at = None;
+                let after = todo.clone();
+                store.record(
+                    owner_id,
+                    TodoAction::Restore,
+                    Some(&before),
+                    Some(&after),
+                    None,
+                );
+            }
+            let todo = store.todos[&id].clone();
+            self.publish(&mut store);
+            Ok(todo)
+        }
+        async fn purge(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            let todo = store
+                .get_trashed(owner_id, id)
+                .cloned()
+                .ok_or(RepositoryError::NotFound(id))?;
+            store.remove(id);
+            store.record(owner_id, TodoAction::Purge, Some(&todo), None, None);
+            self.publish(&mut store);
+            Ok(())
+        }
+        async fn purge_expired(&self, before: DateTime<Utc>) -> Result<u64, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let expired: Vec<i32> = store
+                .todos
+                .values()
+                .filter(|todo| {
+                    todo.deleted_at
+                        .is_some_and(|deleted_at| deleted_at < before)
+                })
+                .map(|todo| todo.id)
+                .collect();
+            for id in expired.iter() {
+                store.remove(*id);
+            }
+            Ok(expired.len() as u64)
+        }
+        async fn history(&self, owner_id: i32, id: i32) -> Result<Vec<TodoEvent>, RepositoryError> {
+            let store = self.read_store_ref();
+            let events: Vec<TodoEvent> = store
+                .events
+                .iter()
+                .filter(|event| event.todo_id == id && event.actor_id == owner_id)
+                .cloned()
+                .collect();
+            if events.is_empty() {
+                return Err(RepositoryError::NotFound(id));
+            }
+            Ok(events)
+        }
+        async fn audit(
+            &self,
+            owner_id: i32,
+            query: AuditQuery,
+        ) -> Result<TodoEventPage, RepositoryError> {
+            let store = self.read_store_ref();
+            let events: Vec<TodoEvent> = store
+                .events
+                .iter()
+                .rev()
+                .filter(|event| event.actor_id == owner_id && query.matches(event))
+                .take(query.limit() as usize + 1)
+                .cloned()
+                .collect();
+            let (items, next_cursor) = paginate(events, query.limit(), |event| event.id);
+            Ok(TodoEventPage { items, next_cursor })
+        }
+        // Applies the operations one by one and puts the previous state back when
+        // one of them fails. Unlike a transaction this does not isolate concurrent
+        // writers, which the tests never have, and the changes of a batch that is
+        // put back have already been published.
+        async fn batch(
+            &self,
+            owner_id: i32,
+            operations: Vec<TodoOperation>,
+        ) -> Result<Vec<TodoOperationResult>, BatchError> {
+            let snapshot = self.read_store_ref().clone();
+            let mut results = vec![];
+            for (index, operation) in operations.into_iter().enumerate() {
+                let result = match operation.resolve(&results) {
+                    Err(error) => Err(error),
+                    Ok(TodoOperation::Create { todo }) => self
+                        .create(owner_id, todo)
+                        .await
+                        .map(|todo| TodoOperationResult::Create { todo }),
+                    Ok(TodoOperation::Update { id, todo }) => self
+                        .update(owner_id, id, todo, None)
+                        .await
+                        .map(|todo| TodoOperationResult::Update { todo }),
+                    Ok(TodoOperation::Delete { id }) => self
+                        .delete(owner_id, id, None)
+                        .await
+                        .map(|_| TodoOperationResult::Delete { id }),
+                };
+                match result {
+                    Ok(result) => results.push(result),
+                    Err(error) => {
+                        *self.write_store_ref() = snapshot;
+                        return Err(BatchError::at(index, error));
+                    }
+                }
+            }
+            Ok(results)
+        }
+        async fn complete_all(
+            &self,
+            owner_id: i32,
+            query: TodoQuery,
+        ) -> Result<u64, RepositoryError> {
+            let query = TodoQuery {
+                completed: Some(false),
+                ..query
+            };
+            let ids = self.matching_ids(owner_id, &query);
+            for id in ids.iter() {
+                self.update(owner_id, *id, UpdateTodo::complete(), None)
+                    .await?;
+            }
+            Ok(ids.len() as u64)
+        }
+        async fn clear_completed(
+            &self,
+            owner_id: i32,
+            query: TodoQuery,
+        ) -> Result<u64, RepositoryError> {
+            let query = TodoQuery {
+                completed: Some(true),
+                ..query
+            };
+            let ids = self.matching_ids(owner_id, &query);
+            let mut store = self.write_store_ref();
+            let now = Utc::now();
+            let trashed: usize = ids.iter().map(|id| store.trash(owner_id, *id, now)).sum();
+            self.publish(&mut store);
+            Ok(trashed as u64)
+        }
+        // Tokenised stand-in for the database search: every word of one of the
+        // `or` groups has to appear, `-word` excludes, and the rank is the share of
+        // matching words.
+        async fn search(
+            &self,
+            owner_id: i32,
+            query: TodoSearchQuery,
+        ) -> Result<Vec<TodoSearchHit>, RepositoryError> {
+            let mut groups: Vec<Vec<String>> = vec![vec![]];
+            let mut excluded = vec![];
+            for word in query.q.split_whitespace() {
+                if word.eq_ignore_ascii_case("or") {
+                    groups.push(vec![]);
+                } else if let Some(word) = word.strip_prefix('-') {
+                    excluded.extend(tokens(word));
+                } else {
+                    groups.last_mut().unwrap().extend(tokens(word));
+                }
+            }
+            groups.retain(|group| !group.is_empty());
+            let included: Vec<String> = groups.concat();
+
+            let store = self.read_store_ref();
+            let mut hits: Vec<TodoSearchHit> = store
+                .owned(owner_id)
+                .filter_map(|todo| {
+                    let words = tokens(&todo.text);
+                    let matches = words.iter().filter(|word| included.contains(word)).count();
+                    let found = groups
+                        .iter()
+                        .any(|group| group.iter().all(|word| words.contains(word)))
+                        && !excluded.iter().any(|word| words.contains(word));
+                    found.then(|| TodoSearchHit {
+                        todo: todo.clone(),
+                        rank: matches as f64 / words.len() as f64,
+                        snippet: highlight(&todo.text, &included),
+                    })
+                })
+                .collect();
+            hits.sort_by(|a, b| b.rank.total_cmp(&a.rank).then(b.todo.id.cmp(&a.todo.id)));
+            hits.truncate(query.limit() as usize);
+            Ok(hits)
+        }
+        async fn undo(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+            self.revert(owner_id, id, TodoAction::Undo)
+        }
+        async fn redo(&self, owner_id: i32, id: i32) -> Result<Todo, RepositoryError> {
+            self.revert(owner_id, id, TodoAction::Redo)
+        }
+        async fn descendants(&self, owner_id: i32, id: i32) -> Result<Vec<Todo>, RepositoryError> {
+            let store = self.read_store_ref();
+            let todos = store
+                .descendants(id)
+                .into_iter()
+                .filter_map(|id| store.get(owner_id, id).cloned())
+                .collect();
+            Ok(todos)
+        }
+        fn changes(&self) -> &TodoChanges {
+            &self.changes
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use super::RepositoryError;
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, SqlitePool};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[async_trait]
+pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, email: String, password_hash: String) -> Result<User, RepositoryError>;
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError>;
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+    async fn find_by_session(&self, token_hash: &str) -> Result<Option<User>, RepositoryError>;
+    /// Sets the secret of the user's calendar feed, replacing any earlier one.
+    async fn create_calendar_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+    ) -> Result<(), RepositoryError>;
+    /// Revokes the secret of the user's calendar feed, if there is one.
+    async fn delete_calendar_token(&self, user_id: i32) -> Result<(), RepositoryError>;
+    async fn find_by_calendar_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<User>, RepositoryError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForDB {
+    pool: PgPool,
+}
+
+impl UserRepositoryForDB {
+    pub fn new(pool: PgPool) -> Self {
+        UserRepositoryForDB { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForDB {
+    async fn create(&self, email: String, password_hash: String) -> Result<User, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                insert into users (email, password_hash, created_at)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(email)
+        .bind(password_hash)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select * from users where email = $1
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                insert into sessions (token_hash, user_id, expires_at)
+                values ($1, $2, $3)
+            "#,
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn find_by_session(&self, token_hash: &str) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select users.* from sessions
+                inner join users on users.id = sessions.user_id
+                where sessions.token_hash = $1 and sessions.expires_at > $2
+            "#,
+        )
+        .bind(token_hash)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn create_calendar_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                insert into calendar_tokens (user_id, token_hash, created_at)
+                values ($1, $2, $3)
+                on conflict (user_id)
+                do update set token_hash = excluded.token_hash, created_at = excluded.created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn delete_calendar_token(&self, user_id: i32) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                delete from calendar_tokens where user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn find_by_calendar_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select users.* from calendar_tokens
+                inner join users on users.id = calendar_tokens.user_id
+                where calendar_tokens.token_hash = $1
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl UserRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        UserRepositoryForSqlite { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForSqlite {
+    async fn create(&self, email: String, password_hash: String) -> Result<User, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                insert into users (email, password_hash, created_at)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(email)
+        .bind(password_hash)
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select * from users where email = $1
+            "#,
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn create_session(
+        &self,
+        user_id: i32,
+        token_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                insert into sessions (token_hash, user_id, expires_at)
+                values ($1, $2, $3)
+            "#,
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn find_by_session(&self, token_hash: &str) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select users.* from sessions
+                inner join users on users.id = sessions.user_id
+                where sessions.token_hash = $1 and sessions.expires_at > $2
+            "#,
+        )
+        .bind(token_hash)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+    async fn create_calendar_token(
+        &self,
+        user_id: i32,
+        token_hash: String,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                insert into calendar_tokens (user_id, token_hash, created_at)
+                values ($1, $2, $3)
+                on conflict (user_id)
+                do update set token_hash = excluded.token_hash, created_at = excluded.created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn delete_calendar_token(&self, user_id: i32) -> Result<(), RepositoryError> {
+        sqlx::query(
+            r#"
+                delete from calendar_tokens where user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn find_by_calendar_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+                select users.* from calendar_tokens
+                inner join users on users.id = calendar_tokens.user_id
+                where calendar_tokens.token_hash = $1
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct User {
+    pub id: i32,
+    pub email: String,
+    #[serde(skip)]
+    pub password_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct Credentials {
+    #[validate(email(message = "Invalid email"))]
+    #[schema(format = "email")]
+    pub email: String,
+    #[validate(length(min = 8, message = "Too short password"))]
+    #[validate(length(max = 128, message = "Over password length"))]
+    #[schema(format = Password, min_length = 8, max_length = 128)]
+    pub password: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::test_utils::{connect_postgres, connect_sqlite};
+    use chrono::Duration;
+
+    async fn user_scenario<T: UserRepository>(repository: T, email: &str) {
+        let created = repository
+            .create(email.to_string(), "hash".to_string())
+            .await
+            .expect("[create] returned error");
+        assert_eq!(created.email, email);
+
+        let duplicated = repository
+            .create(email.to_string(), "hash".to_string())
+            .await;
+        assert!(matches!(duplicated, Err(RepositoryError::Conflict(_))));
+
+        let user = repository
+            .find_by_email(email)
+            .await
+            .expect("[find_by_email] returned error");
+        assert_eq!(user, Some(created.clone()));
+
+        let token_hash = format!("{}-token", email);
+        repository
+            .create_session(
+                created.id,
+                token_hash.clone(),
+                Utc::now() + Duration::hours(1),
+            )
+            .await
+            .expect("[create_session] returned error");
+        let user = repository
+            .find_by_session(&token_hash)
+            .await
+            .expect("[find_by_session] returned error");
+        assert_eq!(user, Some(created.clone()));
+
+        let expired = format!("{}-expired", email);
+        repository
+            .create_session(created.id, expired.clone(), Utc::now() - Duration::hours(1))
+            .await
+            .expect("[create_session] returned error");
+        let user = repository
+            .find_by_session(&expired)
+            .await
+            .expect("[find_by_session] returned error");
+        assert_eq!(user, None);
+
+        for token_hash in ["first", "second"] {
+            repository
+                .create_calendar_token(created.id, format!("{}-calendar-{}", email, token_hash))
+                .await
+                .expect("[create_calendar_token] returned error");
+        }
+        let user = repository
+            .find_by_calendar_token(&format!("{}-calendar-first", email))
+            .await
+            .expect("[find_by_calendar_token] returned error");
+        assert_eq!(user, None, "replaced by the second token");
+        let user = repository
+            .find_by_calendar_token(&format!("{}-calendar-second", email))
+            .await
+            .expect("[find_by_calendar_token] returned error");
+        assert_eq!(user, Some(created.clone()));
+
+        repository
+            .delete_calendar_token(created.id)
+            .await
+            .expect("[delete_calendar_token] returned error");
+        let user = repository
+            .find_by_calendar_token(&format!("{}-calendar-second", email))
+            .await
+            .expect("[find_by_calendar_token] returned error");
+        assert_eq!(user, None);
+    }
+
+    #[tokio::test]
+    async fn user_scenario_for_db() {
+        let pool = connect_postgres().await;
+        sqlx::query("delete from users where email = $1")
+            .bind("scenario@example.com")
+            .execute(&pool)
+            .await
+            .expect("[cleanup] returned error");
+        user_scenario(UserRepositoryForDB::new(pool), "scenario@example.com").await;
+    }
+
+    #[tokio::test]
+    async fn user_scenario_for_sqlite() {
+        user_scenario(
+            UserRepositoryForSqlite::new(connect_sqlite().await),
+            "scenario@example.com",
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn user_scenario_for_memory() {
+        user_scenario(
+            test_utils::UserRepositoryForMemory::new(),
+            "scenario@example.com",
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    #[derive(Debug, Default)]
+    struct UserDatas {
+        users: HashMap<i32, User>,
+        sessions: HashMap<String, (i32, DateTime<Utc>)>,
+        calendar_tokens: HashMap<i32, String>,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct UserRepositoryForMemory {
+        store: Arc<RwLock<UserDatas>>,
+    }
+
+    impl UserRepositoryForMemory {
+        pub fn new() -> Self {
+            UserRepositoryForMemory {
+                store: Arc::default(),
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, UserDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, UserDatas> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl UserRepository for UserRepositoryForMemory {
+        async fn create(
+            &self,
+            email: String,
+            password_hash: String,
+        ) -> Result<User, RepositoryError> {
+            let mut store = self.write_store_ref();
+            if store.users.values().any(|user| user.email == email) {
+                return Err(RepositoryError::Conflict(format!(
+                    "email already exists: {}",
+                    email
+                )));
+            }
+            let id = (store.users.len() + 1) as i32;
+            let user = User {
+                id,
+                email,
+                password_hash,
+                created_at: Utc::now(),
+            };
+            store.users.insert(id, user.clone());
+            Ok(user)
+        }
+        async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+            let store = self.read_store_ref();
+            Ok(store
+                .users
+                .values()
+                .find(|user| user.email == email)
+                .cloned())
+        }
+        async fn create_session(
+            &self,
+            user_id: i32,
+            token_hash: String,
+            expires_at: DateTime<Utc>,
+        ) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            store.sessions.insert(token_hash, (user_id, expires_at));
+            Ok(())
+        }
+        async fn find_by_session(&self, token_hash: &str) -> Result<Option<User>, RepositoryError> {
+            let store = self.read_store_ref();
+            let user = store
+                .sessions
+                .get(token_hash)
+                .filter(|(_, expires_at)| *expires_at > Utc::now())
+                .and_then(|(user_id, _)| store.users.get(user_id))
+                .cloned();
+            Ok(user)
+        }
+        async fn create_calendar_token(
+            &self,
+            user_id: i32,
+            token_hash: String,
+        ) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            store.calendar_tokens.insert(user_id, token_hash);
+            Ok(())
+        }
+        async fn delete_calendar_token(&self, user_id: i32) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            store.calendar_tokens.remove(&user_id);
+            Ok(())
+        }
+        async fn find_by_calendar_token(
+            &self,
+            token_hash: &str,
+        ) -> Result<Option<User>, RepositoryError> {
+            let store = self.read_store_ref();
+            let user = store
+                .calendar_tokens
+                .iter()
+                .find(|(_, hash)| *hash == token_hash)
+                .and_then(|(user_id, _)| store.users.get(user_id))
+                .cloned();
+            Ok(user)
+        }
+    }
+}
@@ -0,0 +1,956 @@
+use super::{changes::ChangeKind, RepositoryError};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{types::Json, FromRow, PgPool, SqlitePool};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// How many deliveries the log of a webhook shows.
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+#[async_trait]
+pub trait WebhookRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(
+        &self,
+        owner_id: i32,
+        payload: CreateWebhook,
+    ) -> Result<Webhook, RepositoryError>;
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Webhook, RepositoryError>;
+    async fn all(&self, owner_id: i32) -> Result<Vec<Webhook>, RepositoryError>;
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateWebhook,
+    ) -> Result<Webhook, RepositoryError>;
+    /// Deletes the webhook together with its delivery log.
+    async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError>;
+    /// The latest deliveries to the webhook, newest first.
+    async fn deliveries(
+        &self,
+        owner_id: i32,
+        id: i32,
+    ) -> Result<Vec<WebhookDelivery>, RepositoryError>;
+    /// Queues `payload` for every webhook of the owner subscribed to `event`, due
+    /// right away. Returns how many deliveries were queued.
+    async fn enqueue(
+        &self,
+        owner_id: i32,
+        event: ChangeKind,
+        payload: Value,
+    ) -> Result<u64, RepositoryError>;
+    /// Pending deliveries due at `now`, for all owners, longest waiting first.
+    async fn due(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<DueDelivery>, RepositoryError>;
+    /// Counts an attempt at delivery `id` and stores its outcome.
+    async fn record_attempt(
+        &self,
+        id: i32,
+        attempt: DeliveryAttempt,
+    ) -> Result<(), RepositoryError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookRepositoryForDB {
+    pool: PgPool,
+}
+
+impl WebhookRepositoryForDB {
+    pub fn new(pool: PgPool) -> Self {
+        WebhookRepositoryForDB { pool }
+    }
+}
+
+const UPDATE_QUERY: &str = r#"
+    update webhooks set
+        url = coalesce($1, url),
+        secret = coalesce($2, secret),
+        events = coalesce($3, events)
+    where id = $4 and owner_id = $5
+    returning *
+"#;
+
+const DUE_QUERY: &str = r#"
+    select webhook_deliveries.*, webhooks.url, webhooks.secret
+    from webhook_deliveries
+    join webhooks on webhooks.id = webhook_deliveries.webhook_id
+    where webhook_deliveries.state = 'pending' and webhook_deliveries.next_attempt_at <= $1
+    order by webhook_deliveries.next_attempt_at asc, webhook_deliveries.id asc
+    limit $2
+"#;
+
+const RECORD_ATTEMPT_QUERY: &str = r#"
+    update webhook_deliveries set
+        attempts = attempts + 1,
+        state = $2,
+        response_status = $3,
+        last_error = $4,
+        next_attempt_at = $5,
+        updated_at = $6
+    where id = $1
+"#;
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForDB {
+    async fn create(
+        &self,
+        owner_id: i32,
+        payload: CreateWebhook,
+    ) -> Result<Webhook, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+                insert into webhooks (owner_id, url, secret, events, created_at)
+                values ($1, $2, $3, $4, $5)
+                returning *
+            "#,
+        )
+        .bind(owner_id)
+        .bind(payload.url)
+        .bind(payload.secret)
+        .bind(Json(payload.events))
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(webhook)
+    }
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Webhook, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+                select * from webhooks where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(webhook)
+    }
+    async fn all(&self, owner_id: i32) -> Result<Vec<Webhook>, RepositoryError> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            r#"
+                select * from webhooks
+                where owner_id = $1
+                order by id asc
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateWebhook,
+    ) -> Result<Webhook, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(UPDATE_QUERY)
+            .bind(payload.url)
+            .bind(payload.secret)
+            .bind(payload.events.map(Json))
+            .bind(id)
+            .bind(owner_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(webhook)
+    }
+    async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+        let result = sqlx::query(
+            r#"
+                delete from webhooks where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+
+        Ok(())
+    }
+    async fn deliveries(
+        &self,
+        owner_id: i32,
+        id: i32,
+    ) -> Result<Vec<WebhookDelivery>, RepositoryError> {
+        self.find(owner_id, id).await?;
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+                select * from webhook_deliveries
+                where webhook_id = $1
+                order by id desc
+                limit $2
+            "#,
+        )
+        .bind(id)
+        .bind(DELIVERY_LOG_LIMIT)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+    async fn enqueue(
+        &self,
+        owner_id: i32,
+        event: ChangeKind,
+        payload: Value,
+    ) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(
+            r#"
+                insert into webhook_deliveries
+                    (webhook_id, event, payload, state, next_attempt_at, created_at, updated_at)
+                select id, $2, $3, 'pending', $4, $4, $4 from webhooks
+                where owner_id = $1 and events ? $2
+            "#,
+        )
+        .bind(owner_id)
+        .bind(event)
+        .bind(Json(payload))
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+    async fn due(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<DueDelivery>, RepositoryError> {
+        let deliveries = sqlx::query_as::<_, DueDelivery>(DUE_QUERY)
+            .bind(now)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(deliveries)
+    }
+    async fn record_attempt(
+        &self,
+        id: i32,
+        attempt: DeliveryAttempt,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(RECORD_ATTEMPT_QUERY)
+            .bind(id)
+            .bind(attempt.state)
+            .bind(attempt.response_status)
+            .bind(attempt.error)
+            .bind(attempt.next_attempt_at)
+            .bind(attempt.at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl WebhookRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        WebhookRepositoryForSqlite { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForSqlite {
+    async fn create(
+        &self,
+        owner_id: i32,
+        payload: CreateWebhook,
+    ) -> Result<Webhook, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+                insert into webhooks (owner_id, url, secret, events, created_at)
+                values ($1, $2, $3, $4, $5)
+                returning *
+            "#,
+        )
+        .bind(owner_id)
+        .bind(payload.url)
+        .bind(payload.secret)
+        .bind(Json(payload.events))
+        .bind(Utc::now())
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(webhook)
+    }
+    async fn find(&self, owner_id: i32, id: i32) -> Result<Webhook, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+                select * from webhooks where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(webhook)
+    }
+    async fn all(&self, owner_id: i32) -> Result<Vec<Webhook>, RepositoryError> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            r#"
+                select * from webhooks
+                where owner_id = $1
+                order by id asc
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+    async fn update(
+        &self,
+        owner_id: i32,
+        id: i32,
+        payload: UpdateWebhook,
+    ) -> Result<Webhook, RepositoryError> {
+        let webhook = sqlx::query_as::<_, Webhook>(UPDATE_QUERY)
+            .bind(payload.url)
+            .bind(payload.secret)
+            .bind(payload.events.map(Json))
+            .bind(id)
+            .bind(owner_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(webhook)
+    }
+    async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+        let result = sqlx::query(
+            r#"
+                delete from webhooks where id = $1 and owner_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id));
+        }
+
+        Ok(())
+    }
+    async fn deliveries(
+        &self,
+        owner_id: i32,
+        id: i32,
+    ) -> Result<Vec<WebhookDelivery>, RepositoryError> {
+        self.find(owner_id, id).await?;
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+                select * from webhook_deliveries
+                where webhook_id = $1
+                order by id desc
+                limit $2
+            "#,
+        )
+        .bind(id)
+        .bind(DELIVERY_LOG_LIMIT)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+    async fn enqueue(
+        &self,
+        owner_id: i32,
+        event: ChangeKind,
+        payload: Value,
+    ) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(
+            r#"
+                insert into webhook_deliveries
+                    (webhook_id, event, payload, state, next_attempt_at, created_at, updated_at)
+                select id, $2, $3, 'pending', $4, $4, $4 from webhooks
+                where owner_id = $1
+                and exists (select 1 from json_each(webhooks.events) where value = $2)
+            "#,
+        )
+        .bind(owner_id)
+        .bind(event)
+        .bind(Json(payload))
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+    async fn due(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<DueDelivery>, RepositoryError> {
+        let deliveries = sqlx::query_as::<_, DueDelivery>(DUE_QUERY)
+            .bind(now)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(deliveries)
+    }
+    async fn record_attempt(
+        &self,
+        id: i32,
+        attempt: DeliveryAttempt,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(RECORD_ATTEMPT_QUERY)
+            .bind(id)
+            .bind(attempt.state)
+            .bind(attempt.response_status)
+            .bind(attempt.error)
+            .bind(attempt.next_attempt_at)
+            .bind(attempt.at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A URL that is sent the todo events of its owner.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    /// Only ever written, responses leave it out.
+    #[serde(skip)]
+    pub secret: String,
+    #[schema(value_type = Vec<ChangeKind>)]
+    pub events: Json<Vec<ChangeKind>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct CreateWebhook {
+    #[validate(url(message = "Invalid url"), custom = "http_url")]
+    #[schema(example = "https://ci.example.com/hooks/todo")]
+    url: String,
+    /// Key of the HMAC-SHA256 signature sent along with every delivery.
+    #[validate(length(min = 16, message = "Too short"))]
+    #[validate(length(max = 255, message = "Over text length"))]
+    #[schema(format = Password, min_length = 16, max_length = 255)]
+    secret: String,
+    /// The todo events to deliver.
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[schema(min_items = 1)]
+    events: Vec<ChangeKind>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate, ToSchema)]
+pub struct UpdateWebhook {
+    #[validate(url(message = "Invalid url"), custom = "http_url")]
+    #[schema(example = "https://ci.example.com/hooks/todo")]
+    url: Option<String>,
+    #[validate(length(min = 16, message = "Too short"))]
+    #[validate(length(max = 255, message = "Over text length"))]
+    #[schema(format = Password, min_length = 16, max_length = 255)]
+    secret: Option<String>,
+    #[validate(length(min = 1, message = "Cannot be empty"))]
+    #[schema(min_items = 1)]
+    events: Option<Vec<ChangeKind>>,
+}
+
+// Deliveries are HTTP requests, other schemes could never succeed.
+fn http_url(url: &str) -> Result<(), ValidationError> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(ValidationError::new("Not an http or https url"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum DeliveryState {
+    /// Waiting for its first or next attempt.
+    Pending,
+    Delivered,
+    /// Given up on after the last attempt failed.
+    Dead,
+}
+
+/// One event sent, or still to be sent, to a webhook.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: ChangeKind,
+    /// The body that is posted.
+    #[schema(value_type = Object)]
+    pub payload: Json<Value>,
+    pub state: DeliveryState,
+    pub attempts: i32,
+    /// Status the receiver answered the last attempt with.
+    pub response_status: Option<i32>,
+    /// Why the last attempt failed.
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A pending delivery along with where it goes.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct DueDelivery {
+    #[sqlx(flatten)]
+    pub delivery: WebhookDelivery,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryAttempt {
+    pub at: DateTime<Utc>,
+    /// `Pending` again when the delivery is to be retried at `next_attempt_at`.
+    pub state: DeliveryState,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        test_utils::{connect_postgres, connect_sqlite, find_or_create_user},
+        user::{UserRepositoryForDB, UserRepositoryForSqlite},
+    };
+    use chrono::Duration;
+    use serde_json::json;
+
+    async fn webhook_scenario<W: WebhookRepository>(
+        repository: W,
+        owner_id: i32,
+        other_owner_id: i32,
+    ) {
+        for webhook in repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error")
+        {
+            repository
+                .delete(owner_id, webhook.id)
+                .await
+                .expect("[cleanup] returned error");
+        }
+
+        let created = repository
+            .create(
+                owner_id,
+                CreateWebhook::new(
+                    "http://localhost/created",
+                    vec![ChangeKind::Created, ChangeKind::Deleted],
+                ),
+            )
+            .await
+            .expect("[create] returned error");
+        assert_eq!(created.url, "http://localhost/created");
+        assert_eq!(created.secret, "0123456789abcdef");
+        let other = repository
+            .create(
+                owner_id,
+                CreateWebhook::new("http://localhost/updated", vec![ChangeKind::Updated]),
+            )
+            .await
+            .expect("[create] returned error");
+
+        let webhook = repository
+            .find(owner_id, created.id)
+            .await
+            .expect("[find] returned error");
+        assert_eq!(created, webhook);
+        let res = repository.find(other_owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let webhooks = repository
+            .all(owner_id)
+            .await
+            .expect("[all] returned error");
+        assert_eq!(webhooks, vec![created.clone(), other.clone()]);
+
+        let updated = repository
+            .update(
+                owner_id,
+                other.id,
+                UpdateWebhook {
+                    events: Some(vec![ChangeKind::Created, ChangeKind::Updated]),
+                    ..UpdateWebhook::default()
+                },
+            )
+            .await
+            .expect("[update] returned error");
+        assert_eq!(updated.url, other.url);
+        assert_eq!(
+            updated.events,
+            Json(vec![ChangeKind::Created, ChangeKind::Updated])
+        );
+        let res = repository
+            .update(other_owner_id, other.id, UpdateWebhook::default())
+            .await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        let queued = repository
+            .enqueue(owner_id, ChangeKind::Created, json!({ "id": 1 }))
+            .await
+            .expect("[enqueue] returned error");
+        assert_eq!(queued, 2);
+        let queued = repository
+            .enqueue(other_owner_id, ChangeKind::Created, json!({ "id": 2 }))
+            .await
+            .expect("[enqueue] returned error");
+        assert_eq!(queued, 0);
+
+        let due: Vec<DueDelivery> = repository
+            .due(Utc::now(), 100)
+            .await
+            .expect("[due] returned error")
+            .into_iter()
+            .filter(|due| due.delivery.webhook_id == created.id)
+            .collect();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].url, created.url);
+        assert_eq!(due[0].secret, created.secret);
+        let delivery = &due[0].delivery;
+        assert_eq!(delivery.event, ChangeKind::Created);
+        assert_eq!(delivery.payload, Json(json!({ "id": 1 })));
+        assert_eq!(
+            (delivery.state, delivery.attempts),
+            (DeliveryState::Pending, 0)
+        );
+
+        let retry_at = Utc::now() + Duration::hours(1);
+        repository
+            .record_attempt(
+                delivery.id,
+                DeliveryAttempt {
+                    at: Utc::now(),
+                    state: DeliveryState::Pending,
+                    response_status: Some(500),
+                    error: Some("500 Internal Server Error".to_string()),
+                    next_attempt_at: Some(retry_at),
+                },
+            )
+            .await
+            .expect("[record_attempt] returned error");
+        let due = repository
+            .due(Utc::now(), 100)
+            .await
+            .expect("[due] returned error");
+        assert!(due.iter().all(|due| due.delivery.id != delivery.id));
+        let due = repository
+            .due(retry_at, 100)
+            .await
+            .expect("[due] returned error");
+        assert!(due.iter().any(|due| due.delivery.id == delivery.id));
+
+        repository
+            .record_attempt(
+                delivery.id,
+                DeliveryAttempt {
+                    at: Utc::now(),
+                    state: DeliveryState::Dead,
+                    response_status: None,
+                    error: Some("connection refused".to_string()),
+                    next_attempt_at: None,
+                },
+            )
+            .await
+            .expect("[record_attempt] returned error");
+        let log = repository
+            .deliveries(owner_id, created.id)
+            .await
+            .expect("[deliveries] returned error");
+        assert_eq!(log.len(), 1);
+        assert_eq!((log[0].state, log[0].attempts), (DeliveryState::Dead, 2));
+        assert_eq!(log[0].response_status, None);
+        assert_eq!(log[0].last_error.as_deref(), Some("connection refused"));
+        let res = repository.deliveries(other_owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+
+        repository
+            .delete(owner_id, created.id)
+            .await
+            .expect("[delete] returned error");
+        let res = repository.delete(owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+        let res = repository.deliveries(owner_id, created.id).await;
+        assert!(matches!(res, Err(RepositoryError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn webhook_scenario_for_db() {
+        let pool = connect_postgres().await;
+        let users = UserRepositoryForDB::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "webhook@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "webhook-other@example.com").await;
+        webhook_scenario(WebhookRepositoryForDB::new(pool), owner_id, other_owner_id).await;
+    }
+
+    #[tokio::test]
+    async fn webhook_scenario_for_sqlite() {
+        let pool = connect_sqlite().await;
+        let users = UserRepositoryForSqlite::new(pool.clone());
+        let owner_id = find_or_create_user(&users, "webhook@example.com").await;
+        let other_owner_id = find_or_create_user(&users, "webhook-other@example.com").await;
+        webhook_scenario(
+            WebhookRepositoryForSqlite::new(pool),
+            owner_id,
+            other_owner_id,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn webhook_scenario_for_memory() {
+        webhook_scenario(test_utils::WebhookRepositoryForMemory::new(), 1, 2).await;
+    }
+
+    #[test]
+    fn should_only_accept_http_urls() {
+        let webhook = |url: &str| CreateWebhook::new(url, vec![ChangeKind::Created]);
+        assert!(webhook("https://ci.example.com/hooks").validate().is_ok());
+        assert!(webhook("ftp://ci.example.com/hooks").validate().is_err());
+        assert!(webhook("not a url").validate().is_err());
+        let no_events = CreateWebhook::new("https://ci.example.com/hooks", vec![]);
+        assert!(no_events.validate().is_err());
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    };
+
+    impl CreateWebhook {
+        /// A webhook signed with the secret `0123456789abcdef`.
+        pub fn new(url: &str, events: Vec<ChangeKind>) -> Self {
+            Self {
+                url: url.to_string(),
+                secret: "0123456789abcdef".to_string(),
+                events,
+            }
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct WebhookDatas {
+        webhooks: HashMap<i32, (i32, Webhook)>,
+        deliveries: Vec<WebhookDelivery>,
+    }
+
+    impl WebhookDatas {
+        fn get(&self, owner_id: i32, id: i32) -> Result<&Webhook, RepositoryError> {
+            self.webhooks
+                .get(&id)
+                .filter(|(owner, _)| *owner == owner_id)
+                .map(|(_, webhook)| webhook)
+                .ok_or(RepositoryError::NotFound(id))
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct WebhookRepositoryForMemory {
+        store: Arc<RwLock<WebhookDatas>>,
+    }
+
+    impl WebhookRepositoryForMemory {
+        pub fn new() -> Self {
+            WebhookRepositoryForMemory {
+                store: Arc::default(),
+            }
+        }
+
+        fn write_store_ref(&self) -> RwLockWriteGuard<'_, WebhookDatas> {
+            self.store.write().unwrap()
+        }
+
+        fn read_store_ref(&self) -> RwLockReadGuard<'_, WebhookDatas> {
+            self.store.read().unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl WebhookRepository for WebhookRepositoryForMemory {
+        async fn create(
+            &self,
+            owner_id: i32,
+            payload: CreateWebhook,
+        ) -> Result<Webhook, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let id = store.webhooks.keys().max().copied().unwrap_or_default() + 1;
+            let webhook = Webhook {
+                id,
+                url: payload.url,
+                secret: payload.secret,
+                events: Json(payload.events),
+                created_at: Utc::now(),
+            };
+            store.webhooks.insert(id, (owner_id, webhook.clone()));
+            Ok(webhook)
+        }
+        async fn find(&self, owner_id: i32, id: i32) -> Result<Webhook, RepositoryError> {
+            self.read_store_ref().get(owner_id, id).cloned()
+        }
+        async fn all(&self, owner_id: i32) -> Result<Vec<Webhook>, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut webhooks: Vec<Webhook> = store
+                .webhooks
+                .values()
+                .filter(|(owner, _)| *owner == owner_id)
+                .map(|(_, webhook)| webhook.clone())
+                .collect();
+            webhooks.sort_by_key(|webhook| webhook.id);
+            Ok(webhooks)
+        }
+        async fn update(
+            &self,
+            owner_id: i32,
+            id: i32,
+            payload: UpdateWebhook,
+        ) -> Result<Webhook, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let webhook = store.get(owner_id, id)?;
+            let webhook = Webhook {
+                url: payload.url.unwrap_or(webhook.url.clone()),
+                secret: payload.secret.unwrap_or(webhook.secret.clone()),
+                events: payload.events.map(Json).unwrap_or(webhook.events.clone()),
+                ..webhook.clone()
+            };
+            store.webhooks.insert(id, (owner_id, webhook.clone()));
+            Ok(webhook)
+        }
+        async fn delete(&self, owner_id: i32, id: i32) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            store.get(owner_id, id)?;
+            store.webhooks.remove(&id);
+            store
+                .deliveries
+                .retain(|delivery| delivery.webhook_id != id);
+            Ok(())
+        }
+        async fn deliveries(
+            &self,
+            owner_id: i32,
+            id: i32,
+        ) -> Result<Vec<WebhookDelivery>, RepositoryError> {
+            let store = self.read_store_ref();
+            store.get(owner_id, id)?;
+            let deliveries = store
+                .deliveries
+                .iter()
+                .rev()
+                .filter(|delivery| delivery.webhook_id == id)
+                .take(DELIVERY_LOG_LIMIT as usize)
+                .cloned()
+                .collect();
+            Ok(deliveries)
+        }
+        async fn enqueue(
+            &self,
+            owner_id: i32,
+            event: ChangeKind,
+            payload: Value,
+        ) -> Result<u64, RepositoryError> {
+            let mut store = self.write_store_ref();
+            let now = Utc::now();
+            let mut webhook_ids: Vec<i32> = store
+                .webhooks
+                .values()
+                .filter(|(owner, webhook)| *owner == owner_id && webhook.events.contains(&event))
+                .map(|(_, webhook)| webhook.id)
+                .collect();
+            webhook_ids.sort();
+            for webhook_id in webhook_ids.iter() {
+                let id = store.deliveries.last().map_or(0, |delivery| delivery.id) + 1;
+                let delivery = WebhookDelivery {
+                    id,
+                    webhook_id: *webhook_id,
+                    event,
+                    payload: Json(payload.clone()),
+                    state: DeliveryState::Pending,
+                    attempts: 0,
+                    response_status: None,
+                    last_error: None,
+                    next_attempt_at: Some(now),
+                    created_at: now,
+                    updated_at: now,
+                };
+                store.deliveries.push(delivery);
+            }
+            Ok(webhook_ids.len() as u64)
+        }
+        async fn due(
+            &self,
+            now: DateTime<Utc>,
+            limit: i64,
+        ) -> Result<Vec<DueDelivery>, RepositoryError> {
+            let store = self.read_store_ref();
+            let mut due: Vec<DueDelivery> = store
+                .deliveries
+                .iter()
+                .filter(|delivery| {
+                    delivery.state == DeliveryState::Pending
+                        && delivery.next_attempt_at.is_some_and(|at| at <= now)
+                })
+                .map(|delivery| {
+                    let (_, webhook) = &store.webhooks[&delivery.webhook_id];
+                    DueDelivery {
+                        delivery: delivery.clone(),
+                        url: webhook.url.clone(),
+                        secret: webhook.secret.clone(),
+                    }
+                })
+                .collect();
+            due.sort_by_key(|due| (due.delivery.next_attempt_at, due.delivery.id));
+            due.truncate(limit as usize);
+            Ok(due)
+        }
+        async fn record_attempt(
+            &self,
+            id: i32,
+            attempt: DeliveryAttempt,
+        ) -> Result<(), RepositoryError> {
+            let mut store = self.write_store_ref();
+            if let Some(delivery) = store
+                .deliveries
+                .iter_mut()
+                .find(|delivery| delivery.id == id)
+            {
+                delivery.attempts += 1;
+                delivery.state = attempt.state;
+                delivery.response_status = attempt.response_status;
+                delivery.last_error = attempt.error;
+                delivery.next_attempt_at = attempt.next_attempt_at;
+                delivery.updated_at = attempt.at;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,1102 @@
+use crate::recurrence::Recurrence;
+use crate::repositories::{
+    label::LabelRepository,
+    project::ProjectRepository,
+    todo::{
+        CreateTodo, Priority, Todo, TodoOperation, TodoOperationResult, TodoQuery, TodoRepository,
+    },
+    RepositoryError,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+// Columns added later come last, so readers that go by position keep working.
+const CSV_COLUMNS: [&str; 12] = [
+    "id",
+    "text",
+    "completed",
+    "priority",
+    "due_at",
+    "project_id",
+    "labels",
+    "created_at",
+    "completed_at",
+    "parent_id",
+    "auto_complete",
+    "recurrence",
+];
+
+/// How todos are written by `GET /todos/export` and read by `POST /todos/import`.
+/// Projects, labels and parents are referred to by id. A parent is first looked
+/// for by `id` among the imported todos, which get new ids; projects, labels and
+/// other parents have to exist.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// RFC 4180 with a header row, labels as space-separated ids.
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+    /// todo.txt, priorities `(A)` to `(D)` from urgent to low, with `id:`,
+    /// `due:`, `project:`, `labels:`, `parent:`, `auto_complete:` and `rrule:`
+    /// tags.
+    Todotxt,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Jsonl => "application/x-ndjson",
+            Format::Todotxt => "text/plain; charset=utf-8",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Format::Csv => "todos.csv",
+            Format::Jsonl => "todos.jsonl",
+            Format::Todotxt => "todo.txt",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    pub format: Format,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Validate, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    pub format: Format,
+    /// Only check the lines, without importing them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A todo as exported. An import only needs `text`, reads `id` only to find the
+/// parents of other lines, and ignores `created_at`. A completed todo without `completed_at` counts as completed at
+/// the time of the import.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct Record {
+    id: Option<i32>,
+    text: String,
+    #[serde(default)]
+    completed: bool,
+    priority: Option<Priority>,
+    due_at: Option<DateTime<Utc>>,
+    project_id: Option<i32>,
+    #[serde(default)]
+    labels: Vec<i32>,
+    created_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+    parent_id: Option<i32>,
+    #[serde(default)]
+    auto_complete: bool,
+    recurrence: Option<Recurrence>,
+}
+
+impl From<&Todo> for Record {
+    fn from(todo: &Todo) -> Self {
+        Self {
+            id: Some(todo.id()),
+            text: todo.text().to_string(),
+            completed: todo.completed(),
+            priority: Some(todo.priority()),
+            due_at: todo.due_at(),
+            project_id: todo.project_id(),
+            labels: todo.labels().iter().map(|label| label.id).collect(),
+            created_at: Some(todo.created_at()),
+            completed_at: todo.completed_at(),
+            parent_id: todo.parent_id(),
+            auto_complete: todo.auto_complete(),
+            recurrence: todo.recurrence().cloned(),
+        }
+    }
+}
+
+/// A line of an import that is ready to be created. A parent from the same
+/// import is referred to by the position of its entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub line: usize,
+    pub todo: CreateTodo,
+}
+
+/// Why a line cannot be imported. Lines count from 1.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct LineError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// The outcome of `POST /todos/import`.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// How many todos were imported, or would be without `dry_run`.
+    pub count: usize,
+    /// The imported todos, empty for a dry run.
+    pub todos: Vec<Todo>,
+}
+
+/// An import that failed in the repository.
+#[derive(Debug)]
+pub struct ImportError {
+    /// The line that failed, `None` when it was not a single one.
+    pub line: Option<usize>,
+    pub error: RepositoryError,
+}
+
+/// All todos of the owner in `format`, read from the repository a page at a
+/// time as the stream is consumed.
+pub fn export<T: TodoRepository>(
+    repository: Arc<T>,
+    owner_id: i32,
+    format: Format,
+) -> impl Stream<Item = Result<String, RepositoryError>> {
+    let (sender, receiver) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut chunk = match format {
+            Format::Csv => csv_line(CSV_COLUMNS.map(String::from)),
+            Format::Jsonl | Format::Todotxt => String::new(),
+        };
+        let mut cursor = None;
+        loop {
+            let page = match repository.all(owner_id, TodoQuery::by_id(cursor)).await {
+                Ok(page) => page,
+                Err(e) => {
+                    tracing::error!("failed to export todos: {}", e);
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+            for todo in page.items() {
+                chunk.push_str(&encode(format, &Record::from(todo)));
+            }
+            // Fails once the client is gone.
+            if sender.send(Ok(std::mem::take(&mut chunk))).await.is_err() {
+                return;
+            }
+            cursor = page.next_cursor();
+            if cursor.is_none() {
+                return;
+            }
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+fn encode(format: Format, record: &Record) -> String {
+    match format {
+        Format::Csv => csv_line([
+            record.id.map(|id| id.to_string()).unwrap_or_default(),
+            record.text.clone(),
+            record.completed.to_string(),
+            record.priority.unwrap_or_default().name().to_string(),
+            record.due_at.map(timestamp).unwrap_or_default(),
+            record
+                .project_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            join(&record.labels, " "),
+            record.created_at.map(timestamp).unwrap_or_default(),
+            record.completed_at.map(timestamp).unwrap_or_default(),
+            record
+                .parent_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            record.auto_complete.to_string(),
+            record
+                .recurrence
+                .as_ref()
+                .map(Recurrence::to_string)
+                .unwrap_or_default(),
+        ]),
+        Format::Jsonl => serde_json::to_string(record).unwrap() + "\n",
+        Format::Todotxt => todotxt_line(record) + "\n",
+    }
+}
+
+fn csv_line(fields: [String; 12]) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(vec![]);
+    writer.write_record(fields).unwrap();
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+// `x 2024-05-02 2024-05-01 text (...) pri:B` for a completed todo, `(B) 2024-05-01
+// text (...)` for an open one. Line breaks in the text become spaces.
+fn todotxt_line(record: &Record) -> String {
+    let mut words = vec![];
+    let priority = priority_letter(record.priority.unwrap_or_default());
+    if record.completed {
+        words.push("x".to_string());
+        words.extend(record.completed_at.map(date));
+    } else {
+        words.push(format!("({})", priority));
+    }
+    words.extend(record.created_at.map(date));
+    words.extend(record.text.split_whitespace().map(String::from));
+    if let Some(id) = record.id {
+        words.push(format!("id:{}", id));
+    }
+    if record.completed {
+        words.push(format!("pri:{}", priority));
+    }
+    if let Some(due_at) = record.due_at {
+        words.push(format!("due:{}", due(due_at)));
+    }
+    if let Some(project_id) = record.project_id {
+        words.push(format!("project:{}", project_id));
+    }
+    if !record.labels.is_empty() {
+        words.push(format!("labels:{}", join(&record.labels, ",")));
+    }
+    if let Some(parent_id) = record.parent_id {
+        words.push(format!("parent:{}", parent_id));
+    }
+    if record.auto_complete {
+        words.push("auto_complete:true".to_string());
+    }
+    if let Some(recurrence) = &record.recurrence {
+        words.push(format!("rrule:{}", recurrence));
+    }
+    words.join(" ")
+}
+
+fn priority_letter(priority: Priority) -> char {
+    match priority {
+        Priority::Urgent => 'A',
+        Priority::High => 'B',
+        Priority::Normal => 'C',
+        Priority::Low => 'D',
+    }
+}
+
+// Letters past `D` are all low, like todos without a priority are normal.
+fn letter_priority(letter: &str) -> Option<Priority> {
+    match letter {
+        "A" => Some(Priority::Urgent),
+        "B" => Some(Priority::High),
+        "C" => Some(Priority::Normal),
+        _ if letter.len() == 1 && letter.chars().all(|c| c.is_ascii_uppercase()) => {
+            Some(Priority::Low)
+        }
+        _ => None,
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn date(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d").to_string()
+}
+
+// Due dates at midnight are written as a plain date.
+fn due(at: DateTime<Utc>) -> String {
+    if at.time() == NaiveTime::MIN {
+        date(at)
+    } else {
+        timestamp(at)
+    }
+}
+
+fn parse_due(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(due) = DateTime::parse_from_rfc3339(value) {
+        return Ok(due.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+        .map_err(|_| format!("expected YYYY-MM-DD or RFC 3339, got {}", value))
+}
+
+fn parse_number(name: &str, value: &str) -> Result<i32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a number, got {}", name, value))
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be true or false, got {}", name, value))
+}
+
+fn parse_recurrence(value: &str) -> Result<Recurrence, String> {
+    value
+        .parse()
+        .map_err(|e| format!("invalid recurrence: {}", e))
+}
+
+fn parse_labels(value: &str, separator: impl Fn(char) -> bool) -> Result<Vec<i32>, String> {
+    value
+        .split(separator)
+        .filter(|id| !id.is_empty())
+        .map(|id| parse_number("label", id))
+        .collect()
+}
+
+fn join(ids: &[i32], separator: &str) -> String {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    ids.join(separator)
+}
+
+/// Reads every line of `input`, checking each like a `POST /todos` body. Either
+/// all lines can be imported, or the errors of all that cannot are returned.
+/// Blank lines are skipped. The entries come in the order they are created in,
+/// which is the one of the lines except that a parent comes before its todos.
+pub fn parse(format: Format, input: &str) -> Result<Vec<Entry>, Vec<LineError>> {
+    let records = match format {
+        Format::Csv => decode_csv(input),
+        Format::Jsonl => decode_lines(input, |line| {
+            serde_json::from_str(line).map_err(|e| e.to_string())
+        }),
+        Format::Todotxt => decode_lines(input, decode_todotxt),
+    };
+
+    let mut lines = vec![];
+    let mut errors = vec![];
+    for (line, record) in records {
+        let read = record.and_then(|record| Ok((record.id, record.parent_id, create(record)?)));
+        match read {
+            Ok(read) => lines.push((line, read)),
+            Err(message) => errors.push(LineError { line, message }),
+        }
+    }
+
+    let mut ids = HashMap::new();
+    for (index, (line, (id, _, _))) in lines.iter().enumerate() {
+        let Some(id) = id else {
+            continue;
+        };
+        if let Some(&first) = ids.get(id) {
+            let (first_line, _) = lines[first];
+            errors.push(LineError {
+                line: *line,
+                message: format!("id {} is already on line {}", id, first_line),
+            });
+        } else {
+            ids.insert(*id, index);
+        }
+    }
+    let parent_of = |index: usize| {
+        let (_, (_, parent_id, _)) = lines[index];
+        parent_id.and_then(|id| ids.get(&id).copied())
+    };
+
+    // Each line is created after the chain of its parents in the file.
+    let mut positions: Vec<Option<usize>> = vec![None; lines.len()];
+    let mut order = vec![];
+    for (start, (line, (_, parent_id, _))) in lines.iter().enumerate() {
+        let mut chain = vec![];
+        let mut seen = HashSet::new();
+        let mut next = Some(start);
+        while let Some(index) = next.filter(|&index| positions[index].is_none()) {
+            if !seen.insert(index) {
+                errors.push(LineError {
+                    line: *line,
+                    message: format!(
+                        "parent would create a cycle, id is {}",
+                        parent_id.unwrap_or_default()
+                    ),
+                });
+                chain.clear();
+                break;
+            }
+            chain.push(index);
+            next = parent_of(index);
+        }
+        for index in chain.into_iter().rev() {
+            positions[index] = Some(order.len());
+            order.push(index);
+        }
+    }
+
+    if !errors.is_empty() {
+        errors.sort_by_key(|error| error.line);
+        return Err(errors);
+    }
+    Ok(order
+        .into_iter()
+        .map(|index| {
+            let (line, (_, parent_id, todo)) = lines[index].clone();
+            let todo = match (parent_of(index), parent_id) {
+                (Some(parent), _) => todo.below_operation(positions[parent].unwrap()),
+                (None, Some(parent_id)) => todo.with_parent(parent_id),
+                (None, None) => todo,
+            };
+            Entry { line, todo }
+        })
+        .collect())
+}
+
+// Everything of the record but its parent, which depends on the other lines.
+fn create(record: Record) -> Result<CreateTodo, String> {
+    let mut todo = CreateTodo::new(record.text, record.labels);
+    if let Some(priority) = record.priority {
+        todo = todo.with_priority(priority);
+    }
+    if let Some(due_at) = record.due_at {
+        todo = todo.with_due_at(due_at);
+    }
+    if let Some(project_id) = record.project_id {
+        todo = todo.in_project(project_id);
+    }
+    if record.auto_complete {
+        todo = todo.with_auto_complete(true);
+    }
+    if let Some(recurrence) = record.recurrence {
+        todo = todo.with_recurrence(recurrence);
+    }
+    if record.completed {
+        todo = todo.completed_at(record.completed_at.unwrap_or_else(Utc::now));
+    }
+    todo.validate()
+        .map_err(|e| format!("Validation error: [{}]", e).replace('\n', ", "))?;
+    Ok(todo)
+}
+
+/// The lines of `entries` that refer to a project, label or parent outside the
+/// import that the owner does not have. Checked before a dry run as well, so it
+/// fails the same way as the import would.
+pub async fn check<T, P, L>(
+    todos: &T,
+    projects: &P,
+    labels: &L,
+    owner_id: i32,
+    entries: &[Entry],
+) -> Result<Vec<LineError>, RepositoryError>
+where
+    T: TodoRepository,
+    P: ProjectRepository,
+    L: LabelRepository,
+{
+    let project_ids: HashSet<i32> = projects
+        .all(owner_id)
+        .await?
+        .into_iter()
+        .map(|project| project.id)
+        .collect();
+    let label_ids: HashSet<i32> = labels
+        .all(owner_id)
+        .await?
+        .into_iter()
+        .map(|label| label.id)
+        .collect();
+    let mut parents = HashMap::new();
+
+    let mut errors = vec![];
+    for entry in entries {
+        let todo = &entry.todo;
+        let mut message = None;
+        if let Some(id) = todo.project_id().filter(|id| !project_ids.contains(id)) {
+            message = Some(format!("project not found, id is {}", id));
+        } else if let Some(id) = todo.labels().iter().find(|id| !label_ids.contains(id)) {
+            message = Some(format!("label not found, id is {}", id));
+        } else if let Some(id) = todo.parent_id() {
+            let found = match parents.get(&id) {
+                Some(&found) => found,
+                None => {
+                    let found = match todos.find(owner_id, id).await {
+                        Ok(_) => true,
+                        Err(RepositoryError::NotFound(_)) => false,
+                        Err(e) => return Err(e),
+                    };
+                    parents.insert(id, found);
+                    found
+                }
+            };
+            if !found {
+                message = Some(format!("parent not found, id is {}", id));
+            }
+        }
+        if let Some(message) = message {
+            errors.push(LineError {
+                line: entry.line,
+                message,
+            });
+        }
+    }
+    errors.sort_by_key(|error| error.line);
+
+    Ok(errors)
+}
+
+fn decode_lines(
+    input: &str,
+    decode: impl Fn(&str) -> Result<Record, String>,
+) -> Vec<(usize, Result<Record, String>)> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, decode(line)))
+        .collect()
+}
+
+// Columns are found by their name in the header row, so they may come in any
+// order and all but `text` may be left out. Empty cells count as missing.
+fn decode_csv(input: &str) -> Vec<(usize, Result<Record, String>)> {
+    let mut reader = csv::ReaderBuilder::new().from_reader(input.as_bytes());
+    let columns: HashMap<String, usize> = match reader.headers() {
+        Ok(headers) => headers
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (name.trim().to_string(), index))
+            .collect(),
+        Err(e) => return vec![(1, Err(e.to_string()))],
+    };
+    if !columns.contains_key("text") {
+        return vec![(1, Err("the header has no text column".to_string()))];
+    }
+
+    reader
+        .records()
+        .map(|row| {
+            let position = match &row {
+                Ok(row) => row.position(),
+                Err(e) => e.position(),
+            };
+            let line = position.map_or(0, |position| line_at(input, position.byte()));
+            let record = row.map_err(|e| e.to_string()).and_then(|row| {
+                let cell = |name: &str| {
+                    columns
+                        .get(name)
+                        .and_then(|index| row.get(*index))
+                        .map(str::trim)
+                        .filter(|value| !value.is_empty())
+                };
+                Ok(Record {
+                    id: cell("id")
+                        .map(|value| parse_number("id", value))
+                        .transpose()?,
+                    text: cell("text").unwrap_or_default().to_string(),
+                    completed: cell("completed")
+                        .map(|value| parse_bool("completed", value))
+                        .transpose()?
+                        .unwrap_or_default(),
+                    priority: cell("priority").map(str::parse).transpose()?,
+                    due_at: cell("due_at").map(parse_due).transpose()?,
+                    project_id: cell("project_id")
+                        .map(|value| parse_number("project_id", value))
+                        .transpose()?,
+                    labels: cell("labels")
+                        .map(|value| parse_labels(value, char::is_whitespace))
+                        .transpose()?
+                        .unwrap_or_default(),
+                    created_at: None,
+                    completed_at: cell("completed_at").map(parse_due).transpose()?,
+                    parent_id: cell("parent_id")
+                        .map(|value| parse_number("parent_id", value))
+                        .transpose()?,
+                    auto_complete: cell("auto_complete")
+                        .map(|value| parse_bool("auto_complete", value))
+                        .transpose()?
+                        .unwrap_or_default(),
+                    recurrence: cell("recurrence").map(parse_recurrence).transpose()?,
+                })
+            });
+            (line, record)
+        })
+        .collect()
+}
+
+// The line a record starts on, from its offset into `input`. After a CRLF the
+// offset is the one of the LF.
+fn line_at(input: &str, byte: u64) -> usize {
+    let before = input.as_bytes().iter().take(byte as usize + 1);
+    before.filter(|&&b| b == b'\n').count() + 1
+}
+
+// Words that are not one of the known `key:value` tags make up the text.
+fn decode_todotxt(line: &str) -> Result<Record, String> {
+    let mut words = line.split_whitespace().peekable();
+    let is_date = |word: &&str| NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok();
+    let completed = words.next_if_eq(&"x").is_some();
+    let completed_at = if completed {
+        words.next_if(is_date).map(parse_due).transpose()?
+    } else {
+        None
+    };
+    // Any other word in parentheses is part of the text, e.g. `(call) mum`.
+    let bracketed = |word: &str| {
+        word.strip_prefix('(')?
+            .strip_suffix(')')
+            .and_then(letter_priority)
+    };
+    let mut priority = words
+        .next_if(|word| bracketed(word).is_some())
+        .and_then(bracketed);
+    words.next_if(is_date);
+
+    let mut text = vec![];
+    let mut id = None;
+    let mut due_at = None;
+    let mut project_id = None;
+    let mut labels = vec![];
+    let mut parent_id = None;
+    let mut auto_complete = false;
+    let mut recurrence = None;
+    for word in words {
+        match word.split_once(':') {
+            Some(("id", value)) => id = Some(parse_number("id", value)?),
+            Some(("due", value)) => due_at = Some(parse_due(value)?),
+            Some(("project", value)) => project_id = Some(parse_number("project", value)?),
+            Some(("labels", value)) => labels = parse_labels(value, |c| c == ',')?,
+            Some(("parent", value)) => parent_id = Some(parse_number("parent", value)?),
+            Some(("auto_complete", value)) => auto_complete = parse_bool("auto_complete", value)?,
+            Some(("rrule", value)) => recurrence = Some(parse_recurrence(value)?),
+            Some(("pri", value)) => {
+                priority = Some(
+                    letter_priority(value)
+                        .ok_or_else(|| format!("pri must be a letter, got {}", value))?,
+                )
+            }
+            _ => text.push(word),
+        }
+    }
+
+    Ok(Record {
+        id,
+        text: text.join(" "),
+        completed,
+        priority,
+        due_at,
+        project_id,
+        labels,
+        created_at: None,
+        completed_at,
+        parent_id,
+        auto_complete,
+        recurrence,
+    })
+}
+
+/// Creates the todos of `entries` in one batch, all or none of them. Completed
+/// todos are created completed, so a recurring one does not spawn its next
+/// occurrence.
+pub async fn import<T: TodoRepository>(
+    repository: &T,
+    owner_id: i32,
+    entries: Vec<Entry>,
+) -> Result<Vec<Todo>, ImportError> {
+    let lines: Vec<usize> = entries.iter().map(|entry| entry.line).collect();
+    let operations = entries
+        .into_iter()
+        .map(|entry| TodoOperation::Create { todo: entry.todo })
+        .collect();
+    let created = repository
+        .batch(owner_id, operations)
+        .await
+        .map_err(|error| ImportError {
+            line: error.index.map(|index| lines[index]),
+            error: error.error,
+        })?;
+
+    Ok(created
+        .into_iter()
+        .filter_map(|result| match result {
+            TodoOperationResult::Create { todo } => Some(todo),
+            _ => None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        test_utils::{connect_sqlite, find_or_create_user},
+        todo::{test_utils::TodoRepositoryForMemory, TodoRepositoryForSqlite, UpdateTodo},
+        user::UserRepositoryForSqlite,
+    };
+    use tokio_stream::StreamExt;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn record() -> Record {
+        Record {
+            id: Some(7),
+            text: "buy \"oat\", milk".to_string(),
+            completed: true,
+            priority: Some(Priority::High),
+            due_at: Some(at("2024-05-03T00:00:00Z")),
+            project_id: Some(2),
+            labels: vec![1, 3],
+            created_at: Some(at("2024-05-01T08:30:00Z")),
+            completed_at: Some(at("2024-05-02T18:00:00Z")),
+            parent_id: Some(5),
+            auto_complete: true,
+            recurrence: Some("FREQ=WEEKLY;BYDAY=SA".parse().unwrap()),
+        }
+    }
+
+    fn entry(line: usize, record: &Record) -> Entry {
+        let todo = CreateTodo::new(record.text.clone(), record.labels.clone())
+            .with_priority(record.priority.unwrap())
+            .with_due_at(record.due_at.unwrap())
+            .in_project(record.project_id.unwrap())
+            .with_parent(record.parent_id.unwrap())
+            .with_auto_complete(record.auto_complete)
+            .with_recurrence(record.recurrence.clone().unwrap())
+            .completed_at(record.completed_at.unwrap());
+        Entry { line, todo }
+    }
+
+    #[test]
+    fn should_write_each_format() {
+        let record = record();
+        assert_eq!(
+            encode(Format::Csv, &record),
+            "7,\"buy \"\"oat\"\", milk\",true,high,2024-05-03T00:00:00Z,2,1 3,\
+             2024-05-01T08:30:00Z,2024-05-02T18:00:00Z,5,true,FREQ=WEEKLY;BYDAY=SA\r\n"
+        );
+        assert_eq!(
+            encode(Format::Jsonl, &record),
+            concat!(
+                r#"{"id":7,"text":"buy \"oat\", milk","completed":true,"priority":"high","#,
+                r#""due_at":"2024-05-03T00:00:00Z","project_id":2,"labels":[1,3],"#,
+                r#""created_at":"2024-05-01T08:30:00Z","completed_at":"2024-05-02T18:00:00Z","#,
+                r#""parent_id":5,"auto_complete":true,"recurrence":"FREQ=WEEKLY;BYDAY=SA"}"#,
+                "\n"
+            )
+        );
+        assert_eq!(
+            encode(Format::Todotxt, &record),
+            "x 2024-05-02 2024-05-01 buy \"oat\", milk id:7 pri:B due:2024-05-03 project:2 \
+             labels:1,3 parent:5 auto_complete:true rrule:FREQ=WEEKLY;BYDAY=SA\n"
+        );
+
+        let open = Record {
+            completed: false,
+            priority: Some(Priority::Normal),
+            due_at: Some(at("2024-05-03T09:15:00Z")),
+            project_id: None,
+            labels: vec![],
+            parent_id: None,
+            auto_complete: false,
+            recurrence: None,
+            ..record
+        };
+        assert_eq!(
+            encode(Format::Todotxt, &open),
+            "(C) 2024-05-01 buy \"oat\", milk id:7 due:2024-05-03T09:15:00Z\n"
+        );
+    }
+
+    #[test]
+    fn should_read_what_was_written() {
+        let record = record();
+        for format in [Format::Csv, Format::Jsonl, Format::Todotxt] {
+            let mut input = match format {
+                Format::Csv => csv_line(CSV_COLUMNS.map(String::from)),
+                _ => String::new(),
+            };
+            input.push_str(&encode(format, &record));
+            let next = Record {
+                id: Some(8),
+                ..record.clone()
+            };
+            input.push_str(&encode(format, &next));
+
+            // todo.txt only keeps the day of the completion.
+            let read = match format {
+                Format::Todotxt => Record {
+                    completed_at: Some(at("2024-05-02T00:00:00Z")),
+                    ..record.clone()
+                },
+                _ => record.clone(),
+            };
+            let second_line = if format == Format::Csv { 3 } else { 2 };
+            assert_eq!(
+                parse(format, &input),
+                Ok(vec![
+                    entry(second_line - 1, &read),
+                    entry(second_line, &read)
+                ]),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn should_read_partial_rows() {
+        let csv = "labels,text\r\n,walk dog\r\n\"2 4\",feed cat\r\n";
+        assert_eq!(
+            parse(Format::Csv, csv),
+            Ok(vec![
+                Entry {
+                    line: 2,
+                    todo: CreateTodo::new("walk dog".to_string(), vec![]),
+                },
+                Entry {
+                    line: 3,
+                    todo: CreateTodo::new("feed cat".to_string(), vec![2, 4]),
+                },
+            ])
+        );
+
+        let todotxt = "\n(Q) call mom\nwater plants\n(call) mum\n";
+        let todos: Vec<CreateTodo> = parse(Format::Todotxt, todotxt)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.todo)
+            .collect();
+        assert_eq!(
+            todos,
+            [
+                CreateTodo::new("call mom".to_string(), vec![]).with_priority(Priority::Low),
+                CreateTodo::new("water plants".to_string(), vec![]),
+                CreateTodo::new("(call) mum".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_report_errors_per_line() {
+        let long = "a".repeat(101);
+        let jsonl = format!(
+            "{{\"text\": \"fine\"}}\n{{\"text\": \"\"}}\nnot json\n\n{{\"text\": \"{}\"}}\n",
+            long
+        );
+        let errors = parse(Format::Jsonl, &jsonl).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, [2, 3, 5]);
+        assert!(errors[0].message.contains("Cannot be empty"));
+        assert!(errors[2].message.contains("Over text length"));
+
+        let csv = "text,priority,due_at\r\nok,,\r\nbad,highest,\r\nlate,,tomorrow\r\n";
+        let errors = parse(Format::Csv, csv).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                LineError {
+                    line: 3,
+                    message: "unknown priority highest, expected low, normal, high or urgent"
+                        .to_string(),
+                },
+                LineError {
+                    line: 4,
+                    message: "expected YYYY-MM-DD or RFC 3339, got tomorrow".to_string(),
+                },
+            ]
+        );
+
+        let errors = parse(Format::Csv, "name\r\nwalk dog\r\n").unwrap_err();
+        assert_eq!(errors[0].line, 1);
+
+        let errors = parse(Format::Todotxt, "ok\nx due:soon\n").unwrap_err();
+        assert_eq!(errors[0].line, 2);
+    }
+
+    #[test]
+    fn should_create_parents_from_the_file_first() {
+        let jsonl = concat!(
+            r#"{"id": 3, "text": "book hotel", "parent_id": 1}"#,
+            "\n",
+            r#"{"id": 1, "text": "plan trip"}"#,
+            "\n",
+            r#"{"id": 4, "text": "pack", "parent_id": 9}"#,
+            "\n",
+        );
+        assert_eq!(
+            parse(Format::Jsonl, jsonl),
+            Ok(vec![
+                Entry {
+                    line: 2,
+                    todo: CreateTodo::new("plan trip".to_string(), vec![]),
+                },
+                Entry {
+                    line: 1,
+                    todo: CreateTodo::new("book hotel".to_string(), vec![]).below_operation(0),
+                },
+                Entry {
+                    line: 3,
+                    todo: CreateTodo::new("pack".to_string(), vec![]).with_parent(9),
+                },
+            ])
+        );
+
+        let todotxt = "a id:1 parent:2\nb id:2 parent:1\nc id:1\n";
+        assert_eq!(
+            parse(Format::Todotxt, todotxt),
+            Err(vec![
+                LineError {
+                    line: 1,
+                    message: "parent would create a cycle, id is 2".to_string(),
+                },
+                LineError {
+                    line: 2,
+                    message: "parent would create a cycle, id is 1".to_string(),
+                },
+                LineError {
+                    line: 3,
+                    message: "id 1 is already on line 1".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn should_import_an_export_into_an_empty_account() {
+        let pool = connect_sqlite().await;
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+        let users = UserRepositoryForSqlite::new(pool);
+        let owner_id = find_or_create_user(&users, "export@example.com").await;
+        let pack = repository
+            .create(owner_id, CreateTodo::new("pack".to_string(), vec![]))
+            .await
+            .unwrap();
+        let trip = repository
+            .create(owner_id, CreateTodo::new("plan trip".to_string(), vec![]))
+            .await
+            .unwrap();
+        let hotel = CreateTodo::new("book hotel".to_string(), vec![]).with_parent(trip.id());
+        repository.create(owner_id, hotel).await.unwrap();
+        // Moved below a todo created after it, so the parent comes later.
+        repository
+            .update(
+                owner_id,
+                pack.id(),
+                UpdateTodo::move_below(Some(trip.id())),
+                None,
+            )
+            .await
+            .unwrap();
+
+        for format in [Format::Csv, Format::Jsonl, Format::Todotxt] {
+            let output: Vec<String> = export(Arc::new(repository.clone()), owner_id, format)
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            let email = format!("{}@example.com", format.file_name());
+            let other_id = find_or_create_user(&users, &email).await;
+            let entries = parse(format, &output.concat()).unwrap();
+            let todos = import(&repository, other_id, entries).await.unwrap();
+
+            let texts: Vec<&str> = todos.iter().map(|todo| todo.text()).collect();
+            assert_eq!(texts, ["plan trip", "pack", "book hotel"], "{:?}", format);
+            assert_ne!(todos[0].id(), trip.id());
+            assert_eq!(todos[0].parent_id(), None);
+            assert_eq!(todos[1].parent_id(), Some(todos[0].id()));
+            assert_eq!(todos[2].parent_id(), Some(todos[0].id()));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_import_and_export_all_todos() {
+        let repository = TodoRepositoryForMemory::new(vec![]);
+        let input: String = (1..=150)
+            .map(|n| {
+                let done = if n % 50 == 0 { "x " } else { "" };
+                format!("{}todo {}\n", done, n)
+            })
+            .collect();
+        let entries = parse(Format::Todotxt, &input).unwrap();
+        let todos = import(&repository, 1, entries).await.unwrap();
+        assert_eq!(todos.len(), 150);
+        assert_eq!(todos.iter().filter(|todo| todo.completed()).count(), 3);
+
+        let output: Vec<String> = export(Arc::new(repository), 1, Format::Jsonl)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(output.len(), 2, "read in pages of 100");
+        let texts: Vec<String> = output
+            .concat()
+            .lines()
+            .map(|line| serde_json::from_str::<Record>(line).unwrap().text)
+            .collect();
+        let expected: Vec<String> = (1..=150).map(|n| format!("todo {}", n)).collect();
+        assert_eq!(texts, expected);
+    }
+
+    #[tokio::test]
+    async fn should_keep_parents_and_recurrence() {
+        let repository = TodoRepositoryForMemory::new(vec![]);
+        let parent = repository
+            .create(1, CreateTodo::new("plan trip".to_string(), vec![]))
+            .await
+            .unwrap();
+        let input = format!(
+            "book hotel parent:{} auto_complete:true rrule:FREQ=DAILY;COUNT=2\n",
+            parent.id()
+        );
+        let entries = parse(Format::Todotxt, &input).unwrap();
+        let todos = import(&repository, 1, entries).await.unwrap();
+        assert_eq!(todos[0].parent_id(), Some(parent.id()));
+
+        let output: Vec<String> = export(Arc::new(repository), 1, Format::Jsonl)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let records: Vec<Record> = output
+            .concat()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records[1].parent_id, Some(parent.id()));
+        assert!(records[1].auto_complete);
+        assert_eq!(
+            records[1].recurrence,
+            Some("FREQ=DAILY;COUNT=2".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn should_import_all_or_nothing() {
+        let pool = connect_sqlite().await;
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+        let owner_id =
+            find_or_create_user(&UserRepositoryForSqlite::new(pool), "import@example.com").await;
+        let entries = parse(Format::Todotxt, "fine\n\nelsewhere project:9\n").unwrap();
+        let error = import(&repository, owner_id, entries).await.unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert!(matches!(error.error, RepositoryError::Validation(_)));
+        let page = repository
+            .all(owner_id, TodoQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.total(), 0);
+    }
+
+    #[tokio::test]
+    async fn should_import_completed_todos_as_they_were() {
+        let pool = connect_sqlite().await;
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+        let owner_id =
+            find_or_create_user(&UserRepositoryForSqlite::new(pool), "done@example.com").await;
+        let entries = parse(
+            Format::Todotxt,
+            "x 2024-05-02 water plants rrule:FREQ=DAILY
+",
+        )
+        .unwrap();
+        let todos = import(&repository, owner_id, entries).await.unwrap();
+        assert!(todos[0].completed());
+        assert_eq!(todos[0].completed_at(), Some(at("2024-05-02T00:00:00Z")));
+
+        let page = repository
+            .all(owner_id, TodoQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.total(), 1, "no next occurrence");
+    }
+}
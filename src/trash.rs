@@ -0,0 +1,68 @@
+use crate::repositories::todo::TodoRepository;
+use chrono::{Duration, Utc};
+use std::env;
+use tokio::task::JoinHandle;
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// How long deleted todos are kept in the trash, `TRASH_RETENTION_DAYS` (30 by default).
+pub fn retention() -> Duration {
+    let days = env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    Duration::days(days)
+}
+
+/// Permanently deletes todos that have been in the trash for longer than
+/// `retention`, right away and then once an hour.
+pub fn spawn_purge<T: TodoRepository>(repository: T, retention: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repository.purge_expired(Utc::now() - retention).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("purged {} todos from the trash", purged),
+                Err(e) => tracing::error!("failed to purge the trash: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::todo::{test_utils::TodoRepositoryForMemory, CreateTodo};
+
+    #[tokio::test]
+    async fn should_purge_expired_todos() {
+        let repository = TodoRepositoryForMemory::new(vec![]);
+        for text in ["old", "fresh"] {
+            let todo = repository
+                .create(1, CreateTodo::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create todo");
+            repository
+                .delete(1, todo.id(), None)
+                .await
+                .expect("failed delete todo");
+        }
+        let purged = repository
+            .purge_expired(Utc::now() - Duration::days(1))
+            .await
+            .expect("failed purge");
+        assert_eq!(purged, 0);
+
+        let handle = spawn_purge(repository.clone(), Duration::zero());
+        for _ in 0..100 {
+            if repository.trash(1).await.expect("failed trash").is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        handle.abort();
+        assert_eq!(repository.trash(1).await.expect("failed trash"), vec![]);
+    }
+}
@@ -0,0 +1,368 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use quick_xml::{
+    escape::escape,
+    events::Event,
+    name::{Namespace, ResolveResult},
+    NsReader,
+};
+
+pub const DAV: &str = "DAV:";
+pub const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+/// Apple's namespace, home of `getctag`.
+pub const CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+
+const XML: &str = "application/xml; charset=utf-8";
+// Prefixes bound on the root of every response. Property values are written with
+// them, e.g. `<d:href>/dav/</d:href>`.
+const NAMESPACES: &str = r#"xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav" xmlns:cs="http://calendarserver.org/ns/""#;
+
+/// A namespaced XML element name, such as `DAV:` `getetag`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Name {
+    pub ns: String,
+    pub local: String,
+}
+
+impl Name {
+    pub fn new(ns: &str, local: &str) -> Self {
+        Self {
+            ns: ns.to_string(),
+            local: local.to_string(),
+        }
+    }
+
+    pub fn is(&self, ns: &str, local: &str) -> bool {
+        self.ns == ns && self.local == local
+    }
+}
+
+/// What a `PROPFIND`, `PROPPATCH` or `REPORT` body asks for. Filters are not kept.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DavRequest {
+    /// The document element, e.g. `calendar-multiget`.
+    pub root: Option<Name>,
+    /// The children of `DAV:prop`.
+    pub props: Vec<Name>,
+    /// `DAV:allprop`, or `DAV:propname` which is answered the same way.
+    pub all: bool,
+    pub hrefs: Vec<String>,
+}
+
+/// Reads a request body. An empty one asks for every property, RFC 4918 section
+/// 9.1.
+pub fn parse(body: &str) -> Result<DavRequest, quick_xml::Error> {
+    let mut request = DavRequest::default();
+    if body.trim().is_empty() {
+        request.all = true;
+        return Ok(request);
+    }
+    let mut reader = NsReader::from_str(body);
+    reader.trim_text(true);
+    let mut open: Vec<Name> = vec![];
+    loop {
+        let (ns, event) = reader.read_resolved_event()?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let name = Name {
+                    ns: namespace(ns),
+                    local: String::from_utf8_lossy(e.local_name().as_ref()).into_owned(),
+                };
+                match open.last() {
+                    None if request.root.is_none() => request.root = Some(name.clone()),
+                    Some(parent) if parent.is(DAV, "prop") => request.props.push(name.clone()),
+                    _ => {}
+                }
+                if name.is(DAV, "allprop") || name.is(DAV, "propname") {
+                    request.all = true;
+                }
+                if matches!(event, Event::Start(_)) {
+                    open.push(name);
+                }
+            }
+            Event::Text(e) if open.last().is_some_and(|name| name.is(DAV, "href")) => {
+                request.hrefs.push(e.unescape()?.trim().to_string());
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Eof => return Ok(request),
+            _ => {}
+        }
+    }
+}
+
+fn namespace(ns: ResolveResult) -> String {
+    match ns {
+        ResolveResult::Bound(Namespace(ns)) => String::from_utf8_lossy(ns).into_owned(),
+        _ => String::new(),
+    }
+}
+
+/// Groups `available` properties, given as their XML content, into those found
+/// and those missing for `request`.
+pub fn propstats(
+    available: Vec<(Name, String)>,
+    request: &DavRequest,
+) -> Vec<(StatusCode, Vec<(Name, String)>)> {
+    if request.all {
+        return vec![(StatusCode::OK, available)];
+    }
+    let (mut found, mut missing) = (vec![], vec![]);
+    for name in &request.props {
+        match available.iter().find(|(candidate, _)| candidate == name) {
+            Some(prop) => found.push(prop.clone()),
+            None => missing.push((name.clone(), String::new())),
+        }
+    }
+    vec![(StatusCode::OK, found), (StatusCode::NOT_FOUND, missing)]
+}
+
+/// A `207 Multi-Status` response, RFC 4918 section 13.
+#[derive(Debug)]
+pub struct Multistatus {
+    body: String,
+}
+
+impl Multistatus {
+    pub fn new() -> Self {
+        Self {
+            body: format!(
+                r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus {}>"#,
+                NAMESPACES
+            ),
+        }
+    }
+
+    /// The properties of the resource at `path`, by status. Empty groups are left
+    /// out.
+    pub fn response(&mut self, path: &str, propstats: Vec<(StatusCode, Vec<(Name, String)>)>) {
+        self.body.push_str("<d:response>");
+        self.body.push_str(&href(path));
+        for (status, props) in propstats.into_iter().filter(|(_, props)| !props.is_empty()) {
+            self.body.push_str("<d:propstat><d:prop>");
+            for (name, value) in props {
+                self.body.push_str(&element(&name, &value));
+            }
+            self.body.push_str("</d:prop>");
+            self.body.push_str(&status_line(status));
+            self.body.push_str("</d:propstat>");
+        }
+        self.body.push_str("</d:response>");
+    }
+
+    /// A resource that could not be looked at, such as an unknown href.
+    pub fn status(&mut self, path: &str, status: StatusCode) {
+        self.body.push_str("<d:response>");
+        self.body.push_str(&href(path));
+        self.body.push_str(&status_line(status));
+        self.body.push_str("</d:response>");
+    }
+}
+
+impl Default for Multistatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntoResponse for Multistatus {
+    fn into_response(mut self) -> Response {
+        self.body.push_str("</d:multistatus>");
+        (
+            StatusCode::MULTI_STATUS,
+            [(header::CONTENT_TYPE, XML)],
+            self.body,
+        )
+            .into_response()
+    }
+}
+
+/// A `DAV:error` body naming the precondition that failed, e.g. `supported-report`.
+pub fn error(status: StatusCode, condition: Name) -> Response {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><d:error {}>{}</d:error>"#,
+        NAMESPACES,
+        element(&condition, "")
+    );
+    (status, [(header::CONTENT_TYPE, XML)], body).into_response()
+}
+
+/// `<d:href>` holding `path`, percent-encoded.
+pub fn href(path: &str) -> String {
+    format!("<d:href>{}</d:href>", escape(&encode_path(path)))
+}
+
+fn status_line(status: StatusCode) -> String {
+    format!("<d:status>HTTP/1.1 {}</d:status>", status)
+}
+
+fn element(name: &Name, content: &str) -> String {
+    let prefix = match name.ns.as_str() {
+        DAV => "d:",
+        CALDAV => "c:",
+        CALENDARSERVER => "cs:",
+        _ => "",
+    };
+    let open = match (prefix, name.ns.as_str()) {
+        ("", "") => format!(r#"{} xmlns="""#, name.local),
+        ("", ns) => format!(r#"{} xmlns="{}""#, name.local, escape(ns)),
+        _ => format!("{}{}", prefix, name.local),
+    };
+    if content.is_empty() {
+        format!("<{}/>", open)
+    } else {
+        format!("<{}>{}</{}{}>", open, content, prefix, name.local)
+    }
+}
+
+/// Percent-encodes everything but unreserved characters and `/`.
+pub fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Reverses `encode_path`. `None` if the result is not UTF-8.
+pub fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::body::to_bytes;
+
+    #[test]
+    fn should_parse_propfind() {
+        let request = parse(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <propfind xmlns="DAV:" xmlns:CS="http://calendarserver.org/ns/">
+                <prop>
+                    <resourcetype/>
+                    <CS:getctag/>
+                    <X:color xmlns:X="http://apple.com/ns/ical/"/>
+                </prop>
+            </propfind>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            DavRequest {
+                root: Some(Name::new(DAV, "propfind")),
+                props: vec![
+                    Name::new(DAV, "resourcetype"),
+                    Name::new(CALENDARSERVER, "getctag"),
+                    Name::new("http://apple.com/ns/ical/", "color"),
+                ],
+                all: false,
+                hrefs: vec![],
+            }
+        );
+
+        assert!(parse("").unwrap().all);
+        let request = parse(r#"<d:propfind xmlns:d="DAV:"><d:allprop/></d:propfind>"#);
+        assert!(request.unwrap().all);
+        assert!(parse("<propfind><prop></propfind>").is_err());
+    }
+
+    #[test]
+    fn should_parse_multiget() {
+        let request = parse(
+            r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+                <d:prop><d:getetag/><c:calendar-data/></d:prop>
+                <d:href>/dav/calendars/todos/a%20b.ics</d:href>
+                <d:href>/dav/calendars/todos/c&amp;d.ics</d:href>
+            </c:calendar-multiget>"#,
+        )
+        .unwrap();
+        assert_eq!(request.root, Some(Name::new(CALDAV, "calendar-multiget")));
+        assert_eq!(
+            request.props,
+            [
+                Name::new(DAV, "getetag"),
+                Name::new(CALDAV, "calendar-data")
+            ]
+        );
+        assert_eq!(
+            request.hrefs,
+            [
+                "/dav/calendars/todos/a%20b.ics",
+                "/dav/calendars/todos/c&d.ics"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_write_multistatus() {
+        let request = DavRequest {
+            props: vec![Name::new(DAV, "getetag"), Name::new("urn:x", "color")],
+            ..DavRequest::default()
+        };
+        let available = vec![
+            (Name::new(DAV, "getetag"), escape("\"1-1\"").into_owned()),
+            (Name::new(DAV, "resourcetype"), String::new()),
+        ];
+        let mut multistatus = Multistatus::new();
+        multistatus.response("/dav/a b.ics", propstats(available, &request));
+        multistatus.status("/dav/missing.ics", StatusCode::NOT_FOUND);
+
+        let response = multistatus.into_response();
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?><d:multistatus {}>\
+                 <d:response><d:href>/dav/a%20b.ics</d:href>\
+                 <d:propstat><d:prop><d:getetag>&quot;1-1&quot;</d:getetag></d:prop>\
+                 <d:status>HTTP/1.1 200 OK</d:status></d:propstat>\
+                 <d:propstat><d:prop><color xmlns=\"urn:x\"/></d:prop>\
+                 <d:status>HTTP/1.1 404 Not Found</d:status></d:propstat></d:response>\
+                 <d:response><d:href>/dav/missing.ics</d:href>\
+                 <d:status>HTTP/1.1 404 Not Found</d:status></d:response></d:multistatus>",
+                NAMESPACES
+            )
+        );
+    }
+
+    #[test]
+    fn should_encode_paths() {
+        assert_eq!(encode_path("/dav/todo-1.ics"), "/dav/todo-1.ics");
+        assert_eq!(encode_path("/dav/a b+é.ics"), "/dav/a%20b%2B%C3%A9.ics");
+        assert_eq!(
+            decode_path("/dav/a%20b%2B%C3%A9.ics").as_deref(),
+            Some("/dav/a b+é.ics")
+        );
+        assert_eq!(decode_path("/dav/100%"), Some("/dav/100%".to_string()));
+        assert_eq!(decode_path("/dav/%FF"), None);
+    }
+}
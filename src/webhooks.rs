@@ -0,0 +1,512 @@
+use crate::repositories::{
+    changes::TodoChange,
+    todo::TodoRepository,
+    webhook::{DeliveryAttempt, DeliveryState, DueDelivery, WebhookRepository},
+};
+use chrono::{DateTime, Duration, Utc};
+use futures_util::{stream, StreamExt};
+use hmac::{Hmac, Mac};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+    redirect, Url,
+};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+
+const SIGNATURE_HEADER: &str = "x-webhook-signature";
+const EVENT_HEADER: &str = "x-webhook-event";
+const DELIVERY_HEADER: &str = "x-webhook-delivery";
+const BATCH_SIZE: i64 = 20;
+const CONCURRENT_DELIVERIES: usize = 8;
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often pending deliveries are looked for, how failed ones are retried and
+/// where they may go.
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryPolicy {
+    pub poll_interval: std::time::Duration,
+    /// Wait before the second attempt, doubled before each attempt after it.
+    pub base_delay: Duration,
+    /// Attempts before a delivery is given up on as dead.
+    pub max_attempts: i32,
+    /// Also deliver to loopback, private and link-local addresses. Off by
+    /// default, so webhooks cannot be used to reach into the network the server
+    /// runs in.
+    pub allow_private: bool,
+}
+
+impl Default for DeliveryPolicy {
+    /// Eight attempts over about an hour, to public addresses only.
+    fn default() -> Self {
+        Self {
+            poll_interval: std::time::Duration::from_secs(1),
+            base_delay: Duration::seconds(30),
+            max_attempts: 8,
+            allow_private: false,
+        }
+    }
+}
+
+impl DeliveryPolicy {
+    /// When to try again after `attempts` failed attempts, `None` once they are
+    /// used up.
+    pub fn next_attempt(&self, attempts: i32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        (attempts < self.max_attempts)
+            .then(|| now + self.base_delay * 2_i32.pow(attempts.max(1) as u32 - 1))
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `body` keyed with `secret`, as
+/// sent in `X-Webhook-Signature` for receivers to check.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Queues every change committed through `todos` for the webhooks of its owner.
+/// Changes published while the queue cannot keep up are lost, and logged.
+pub fn spawn_dispatch<T: TodoRepository, W: WebhookRepository>(
+    todos: &T,
+    webhooks: W,
+) -> JoinHandle<()> {
+    let mut receiver = todos.changes().subscribe(None).receiver;
+    tokio::spawn(async move {
+        loop {
+            let change = match receiver.recv().await {
+                Ok(change) => change,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::error!("webhooks missed {} todo changes", missed);
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if let Err(e) = webhooks
+                .enqueue(change.owner_id, change.kind, payload(&change))
+                .await
+            {
+                tracing::error!("failed to queue webhook deliveries: {}", e);
+            }
+        }
+    })
+}
+
+fn payload(change: &TodoChange) -> Value {
+    json!({ "event": change.kind, "todo": change.todo })
+}
+
+/// Sends the deliveries that are due every `poll_interval`, a few at a time,
+/// retrying failed ones with exponential backoff until they are delivered or
+/// dead. Redirects are not followed.
+pub fn spawn_delivery<W: WebhookRepository>(webhooks: W, policy: DeliveryPolicy) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut builder = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(redirect::Policy::none());
+        if !policy.allow_private {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build().expect("failed to build the webhook client");
+        let mut interval = tokio::time::interval(policy.poll_interval);
+        loop {
+            interval.tick().await;
+            let due = match webhooks.due(Utc::now(), BATCH_SIZE).await {
+                Ok(due) => due,
+                Err(e) => {
+                    tracing::error!("failed to look for webhook deliveries: {}", e);
+                    continue;
+                }
+            };
+            stream::iter(due)
+                .for_each_concurrent(CONCURRENT_DELIVERIES, |due| async {
+                    let id = due.delivery.id;
+                    let attempt = attempt(&client, &policy, due).await;
+                    if let Err(e) = webhooks.record_attempt(id, attempt).await {
+                        tracing::error!("failed to record webhook delivery {}: {}", id, e);
+                    }
+                })
+                .await;
+        }
+    })
+}
+
+async fn attempt(
+    client: &reqwest::Client,
+    policy: &DeliveryPolicy,
+    due: DueDelivery,
+) -> DeliveryAttempt {
+    let delivery = due.delivery;
+    let body = serde_json::to_vec(&delivery.payload).unwrap();
+    let response = match refused_ip(policy, &due.url) {
+        // Hosts given as an address are not looked up, so `PublicResolver` never
+        // gets to see them.
+        Some(ip) => Err(format!("refusing to deliver to {}", ip)),
+        None => client
+            .post(&due.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.name())
+            .header(DELIVERY_HEADER, delivery.id)
+            .header(SIGNATURE_HEADER, signature(&due.secret, &body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| error_chain(&e)),
+    };
+    let (response_status, error) = match response {
+        Ok(response) if response.status().is_success() => (Some(response.status()), None),
+        Ok(response) => (Some(response.status()), Some(response.status().to_string())),
+        Err(e) => (None, Some(e)),
+    };
+
+    let now = Utc::now();
+    let next_attempt_at = error
+        .as_ref()
+        .and_then(|_| policy.next_attempt(delivery.attempts + 1, now));
+    let state = match (&error, next_attempt_at) {
+        (None, _) => DeliveryState::Delivered,
+        (Some(_), Some(_)) => DeliveryState::Pending,
+        (Some(_), None) => DeliveryState::Dead,
+    };
+    DeliveryAttempt {
+        at: now,
+        state,
+        response_status: response_status.map(|status| status.as_u16() as i32),
+        error,
+        next_attempt_at,
+    }
+}
+
+fn refused_ip(policy: &DeliveryPolicy, url: &str) -> Option<IpAddr> {
+    if policy.allow_private {
+        return None;
+    }
+    let url = Url::parse(url).ok()?;
+    let ip: IpAddr = url.host_str()?.trim_matches(['[', ']']).parse().ok()?;
+    (!is_public(ip)).then_some(ip)
+}
+
+// The error along with its causes, which say why a request could not be sent.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Looks hosts up like the system does, but only hands out the addresses that
+/// are reachable from the internet as a whole. Checking the addresses that are
+/// connected to, not the ones a host had when it was checked, leaves no room
+/// for DNS answers that change in between.
+#[derive(Debug, Clone, Copy)]
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `ip` is outside loopback, private, shared, link-local (which holds
+/// the cloud metadata endpoint at 169.254.169.254) and other special ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                // 100.64.0.0/10, shared address space behind carrier-grade NAT.
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // fc00::/7, unique local addresses.
+                    || first & 0xfe00 == 0xfc00
+                    // fe80::/10, link-local addresses.
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        changes::ChangeKind,
+        todo::{test_utils::TodoRepositoryForMemory, CreateTodo, UpdateTodo},
+        webhook::{test_utils::WebhookRepositoryForMemory, CreateWebhook, WebhookDelivery},
+    };
+    use axum::{
+        body::Bytes,
+        http::{HeaderMap, StatusCode},
+        response::Redirect,
+        routing::post,
+        Extension, Router,
+    };
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    // Stand-in for the receiving end of a webhook: records every request and
+    // answers with the scripted statuses, then with 200.
+    #[derive(Debug, Clone, Default)]
+    struct Receiver {
+        requests: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+        statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+    }
+
+    impl Receiver {
+        async fn serve(statuses: Vec<StatusCode>) -> (Self, String) {
+            let receiver = Receiver::default();
+            receiver.statuses.lock().unwrap().extend(statuses);
+            let app = Router::new()
+                .route("/hook", post(receive))
+                .route("/moved", post(|| async { Redirect::temporary("/hook") }))
+                .layer(Extension(receiver.clone()));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/hook", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            (receiver, url)
+        }
+
+        fn requests(&self) -> Vec<(HeaderMap, Bytes)> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn receive(
+        Extension(receiver): Extension<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        receiver.requests.lock().unwrap().push((headers, body));
+        let status = receiver.statuses.lock().unwrap().pop_front();
+        status.unwrap_or(StatusCode::OK)
+    }
+
+    fn policy() -> DeliveryPolicy {
+        DeliveryPolicy {
+            poll_interval: std::time::Duration::from_millis(10),
+            base_delay: Duration::milliseconds(10),
+            max_attempts: 3,
+            // The receivers of these tests listen on loopback.
+            allow_private: true,
+        }
+    }
+
+    // Waits for the only delivery of the webhook to stop being pending.
+    async fn settled(webhooks: &WebhookRepositoryForMemory, id: i32) -> WebhookDelivery {
+        for _ in 0..500 {
+            let deliveries = webhooks.deliveries(1, id).await.unwrap();
+            if let [delivery] = &deliveries[..] {
+                if delivery.state != DeliveryState::Pending {
+                    return delivery.clone();
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("delivery did not settle in time");
+    }
+
+    #[tokio::test]
+    async fn should_deliver_signed_events_with_retries() {
+        let (receiver, url) = Receiver::serve(vec![StatusCode::INTERNAL_SERVER_ERROR]).await;
+        let todos = TodoRepositoryForMemory::new(vec![]);
+        let webhooks = WebhookRepositoryForMemory::new();
+        let webhook = webhooks
+            .create(1, CreateWebhook::new(&url, vec![ChangeKind::Created]))
+            .await
+            .unwrap();
+        let dispatch = spawn_dispatch(&todos, webhooks.clone());
+        let delivery = spawn_delivery(webhooks.clone(), policy());
+
+        let todo = todos
+            .create(1, CreateTodo::new("ship it".to_string(), vec![]))
+            .await
+            .unwrap();
+        todos
+            .update(1, todo.id(), UpdateTodo::complete(), None)
+            .await
+            .unwrap();
+        todos
+            .create(2, CreateTodo::new("not theirs".to_string(), vec![]))
+            .await
+            .unwrap();
+
+        let logged = settled(&webhooks, webhook.id).await;
+        dispatch.abort();
+        delivery.abort();
+        assert_eq!(logged.state, DeliveryState::Delivered);
+        assert_eq!(logged.attempts, 2);
+        assert_eq!(logged.response_status, Some(200));
+        assert_eq!(logged.next_attempt_at, None);
+
+        let requests = receiver.requests();
+        assert_eq!(requests.len(), 2, "the failed attempt is retried");
+        let (headers, body) = &requests[1];
+        assert_eq!(headers[EVENT_HEADER], "created");
+        assert_eq!(headers[DELIVERY_HEADER], logged.id.to_string().as_str());
+        assert_eq!(
+            headers[SIGNATURE_HEADER],
+            signature("0123456789abcdef", body).as_str()
+        );
+        let body: Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body, json!({ "event": "created", "todo": todo }));
+        assert_eq!(requests[0].1, requests[1].1);
+    }
+
+    #[tokio::test]
+    async fn should_give_up_on_dead_deliveries() {
+        let (receiver, url) = Receiver::serve(vec![StatusCode::BAD_GATEWAY; 5]).await;
+        let webhooks = WebhookRepositoryForMemory::new();
+        let webhook = webhooks
+            .create(1, CreateWebhook::new(&url, vec![ChangeKind::Deleted]))
+            .await
+            .unwrap();
+        webhooks
+            .enqueue(1, ChangeKind::Deleted, json!({}))
+            .await
+            .unwrap();
+        let delivery = spawn_delivery(webhooks.clone(), policy());
+
+        let logged = settled(&webhooks, webhook.id).await;
+        delivery.abort();
+        assert_eq!(logged.state, DeliveryState::Dead);
+        assert_eq!(logged.attempts, policy().max_attempts);
+        assert_eq!(logged.response_status, Some(502));
+        assert_eq!(logged.last_error.as_deref(), Some("502 Bad Gateway"));
+        assert_eq!(receiver.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_not_follow_redirects() {
+        let (receiver, url) = Receiver::serve(vec![]).await;
+        let webhooks = WebhookRepositoryForMemory::new();
+        let webhook = webhooks
+            .create(
+                1,
+                CreateWebhook::new(&url.replace("/hook", "/moved"), vec![ChangeKind::Deleted]),
+            )
+            .await
+            .unwrap();
+        webhooks
+            .enqueue(1, ChangeKind::Deleted, json!({}))
+            .await
+            .unwrap();
+        let delivery = spawn_delivery(webhooks.clone(), policy());
+
+        let logged = settled(&webhooks, webhook.id).await;
+        delivery.abort();
+        assert_eq!(logged.state, DeliveryState::Dead);
+        assert_eq!(logged.response_status, Some(307));
+        assert!(receiver.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_refuse_private_addresses() {
+        let (receiver, url) = Receiver::serve(vec![]).await;
+        let webhooks = WebhookRepositoryForMemory::new();
+        let mut ids = vec![];
+        for url in [url.clone(), url.replace("127.0.0.1", "localhost")] {
+            let webhook = webhooks
+                .create(1, CreateWebhook::new(&url, vec![ChangeKind::Deleted]))
+                .await
+                .unwrap();
+            ids.push(webhook.id);
+        }
+        webhooks
+            .enqueue(1, ChangeKind::Deleted, json!({}))
+            .await
+            .unwrap();
+        let policy = DeliveryPolicy {
+            max_attempts: 1,
+            allow_private: false,
+            ..policy()
+        };
+        let delivery = spawn_delivery(webhooks.clone(), policy);
+
+        for id in ids {
+            let logged = settled(&webhooks, id).await;
+            assert_eq!(logged.state, DeliveryState::Dead);
+            assert_eq!(logged.response_status, None);
+        }
+        delivery.abort();
+        assert!(receiver.requests().is_empty());
+    }
+
+    #[test]
+    fn should_tell_public_addresses_apart() {
+        for ip in ["93.184.216.34", "2606:2800:220:1::248"] {
+            assert!(is_public(ip.parse().unwrap()), "{} is public", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} is not public", ip);
+        }
+    }
+
+    #[test]
+    fn should_back_off_exponentially() {
+        let now = Utc::now();
+        let policy = DeliveryPolicy::default();
+        let delays: Vec<i64> = (1..=8)
+            .filter_map(|attempts| policy.next_attempt(attempts, now))
+            .map(|at| (at - now).num_seconds())
+            .collect();
+        assert_eq!(delays, [30, 60, 120, 240, 480, 960, 1920]);
+    }
+
+    #[test]
+    fn should_sign_with_hmac_sha256() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}